@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS strategy_budgets
+(
+    strategy                 TEXT    NOT NULL,
+    sub_strategy             TEXT,
+    capital                  NUMERIC NOT NULL,
+    max_gross_exposure       NUMERIC,
+    max_net_exposure         NUMERIC,
+    max_ticker_concentration NUMERIC
+);
+CREATE UNIQUE INDEX strategy_substrategy_budgets_idx ON strategy_budgets (strategy, COALESCE(sub_strategy, ' '));
+
+CREATE TABLE IF NOT EXISTS budget_denials
+(
+    id           UUID PRIMARY KEY,
+    intent_id    UUID NOT NULL,
+    strategy     TEXT NOT NULL,
+    sub_strategy TEXT,
+    ticker       TEXT NOT NULL,
+    reason       TEXT NOT NULL,
+    datetime     TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS risk_check_requests
+(
+    id       UUID PRIMARY KEY,
+    ticker   TEXT NOT NULL,
+    intent   TEXT NOT NULL,
+    sent_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    attempts int  NOT NULL
+);
@@ -0,0 +1,4 @@
+ALTER TABLE risk_check_requests ADD COLUMN claim_id UUID;
+ALTER TABLE claims ADD COLUMN denial_count int NOT NULL DEFAULT 0;
+ALTER TABLE claims ADD COLUMN denied_reason TEXT;
+ALTER TABLE claims ADD COLUMN retry_after TIMESTAMP WITH TIME ZONE;
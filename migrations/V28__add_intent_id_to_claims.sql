@@ -0,0 +1 @@
+ALTER TABLE claims ADD COLUMN intent_id UUID;
@@ -0,0 +1 @@
+ALTER TABLE trades ADD COLUMN cancel_requested BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS execution_policies
+(
+    strategy           TEXT    NOT NULL,
+    sub_strategy       TEXT,
+    algorithm          TEXT    NOT NULL,
+    min_notional       NUMERIC NOT NULL,
+    duration_seconds   int     NOT NULL,
+    slices             int     NOT NULL,
+    participation_rate NUMERIC,
+    display_size       int
+);
+CREATE UNIQUE INDEX strategy_substrategy_execution_policies_idx ON execution_policies (strategy, COALESCE(sub_strategy, ' '));
+
+CREATE TABLE IF NOT EXISTS executions
+(
+    id                 UUID PRIMARY KEY,
+    claim_id           UUID,
+    ticker             TEXT    NOT NULL,
+    algorithm          TEXT    NOT NULL,
+    total_quantity     int     NOT NULL,
+    remaining_quantity int     NOT NULL,
+    slices_remaining   int     NOT NULL,
+    interval_seconds   int     NOT NULL,
+    participation_rate NUMERIC,
+    display_size       int,
+    limit_price        NUMERIC,
+    stop_price         NUMERIC,
+    last_volume        NUMERIC,
+    working_id         UUID,
+    next_release_at    TIMESTAMP WITH TIME ZONE,
+    abandoned_at       TIMESTAMP WITH TIME ZONE
+);
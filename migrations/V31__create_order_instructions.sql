@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS order_instructions
+(
+    intent_id      UUID PRIMARY KEY,
+    order_kind     TEXT    NOT NULL,
+    trail_price    NUMERIC,
+    trail_percent  NUMERIC,
+    time_in_force  TEXT,
+    extended_hours BOOLEAN NOT NULL
+);
+
+ALTER TABLE dependent_trades
+    ADD COLUMN order_kind     TEXT    NOT NULL DEFAULT 'standard',
+    ADD COLUMN trail_price    NUMERIC,
+    ADD COLUMN trail_percent  NUMERIC,
+    ADD COLUMN extended_hours BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE risk_check_requests
+    ADD COLUMN extensions TEXT NOT NULL DEFAULT '{}';
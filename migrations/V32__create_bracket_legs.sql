@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS bracket_legs
+(
+    id           UUID PRIMARY KEY,
+    parent_id    UUID    NOT NULL,
+    strategy     TEXT    NOT NULL,
+    sub_strategy TEXT,
+    ticker       TEXT    NOT NULL,
+    kind         TEXT    NOT NULL,
+    price        NUMERIC NOT NULL,
+    resize_to    int,
+    claim_id     UUID
+);
+CREATE INDEX bracket_legs_parent_id_idx ON bracket_legs (parent_id);
+CREATE INDEX bracket_legs_claim_id_idx ON bracket_legs (claim_id);
+
+ALTER TABLE order_instructions
+    ADD COLUMN take_profit NUMERIC,
+    ADD COLUMN stop_loss   NUMERIC;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS trade_events
+(
+    trade_id    UUID                     NOT NULL,
+    from_status status,
+    to_status   status                   NOT NULL,
+    rejected    BOOLEAN                  NOT NULL,
+    datetime    TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX trade_events_trade_id_idx ON trade_events (trade_id);
@@ -0,0 +1,10 @@
+ALTER TABLE trades
+    ADD COLUMN filled_quantity    int     NOT NULL DEFAULT 0,
+    ADD COLUMN average_fill_price NUMERIC,
+    ADD COLUMN fees               NUMERIC NOT NULL DEFAULT 0;
+
+ALTER TABLE lots
+    ADD COLUMN fees NUMERIC NOT NULL DEFAULT 0;
+
+ALTER TABLE allocations
+    ADD COLUMN fees NUMERIC NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+ALTER TABLE trades
+    ALTER COLUMN quantity TYPE NUMERIC,
+    ALTER COLUMN pending_quantity TYPE NUMERIC,
+    ALTER COLUMN filled_quantity TYPE NUMERIC;
+
+ALTER TABLE dependent_trades
+    ALTER COLUMN qty TYPE NUMERIC,
+    ADD COLUMN notional NUMERIC;
+
+ALTER TABLE executions
+    ALTER COLUMN total_quantity TYPE NUMERIC,
+    ALTER COLUMN remaining_quantity TYPE NUMERIC;
+
+ALTER TABLE bracket_legs
+    ALTER COLUMN resize_to TYPE NUMERIC;
@@ -0,0 +1 @@
+ALTER TABLE scheduled_intents ADD COLUMN IF NOT EXISTS cancelled_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS recurring_intents
+(
+    id           UUID PRIMARY KEY,
+    strategy     TEXT NOT NULL,
+    schedule     TEXT NOT NULL,
+    intent       TEXT NOT NULL,
+    instructions TEXT NOT NULL,
+    next_run_at  TIMESTAMP WITH TIME ZONE,
+    last_run_at  TIMESTAMP WITH TIME ZONE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS queued_trades
+(
+    id           UUID PRIMARY KEY,
+    ticker       TEXT NOT NULL,
+    intent       TEXT NOT NULL,
+    extensions   TEXT NOT NULL,
+    claim_id     UUID,
+    queued_at    TIMESTAMP WITH TIME ZONE NOT NULL,
+    risk_checked BOOLEAN NOT NULL DEFAULT false
+);
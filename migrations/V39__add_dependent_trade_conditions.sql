@@ -0,0 +1,9 @@
+ALTER TABLE dependent_trades
+    ADD COLUMN trigger_condition TEXT NOT NULL DEFAULT 'on_fill',
+    ADD COLUMN trigger_quantity  NUMERIC,
+    ADD COLUMN expires_at        TIMESTAMP WITH TIME ZONE;
+CREATE INDEX IF NOT EXISTS dependent_trades_dependent_id_idx ON dependent_trades (dependent_id);
+
+ALTER TABLE order_instructions
+    ADD COLUMN trigger_condition TEXT NOT NULL DEFAULT 'on_fill',
+    ADD COLUMN trigger_quantity  NUMERIC;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS stale_trade_resolutions
+(
+    trade_id   UUID NOT NULL,
+    ticker     TEXT NOT NULL,
+    status     status NOT NULL,
+    resolution TEXT NOT NULL,
+    datetime   TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX IF NOT EXISTS stale_trade_resolutions_trade_id_idx ON stale_trade_resolutions (trade_id);
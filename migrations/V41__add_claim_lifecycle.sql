@@ -0,0 +1,26 @@
+CREATE TYPE claim_state AS ENUM ('open', 'working', 'partially_satisfied', 'satisfied', 'expired', 'cancelled', 'superseded');
+ALTER TABLE claims ADD COLUMN state claim_state NOT NULL DEFAULT 'open';
+CREATE TABLE IF NOT EXISTS claim_history
+(
+    id            UUID NOT NULL,
+    strategy      TEXT NOT NULL,
+    sub_strategy  TEXT,
+    ticker        TEXT NOT NULL,
+    amount        NUMERIC NOT NULL,
+    unit          TEXT NOT NULL,
+    limit_price   NUMERIC,
+    before        TIMESTAMP WITH TIME ZONE,
+    intent_id     UUID,
+    denial_count  int NOT NULL,
+    denied_reason TEXT,
+    retry_after   TIMESTAMP WITH TIME ZONE,
+    state         claim_state NOT NULL,
+    archived_at   TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX IF NOT EXISTS claim_history_strategy_ticker_idx ON claim_history (strategy, ticker);
+CREATE TABLE IF NOT EXISTS claim_trades
+(
+    claim_id UUID NOT NULL,
+    trade_id UUID NOT NULL,
+    PRIMARY KEY (claim_id, trade_id)
+);
@@ -0,0 +1,5 @@
+-- Claims without a sub-strategy used to share their key with claims whose sub-strategy is a single
+-- space. Existing rows are unique under the new indexes, since the old index was stricter.
+DROP INDEX IF EXISTS strategy_substrategy_claims_idx;
+CREATE UNIQUE INDEX IF NOT EXISTS claims_strategy_ticker_idx ON claims (strategy, ticker) WHERE sub_strategy IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS claims_strategy_sub_strategy_ticker_idx ON claims (strategy, sub_strategy, ticker) WHERE sub_strategy IS NOT NULL;
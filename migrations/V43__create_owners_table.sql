@@ -0,0 +1,31 @@
+CREATE TYPE owner_kind AS ENUM ('house', 'strategy');
+CREATE TYPE owner AS
+(
+    kind         owner_kind,
+    strategy     TEXT,
+    sub_strategy TEXT
+);
+CREATE TABLE IF NOT EXISTS owners
+(
+    id    SERIAL PRIMARY KEY,
+    owner owner NOT NULL UNIQUE
+);
+INSERT INTO owners (owner) VALUES (ROW('house', NULL, NULL)::owner);
+INSERT INTO owners (owner)
+SELECT DISTINCT ROW('strategy', owner, sub_owner)::owner FROM allocations WHERE owner != 'House';
+ALTER TABLE allocations ADD COLUMN owner_id int REFERENCES owners (id);
+UPDATE allocations SET owner_id = owners.id FROM owners
+WHERE ((owners.owner).kind = 'house' AND allocations.owner = 'House')
+   OR ((owners.owner).kind = 'strategy'
+   AND (owners.owner).strategy = allocations.owner
+   AND (owners.owner).sub_strategy IS NOT DISTINCT FROM allocations.sub_owner);
+DROP VIEW positions;
+ALTER TABLE allocations ALTER COLUMN owner_id SET NOT NULL;
+ALTER TABLE allocations DROP COLUMN owner;
+ALTER TABLE allocations DROP COLUMN sub_owner;
+CREATE INDEX IF NOT EXISTS allocations_owner_id_idx ON allocations (owner_id);
+CREATE VIEW positions AS
+SELECT owners.owner, ticker, sum(shares) AS shares, sum(basis) AS basis
+FROM allocations JOIN owners ON owners.id = allocations.owner_id
+GROUP BY owners.owner, ticker
+HAVING sum(shares) != 0;
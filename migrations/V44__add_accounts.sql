@@ -0,0 +1,22 @@
+ALTER TABLE trades ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE lots ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE allocations ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE claims ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE claim_history ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE risk_check_requests ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE queued_trades ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE dependent_trades ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE executions ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+CREATE TABLE IF NOT EXISTS account_routes
+(
+    strategy     TEXT NOT NULL,
+    sub_strategy TEXT,
+    account      TEXT NOT NULL
+);
+CREATE UNIQUE INDEX strategy_substrategy_account_routes_idx ON account_routes (strategy, COALESCE(sub_strategy, ' '));
+DROP VIEW positions;
+CREATE VIEW positions AS
+SELECT owners.owner, account, ticker, sum(shares) AS shares, sum(basis) AS basis
+FROM allocations JOIN owners ON owners.id = allocations.owner_id
+GROUP BY owners.owner, account, ticker
+HAVING sum(shares) != 0;
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS house_actions
+(
+    id         UUID PRIMARY KEY,
+    account    TEXT NOT NULL,
+    ticker     TEXT NOT NULL,
+    policy     TEXT NOT NULL,
+    shares     NUMERIC NOT NULL,
+    state      TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+-- A house position has at most one action waiting to be approved or applied
+CREATE UNIQUE INDEX IF NOT EXISTS house_actions_open_idx ON house_actions (account, ticker) WHERE state IN ('pending', 'approved');
+
+ALTER TYPE owner_kind ADD VALUE IF NOT EXISTS 'error_account';
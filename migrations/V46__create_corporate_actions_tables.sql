@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS corporate_actions
+(
+    id         UUID PRIMARY KEY,
+    ticker     TEXT NOT NULL,
+    action     TEXT NOT NULL,
+    applied_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE TABLE IF NOT EXISTS corporate_action_adjustments
+(
+    corporate_action_id UUID NOT NULL REFERENCES corporate_actions (id),
+    entity              TEXT NOT NULL,
+    entity_id           UUID NOT NULL,
+    ticker              TEXT NOT NULL,
+    quantity            NUMERIC,
+    adjusted_quantity   NUMERIC,
+    price               NUMERIC,
+    adjusted_price      NUMERIC,
+    cash                NUMERIC
+);
+CREATE INDEX IF NOT EXISTS corporate_action_adjustments_action_idx ON corporate_action_adjustments (corporate_action_id);
@@ -0,0 +1,38 @@
+CREATE TABLE IF NOT EXISTS cash_movements
+(
+    id          UUID PRIMARY KEY,
+    owner_id    int  NOT NULL REFERENCES owners (id),
+    account     TEXT NOT NULL,
+    kind        TEXT NOT NULL,
+    amount      NUMERIC NOT NULL,
+    transfer_id UUID,
+    description TEXT,
+    datetime    TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX IF NOT EXISTS cash_movements_owner_id_idx ON cash_movements (owner_id);
+ALTER TABLE strategy_budgets ADD COLUMN enforce_buying_power BOOLEAN NOT NULL DEFAULT FALSE;
+-- Trades move the basis of their allocations, excluding fees, which are entries of their own
+CREATE VIEW cash_ledger AS
+SELECT owners.owner, entries.account, entries.kind, entries.amount, entries.datetime, entries.reference_id
+FROM (
+    SELECT allocations.owner_id, allocations.account, 'trade' AS kind, allocations.fees - allocations.basis AS amount,
+           lots.fill_time AS datetime, allocations.id AS reference_id
+    FROM allocations LEFT JOIN lots ON lots.id = allocations.lot_id
+    UNION ALL
+    SELECT allocations.owner_id, allocations.account, 'fee', -allocations.fees, lots.fill_time, allocations.id
+    FROM allocations LEFT JOIN lots ON lots.id = allocations.lot_id
+    WHERE allocations.fees != 0
+    UNION ALL
+    SELECT allocations.owner_id, allocations.account, 'dividend', adjustments.cash, corporate_actions.applied_at,
+           corporate_actions.id
+    FROM corporate_action_adjustments adjustments
+    JOIN corporate_actions ON corporate_actions.id = adjustments.corporate_action_id
+    JOIN allocations ON allocations.id = adjustments.entity_id
+    WHERE adjustments.entity = 'allocation' AND adjustments.cash IS NOT NULL
+    UNION ALL
+    SELECT owner_id, account, kind, amount, datetime, id FROM cash_movements
+) entries JOIN owners ON owners.id = entries.owner_id;
+CREATE VIEW cash_balances AS
+SELECT owner, account, sum(amount) AS cash
+FROM cash_ledger
+GROUP BY owner, account;
@@ -0,0 +1,45 @@
+use crate::types::AccountRoute;
+use std::convert::TryInto;
+use tokio_postgres::{Error, GenericClient};
+use tracing::trace;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_account_routes<T: GenericClient>(client: &T) -> Result<Vec<AccountRoute>, Error> {
+    trace!("Fetching all account routes");
+    client
+        .query("SELECT * FROM account_routes", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, strategy, sub_strategy))]
+pub async fn get_account_route<T: GenericClient>(
+    client: &T,
+    strategy: &str,
+    sub_strategy: Option<&str>,
+) -> Result<Option<AccountRoute>, Error> {
+    trace!(strategy, ?sub_strategy, "Fetching account route for strategy");
+    // A route for the sub-strategy takes precedence over one for the whole strategy
+    client
+        .query_opt(
+            "SELECT * FROM account_routes WHERE strategy = $1 AND (sub_strategy = $2 OR sub_strategy IS NULL) ORDER BY sub_strategy NULLS LAST LIMIT 1",
+            &[&strategy, &sub_strategy],
+        )
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, route))]
+pub async fn upsert_account_route<T: GenericClient>(client: &T, route: &AccountRoute) -> Result<(), Error> {
+    trace!(strategy = %route.strategy, sub_strategy = ?route.sub_strategy, account = %route.account, "Saving account route");
+    client
+        .execute(
+            "INSERT INTO account_routes (strategy, sub_strategy, account) VALUES ($1, $2, $3) ON CONFLICT (strategy, COALESCE(sub_strategy, ' ')) DO UPDATE SET account = EXCLUDED.account;",
+            &[&route.strategy, &route.sub_strategy, &route.account],
+        )
+        .await?;
+    Ok(())
+}
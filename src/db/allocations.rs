@@ -1,3 +1,4 @@
+use super::get_or_create_owner_id;
 use crate::types::{Allocation, Owner};
 use std::convert::TryInto;
 use tokio_postgres::{Error, GenericClient};
@@ -8,7 +9,43 @@ use uuid::Uuid;
 pub async fn get_allocations<T: GenericClient>(client: &T) -> Result<Vec<Allocation>, Error> {
     trace!("Fetching all allocations");
     client
-        .query("SELECT * FROM allocations", &[])
+        .query(
+            "SELECT allocations.*, owners.owner FROM allocations JOIN owners ON owners.id = allocations.owner_id",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_allocations_by_lot_id<T: GenericClient>(client: &T, lot_id: Uuid) -> Result<Vec<Allocation>, Error> {
+    trace!("Fetching allocations of lot");
+    client
+        .query(
+            "SELECT allocations.*, owners.owner FROM allocations JOIN owners ON owners.id = allocations.owner_id WHERE lot_id = $1",
+            &[&lot_id],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, owner, account, ticker))]
+pub async fn get_allocations_by_owner_and_ticker<T: GenericClient>(
+    client: &T,
+    owner: &Owner,
+    account: &str,
+    ticker: &str,
+) -> Result<Vec<Allocation>, Error> {
+    trace!(%owner, account, ticker, "Fetching allocations for owner and ticker");
+    client
+        .query(
+            "SELECT allocations.*, owners.owner FROM allocations JOIN owners ON owners.id = allocations.owner_id WHERE owners.owner = $1 AND account = $2 AND ticker = $3",
+            &[owner, &account, &ticker],
+        )
         .await?
         .into_iter()
         .map(TryInto::try_into)
@@ -17,44 +54,48 @@ pub async fn get_allocations<T: GenericClient>(client: &T) -> Result<Vec<Allocat
 
 pub async fn set_allocation_owner<T: GenericClient>(client: &T, id: Uuid, owner: &Owner) -> Result<(), Error> {
     trace!("Updating allocation owner");
-    let (owner, sub_owner) = match owner {
-        Owner::House => ("House", None),
-        Owner::Strategy(owner, sub_owner) => (owner.as_str(), sub_owner.as_ref()),
-    };
-    match sub_owner {
-        Some(sub_owner) => {
-            client
-                .query(
-                    "UPDATE allocations SET owner = $1, sub_owner = $2 WHERE id = $3",
-                    &[&owner, &sub_owner, &id],
-                )
-                .await?
-        }
-        None => {
-            client
-                .query("UPDATE allocations SET owner = $1 WHERE id = $2", &[&owner, &id])
-                .await?
-        }
-    };
+    let owner_id = get_or_create_owner_id(client, owner).await?;
+    client
+        .execute("UPDATE allocations SET owner_id = $1 WHERE id = $2", &[&owner_id, &id])
+        .await?;
     Ok(())
 }
 
+/// Moves all the allocations of an owner in a ticker to another owner, returning the number of
+/// allocations moved.
+#[tracing::instrument(skip(client, from, to, account, ticker))]
+pub async fn reassign_allocations<T: GenericClient>(
+    client: &T,
+    from: &Owner,
+    to: &Owner,
+    account: &str,
+    ticker: &str,
+) -> Result<u64, Error> {
+    trace!(%from, %to, account, ticker, "Reassigning allocations");
+    let from_id = get_or_create_owner_id(client, from).await?;
+    let to_id = get_or_create_owner_id(client, to).await?;
+    client
+        .execute(
+            "UPDATE allocations SET owner_id = $1 WHERE owner_id = $2 AND account = $3 AND ticker = $4",
+            &[&to_id, &from_id, &account, &ticker],
+        )
+        .await
+}
+
 #[tracing::instrument(skip(client, allocation))]
 pub async fn save_allocation<T: GenericClient>(client: &T, allocation: &Allocation) -> Result<(), Error> {
     trace!("Saving allocation");
-    let (owner, sub_owner) = match &allocation.owner {
-        Owner::House => ("House", None),
-        Owner::Strategy(owner, sub_owner) => (owner.as_str(), sub_owner.as_ref()),
-    };
-    client.execute("INSERT INTO allocations (id, owner, sub_owner, claim_id, lot_id, ticker, shares, basis) VALUES ($1, $2, $3, $4, $5, $6, $7, $8);", &[
+    let owner_id = get_or_create_owner_id(client, &allocation.owner).await?;
+    client.execute("INSERT INTO allocations (id, owner_id, claim_id, lot_id, ticker, shares, basis, fees, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);", &[
             &allocation.id,
-            &owner,
-            &sub_owner,
+            &owner_id,
             &allocation.claim_id,
             &allocation.lot_id,
             &allocation.ticker,
             &allocation.shares,
-            &allocation.basis
+            &allocation.basis,
+            &allocation.fees,
+            &allocation.account
         ])
             .await?;
     Ok(())
@@ -0,0 +1,92 @@
+use crate::types::BracketLeg;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_bracket_leg<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<BracketLeg>> {
+    trace!(%id, "Fetching bracket leg");
+    client
+        .query_opt("SELECT * FROM bracket_legs WHERE id = $1", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_bracket_legs_by_parent<T: GenericClient>(client: &T, parent_id: Uuid) -> Result<Vec<BracketLeg>> {
+    trace!(%parent_id, "Fetching bracket legs for parent");
+    client
+        .query("SELECT * FROM bracket_legs WHERE parent_id = $1", &[&parent_id])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_bracket_legs_by_claim<T: GenericClient>(client: &T, claim_id: Uuid) -> Result<Vec<BracketLeg>> {
+    trace!(%claim_id, "Fetching bracket legs for claim");
+    client
+        .query("SELECT * FROM bracket_legs WHERE claim_id = $1", &[&claim_id])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Deletes the legs of a claim that have not been sent yet, along with their dependent trades.
+#[tracing::instrument(skip(client))]
+pub async fn delete_pending_bracket_legs<T: GenericClient>(client: &T, claim_id: Uuid) -> Result<u64> {
+    trace!(%claim_id, "Deleting pending bracket legs");
+    let deleted = client
+        .execute(
+            "WITH pending AS (
+                SELECT id FROM bracket_legs
+                WHERE claim_id = $1 AND NOT EXISTS (SELECT 1 FROM trades WHERE trades.id = bracket_legs.id)
+            ), dropped AS (
+                DELETE FROM dependent_trades WHERE id IN (SELECT id FROM pending)
+            )
+            DELETE FROM bracket_legs WHERE id IN (SELECT id FROM pending)",
+            &[&claim_id],
+        )
+        .await?;
+    Ok(deleted)
+}
+
+#[tracing::instrument(skip(client, leg))]
+pub async fn save_bracket_leg<T: GenericClient>(client: &T, leg: &BracketLeg) -> Result<()> {
+    trace!(id = %leg.id, "Saving bracket leg");
+    client
+        .execute(
+            "INSERT INTO bracket_legs (id, parent_id, claim_id, strategy, sub_strategy, ticker, kind, price, resize_to) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            &[
+                &leg.id,
+                &leg.parent_id,
+                &leg.claim_id,
+                &leg.strategy,
+                &leg.sub_strategy,
+                &leg.ticker,
+                &serde_plain::to_string(&leg.kind)?,
+                &leg.price,
+                &leg.resize_to,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn set_bracket_leg_resize<T: GenericClient>(client: &T, id: Uuid, resize_to: Option<Decimal>) -> Result<()> {
+    trace!(%id, ?resize_to, "Setting bracket leg resize");
+    client
+        .execute(
+            "UPDATE bracket_legs SET resize_to = $1 WHERE id = $2",
+            &[&resize_to, &id],
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::types::{Budget, BudgetDenial};
+use std::convert::TryInto;
+use tokio_postgres::{Error, GenericClient};
+use tracing::trace;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_budgets<T: GenericClient>(client: &T) -> Result<Vec<Budget>, Error> {
+    trace!("Fetching all budgets");
+    client
+        .query("SELECT * FROM strategy_budgets", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, strategy))]
+pub async fn get_budgets_by_strategy<T: GenericClient>(client: &T, strategy: &str) -> Result<Vec<Budget>, Error> {
+    trace!(strategy, "Fetching budgets for strategy");
+    client
+        .query("SELECT * FROM strategy_budgets WHERE strategy = $1", &[&strategy])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, budget))]
+pub async fn upsert_budget<T: GenericClient>(client: &T, budget: &Budget) -> Result<(), Error> {
+    trace!(strategy = %budget.strategy, sub_strategy = ?budget.sub_strategy, "Saving budget");
+    client
+        .execute(
+            "INSERT INTO strategy_budgets (strategy, sub_strategy, capital, max_gross_exposure, max_net_exposure, max_ticker_concentration, enforce_buying_power) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (strategy, COALESCE(sub_strategy, ' ')) DO UPDATE SET capital = EXCLUDED.capital, max_gross_exposure = EXCLUDED.max_gross_exposure, max_net_exposure = EXCLUDED.max_net_exposure, max_ticker_concentration = EXCLUDED.max_ticker_concentration, enforce_buying_power = EXCLUDED.enforce_buying_power;",
+            &[
+                &budget.strategy,
+                &budget.sub_strategy,
+                &budget.capital,
+                &budget.max_gross_exposure,
+                &budget.max_net_exposure,
+                &budget.max_ticker_concentration,
+                &budget.enforce_buying_power,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_budget_denials<T: GenericClient>(client: &T) -> Result<Vec<BudgetDenial>, Error> {
+    trace!("Fetching all budget denials");
+    client
+        .query("SELECT * FROM budget_denials ORDER BY datetime DESC", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, denial))]
+pub async fn save_budget_denial<T: GenericClient>(client: &T, denial: &BudgetDenial) -> Result<(), Error> {
+    trace!(id = %denial.id, "Saving budget denial");
+    client
+        .execute(
+            "INSERT INTO budget_denials (id, intent_id, strategy, sub_strategy, ticker, reason, datetime) VALUES ($1, $2, $3, $4, $5, $6, $7);",
+            &[
+                &denial.id,
+                &denial.intent_id,
+                &denial.strategy,
+                &denial.sub_strategy,
+                &denial.ticker,
+                &denial.reason,
+                &denial.datetime,
+            ],
+        )
+        .await?;
+    Ok(())
+}
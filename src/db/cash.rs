@@ -0,0 +1,103 @@
+use super::get_or_create_owner_id;
+use crate::types::{CashBalance, CashEntry, CashEntryKind, CashMovement, CashTransfer, Owner};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_cash_ledger<T: GenericClient>(client: &T) -> Result<Vec<CashEntry>> {
+    trace!("Fetching cash ledger");
+    client
+        .query("SELECT * FROM cash_ledger ORDER BY datetime", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_cash_balances<T: GenericClient>(client: &T) -> Result<Vec<CashBalance>> {
+    trace!("Fetching cash balances");
+    client
+        .query("SELECT * FROM cash_balances", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// The cash of an owner in an account. A strategy owner without a sub-strategy includes the cash
+/// of all its sub-strategies.
+#[tracing::instrument(skip(client, owner))]
+pub async fn get_cash_balance_by_owner<T: GenericClient>(client: &T, owner: &Owner, account: &str) -> Result<Decimal> {
+    trace!(%owner, account, "Fetching cash balance for owner");
+    let row = match owner {
+        Owner::Strategy(strategy, None) => {
+            client
+                .query_one(
+                    "SELECT COALESCE(sum(cash), 0) AS cash FROM cash_balances WHERE (owner).kind = 'strategy' AND (owner).strategy = $1 AND account = $2",
+                    &[strategy, &account],
+                )
+                .await?
+        }
+        owner => {
+            client
+                .query_one(
+                    "SELECT COALESCE(sum(cash), 0) AS cash FROM cash_balances WHERE owner = $1 AND account = $2",
+                    &[owner, &account],
+                )
+                .await?
+        }
+    };
+    Ok(row.try_get("cash")?)
+}
+
+#[tracing::instrument(skip(client, movement), fields(id = %movement.id))]
+pub async fn save_cash_movement<T: GenericClient>(client: &T, movement: &CashMovement) -> Result<()> {
+    trace!("Saving cash movement");
+    let owner_id = get_or_create_owner_id(client, &movement.owner).await?;
+    client
+        .execute(
+            "INSERT INTO cash_movements (id, owner_id, account, kind, amount, description, datetime) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &movement.id,
+                &owner_id,
+                &movement.account,
+                &serde_plain::to_string(&movement.kind())?,
+                &movement.amount,
+                &movement.description,
+                &movement.datetime,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Saves both sides of a transfer in a single statement, so that cash is never created or lost.
+#[tracing::instrument(skip(client, transfer), fields(id = %transfer.id))]
+pub async fn save_cash_transfer<T: GenericClient>(client: &T, transfer: &CashTransfer) -> Result<()> {
+    trace!(from = %transfer.from, to = %transfer.to, "Saving cash transfer");
+    let from_id = get_or_create_owner_id(client, &transfer.from).await?;
+    let to_id = get_or_create_owner_id(client, &transfer.to).await?;
+    client
+        .execute(
+            "INSERT INTO cash_movements (id, owner_id, account, kind, amount, transfer_id, description, datetime) VALUES ($1, $2, $5, $6, -$7::numeric, $8, $9, $10), ($3, $4, $5, $6, $7::numeric, $8, $9, $10)",
+            &[
+                &Uuid::new_v4(),
+                &from_id,
+                &Uuid::new_v4(),
+                &to_id,
+                &transfer.account,
+                &serde_plain::to_string(&CashEntryKind::Transfer)?,
+                &transfer.amount,
+                &transfer.id,
+                &transfer.description,
+                &transfer.datetime,
+            ],
+        )
+        .await?;
+    Ok(())
+}
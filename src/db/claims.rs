@@ -1,11 +1,26 @@
 use super::utils::split_amount_spec;
-use crate::types::Claim;
+use crate::types::{ArchivedClaim, Claim, ClaimState, Owner, Trade};
 use std::convert::TryInto;
 use tokio_postgres::{Error, GenericClient};
 use tracing::trace;
 use trading_base::Amount;
 use uuid::Uuid;
 
+/// Columns copied from a claim to its row in the claim history, which adds its final state and
+/// the time it was archived.
+const ARCHIVED_CLAIM_COLUMNS: &str =
+    "id, strategy, sub_strategy, ticker, amount, unit, limit_price, before, intent_id, denial_count, denied_reason, retry_after, account";
+
+/// Statement inserting the claims selected from `source` into the claim history in `state`.
+pub(super) fn archive_statement(source: &str, state: &str) -> String {
+    format!(
+        "INSERT INTO claim_history ({columns}, state, archived_at) SELECT {columns}, {state}, now() FROM {source}",
+        columns = ARCHIVED_CLAIM_COLUMNS,
+        state = state,
+        source = source
+    )
+}
+
 pub async fn get_claims<T: GenericClient>(client: &T) -> Result<Vec<Claim>, Error> {
     trace!("Fetching all claims");
     client
@@ -36,6 +51,93 @@ pub async fn get_claim_by_id<T: GenericClient>(client: &T, id: Uuid) -> Result<C
         .try_into()
 }
 
+#[tracing::instrument(skip(client, id))]
+pub async fn find_claim_by_id<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<Claim>, Error> {
+    trace!(%id, "Fetching claim for id if it exists");
+    client
+        .query_opt("SELECT * FROM claims WHERE id = $1", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, strategy, sub_strategy, ticker))]
+pub async fn get_claim_by_strategy_and_ticker<T: GenericClient>(
+    client: &T,
+    strategy: &str,
+    sub_strategy: Option<&str>,
+    ticker: &str,
+) -> Result<Option<Claim>, Error> {
+    trace!(
+        strategy,
+        ?sub_strategy,
+        ticker,
+        "Fetching claim for strategy and ticker"
+    );
+    let res = match sub_strategy {
+        Some(sub_strategy) => {
+            client
+                .query_opt(
+                    "SELECT * FROM claims WHERE strategy = $1 AND sub_strategy = $2 AND ticker = $3",
+                    &[&strategy, &sub_strategy, &ticker],
+                )
+                .await?
+        }
+        None => {
+            client
+                .query_opt(
+                    "SELECT * FROM claims WHERE strategy = $1 AND sub_strategy IS NULL AND ticker = $2",
+                    &[&strategy, &ticker],
+                )
+                .await?
+        }
+    };
+    res.map(TryInto::try_into).transpose()
+}
+
+/// The live claims of an owner in an account that are still buying. A strategy owner without a
+/// sub-strategy includes the claims of all its sub-strategies.
+#[tracing::instrument(skip(client, owner))]
+pub async fn get_buy_claims_by_owner<T: GenericClient>(
+    client: &T,
+    owner: &Owner,
+    account: &str,
+) -> Result<Vec<Claim>, Error> {
+    trace!(%owner, account, "Fetching buy claims for owner");
+    let rows = match owner {
+        Owner::House | Owner::ErrorAccount(_) => return Ok(Vec::new()),
+        Owner::Strategy(strategy, None) => {
+            client
+                .query(
+                    "SELECT * FROM claims WHERE strategy = $1 AND account = $2 AND amount > 0 AND state IN ('open', 'working', 'partially_satisfied')",
+                    &[strategy, &account],
+                )
+                .await?
+        }
+        Owner::Strategy(strategy, Some(sub_strategy)) => {
+            client
+                .query(
+                    "SELECT * FROM claims WHERE strategy = $1 AND sub_strategy = $2 AND account = $3 AND amount > 0 AND state IN ('open', 'working', 'partially_satisfied')",
+                    &[strategy, sub_strategy, &account],
+                )
+                .await?
+        }
+    };
+    rows.into_iter().map(TryInto::try_into).collect()
+}
+
+#[tracing::instrument(skip(client, claim))]
+pub async fn update_claim_denial<T: GenericClient>(client: &T, claim: &Claim) -> Result<(), Error> {
+    trace!(id = %claim.id, denial_count = claim.denial_count, "Updating claim denial");
+    client
+        .execute(
+            "UPDATE claims SET denial_count = $1, denied_reason = $2, retry_after = $3 WHERE id = $4",
+            &[&claim.denial_count, &claim.denied_reason, &claim.retry_after, &claim.id],
+        )
+        .await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip(client, id, amount))]
 pub async fn update_claim_amount<T: GenericClient>(client: &T, id: Uuid, amount: &Amount) -> Result<(), Error> {
     trace!(%id, ?amount, "Updating claim amount");
@@ -50,53 +152,141 @@ pub async fn update_claim_amount<T: GenericClient>(client: &T, id: Uuid, amount:
 }
 
 #[tracing::instrument(skip(client, id))]
-pub async fn delete_claim_by_id<T: GenericClient>(client: &T, id: Uuid) -> Result<(), Error> {
-    trace!(%id, "Deleting claim for id");
-    client.execute("DELETE FROM claims WHERE id = $1;", &[&id]).await?;
+pub async fn update_claim_state<T: GenericClient>(client: &T, id: Uuid, state: ClaimState) -> Result<(), Error> {
+    trace!(%id, ?state, "Updating claim state");
+    client
+        .execute("UPDATE claims SET state = $1 WHERE id = $2", &[&state, &id])
+        .await?;
+    Ok(())
+}
+
+/// Links a trade to the claim it was sent for, marking the claim as worked if it was still open.
+#[tracing::instrument(skip(client, claim_id, trade_id))]
+pub async fn save_claim_trade<T: GenericClient>(client: &T, claim_id: Uuid, trade_id: Uuid) -> Result<(), Error> {
+    trace!(%claim_id, %trade_id, "Saving claim trade");
+    client
+        .execute(
+            "INSERT INTO claim_trades (claim_id, trade_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            &[&claim_id, &trade_id],
+        )
+        .await?;
+    client
+        .execute(
+            "UPDATE claims SET state = 'working' WHERE id = $1 AND state = 'open'",
+            &[&claim_id],
+        )
+        .await?;
+    Ok(())
+}
+
+/// The claim that a trade was sent for, if any.
+#[tracing::instrument(skip(client, trade_id))]
+pub async fn get_trade_claim_id<T: GenericClient>(client: &T, trade_id: Uuid) -> Result<Option<Uuid>, Error> {
+    trace!(%trade_id, "Fetching claim of trade");
+    client
+        .query_opt("SELECT claim_id FROM claim_trades WHERE trade_id = $1", &[&trade_id])
+        .await?
+        .map(|row| row.try_get("claim_id"))
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, claim_id))]
+pub async fn get_claim_trades<T: GenericClient>(client: &T, claim_id: Uuid) -> Result<Vec<Trade>, Error> {
+    trace!(%claim_id, "Fetching trades of claim");
+    client
+        .query(
+            "SELECT trades.* FROM trades JOIN claim_trades ON claim_trades.trade_id = trades.id WHERE claim_trades.claim_id = $1 ORDER BY trades.datetime",
+            &[&claim_id],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_claim_history<T: GenericClient>(client: &T) -> Result<Vec<ArchivedClaim>, Error> {
+    trace!("Fetching archived claims");
+    client
+        .query("SELECT * FROM claim_history ORDER BY archived_at DESC", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Moves a claim to the claim history in its final state.
+#[tracing::instrument(skip(client, id))]
+pub async fn archive_claim_by_id<T: GenericClient>(client: &T, id: Uuid, state: ClaimState) -> Result<(), Error> {
+    trace!(%id, ?state, "Archiving claim for id");
+    let statement = format!(
+        "WITH archived AS (DELETE FROM claims WHERE id = $1 RETURNING *) {}",
+        archive_statement("archived", "$2::claim_state")
+    );
+    client.execute(statement.as_str(), &[&id, &state]).await?;
     Ok(())
 }
 
+/// Archives the claims of a strategy in a ticker. Without a sub-strategy, the claims of all
+/// sub-strategies are archived, in the same way that positions are aggregated over them.
 #[tracing::instrument(skip(client, strategy, sub_strategy, ticker))]
-pub async fn delete_claims_by_strategy_and_ticker<T: GenericClient>(
+pub async fn archive_claims_by_strategy_and_ticker<T: GenericClient>(
     client: &T,
     strategy: &str,
     sub_strategy: Option<&str>,
     ticker: &str,
+    state: ClaimState,
 ) -> Result<(), Error> {
     trace!(
         strategy,
         ?sub_strategy,
         ticker,
-        "Deleting claims for strategy and ticker"
+        ?state,
+        "Archiving claims for strategy and ticker"
     );
     match sub_strategy {
         Some(sub_strategy) => {
+            let statement = format!(
+                "WITH archived AS (DELETE FROM claims WHERE strategy = $1 AND sub_strategy = $2 AND ticker = $3 RETURNING *) {}",
+                archive_statement("archived", "$4::claim_state")
+            );
             client
-                .execute(
-                    "DELETE FROM claims WHERE strategy = $1 AND sub_strategy = $2 AND ticker = $3",
-                    &[&strategy, &sub_strategy, &ticker],
-                )
+                .execute(statement.as_str(), &[&strategy, &sub_strategy, &ticker, &state])
                 .await?
         }
         None => {
+            let statement = format!(
+                "WITH archived AS (DELETE FROM claims WHERE strategy = $1 AND ticker = $2 RETURNING *) {}",
+                archive_statement("archived", "$3::claim_state")
+            );
             client
-                .execute(
-                    "DELETE FROM claims WHERE strategy = $1 AND ticker = $2",
-                    &[&strategy, &ticker],
-                )
+                .execute(statement.as_str(), &[&strategy, &ticker, &state])
                 .await?
         }
     };
     Ok(())
 }
 
+/// Saves a claim, replacing the claim of the same strategy and sub-strategy in the ticker. The
+/// replaced claim is copied to the claim history in the same statement, so that a claim is never
+/// lost or archived without its replacement.
 #[tracing::instrument(skip(client, claim))]
 pub async fn upsert_claim<T: GenericClient>(client: &T, claim: &Claim) -> Result<(), Error> {
     trace!(id = %claim.id, "Saving claim");
     let (amount, unit) = split_amount_spec(&claim.amount);
+    let superseded = archive_statement(
+        "claims WHERE strategy = $2 AND sub_strategy IS NOT DISTINCT FROM $3 AND ticker = $4 AND id <> $1",
+        "'superseded'::claim_state",
+    );
+    // Claims with and without a sub-strategy are kept unique by separate partial indexes
+    let upsert = match claim.sub_strategy {
+        Some(_) => "INSERT INTO claims (id, strategy, sub_strategy, ticker, amount, unit, limit_price, before, intent_id, state, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (strategy, sub_strategy, ticker) WHERE sub_strategy IS NOT NULL DO UPDATE SET id = EXCLUDED.id, amount = EXCLUDED.amount, unit = EXCLUDED.unit, limit_price = EXCLUDED.limit_price, before = EXCLUDED.before, intent_id = EXCLUDED.intent_id, state = EXCLUDED.state, account = EXCLUDED.account, denial_count = 0, denied_reason = NULL, retry_after = NULL",
+        None => "INSERT INTO claims (id, strategy, sub_strategy, ticker, amount, unit, limit_price, before, intent_id, state, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (strategy, ticker) WHERE sub_strategy IS NULL DO UPDATE SET id = EXCLUDED.id, amount = EXCLUDED.amount, unit = EXCLUDED.unit, limit_price = EXCLUDED.limit_price, before = EXCLUDED.before, intent_id = EXCLUDED.intent_id, state = EXCLUDED.state, account = EXCLUDED.account, denial_count = 0, denied_reason = NULL, retry_after = NULL",
+    };
+    let statement = format!("WITH superseded AS ({}) {}", superseded, upsert);
     client
         .execute(
-            "INSERT INTO claims (id, strategy, sub_strategy, ticker, amount, unit, limit_price, before) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (strategy, COALESCE(sub_strategy, ' '), ticker) WHERE sub_strategy IS NOT NULL DO UPDATE SET id = EXCLUDED.id, amount = EXCLUDED.amount, unit = EXCLUDED.unit, limit_price = EXCLUDED.limit_price, before = EXCLUDED.before;",
+            statement.as_str(),
             &[
                 &claim.id,
                 &claim.strategy,
@@ -105,7 +295,10 @@ pub async fn upsert_claim<T: GenericClient>(client: &T, claim: &Claim) -> Result
                 &amount,
                 &unit,
                 &claim.limit_price,
-                &claim.before
+                &claim.before,
+                &claim.intent_id,
+                &claim.state,
+                &claim.account,
             ],
         )
         .await?;
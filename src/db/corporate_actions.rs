@@ -0,0 +1,214 @@
+use super::claims::archive_statement;
+use crate::types::{CorporateAction, CorporateActionAdjustment, CorporateActionKind};
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_corporate_actions<T: GenericClient>(client: &T) -> Result<Vec<CorporateAction>> {
+    trace!("Getting corporate actions");
+    client
+        .query("SELECT * FROM corporate_actions ORDER BY applied_at DESC", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_corporate_action_adjustments<T: GenericClient>(
+    client: &T,
+    id: Uuid,
+) -> Result<Vec<CorporateActionAdjustment>> {
+    trace!("Getting corporate action adjustments");
+    client
+        .query(
+            "SELECT * FROM corporate_action_adjustments WHERE corporate_action_id = $1",
+            &[&id],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Records a corporate action and applies it to the lots, allocations, active claims, dependent
+/// trades, bracket legs, order instructions and scheduled intents in its ticker, auditing every
+/// record adjusted. Working trades in the ticker are marked for cancellation and queued trades
+/// are dropped, since they were sized and priced before the action; the trades of claims are
+/// regenerated from the adjusted claims. On a symbol change, active claims of strategies that
+/// already have a claim on the new ticker are superseded by it. This is a single statement, so
+/// the action is applied in full or not at all, and an action can't be applied twice. Returns
+/// the number of records adjusted.
+#[tracing::instrument(skip(client, action), fields(id = %action.id, ticker = %action.ticker))]
+pub async fn apply_corporate_action<T: GenericClient>(client: &T, action: &CorporateAction) -> Result<u64> {
+    trace!("Applying corporate action");
+    let kind = serde_json::to_string(&action.kind)?;
+    let adjusted = match &action.kind {
+        // Cash dividends only change what the owners of the shares held before the ex-date are
+        // owed
+        CorporateActionKind::CashDividend { amount, ex_date } => {
+            client
+                .execute(
+                    "WITH action AS (
+                        INSERT INTO corporate_actions (id, ticker, action, applied_at) VALUES ($1, $2, $3, $4) RETURNING id
+                    )
+                    INSERT INTO corporate_action_adjustments (corporate_action_id, entity, entity_id, ticker, quantity, adjusted_quantity, cash)
+                    SELECT action.id, 'allocation', allocations.id, $2, allocations.shares, allocations.shares, round(allocations.shares * $5, 8)
+                    FROM action, allocations JOIN lots ON lots.id = allocations.lot_id
+                    WHERE allocations.ticker = $2 AND (lots.fill_time AT TIME ZONE 'America/New_York')::date < $6",
+                    &[&action.id, &action.ticker, &kind, &action.applied_at, amount, ex_date],
+                )
+                .await?
+        }
+        _ => {
+            // Order prices are rounded to cents, and order quantities truncated so that orders
+            // never exceed the shares held
+            let statement = format!(
+                "WITH action AS (
+                    INSERT INTO corporate_actions (id, ticker, action, applied_at) VALUES ($1, $2, $3, $4) RETURNING id
+                ), old_lots AS (
+                    SELECT id, shares, price FROM lots WHERE ticker = $2
+                ), new_lots AS (
+                    UPDATE lots SET ticker = $5, shares = round(shares * $6, 8), price = round(price / $6, 8)
+                    WHERE ticker = $2 RETURNING id, shares, price
+                ), old_allocations AS (
+                    SELECT id, shares FROM allocations WHERE ticker = $2
+                ), new_allocations AS (
+                    UPDATE allocations SET ticker = $5, shares = round(shares * $6, 8)
+                    WHERE ticker = $2 RETURNING id, shares
+                ), old_claims AS (
+                    SELECT * FROM claims WHERE ticker = $2 AND state IN ('open', 'working', 'partially_satisfied')
+                ), conflicting_claims AS (
+                    SELECT id FROM old_claims WHERE $5 <> $2 AND EXISTS (
+                        SELECT 1 FROM claims
+                        WHERE claims.ticker = $5
+                        AND claims.strategy = old_claims.strategy
+                        AND claims.sub_strategy IS NOT DISTINCT FROM old_claims.sub_strategy
+                    )
+                ), superseded_claims AS (
+                    DELETE FROM claims WHERE id IN (SELECT id FROM conflicting_claims) RETURNING *
+                ), archived_claims AS (
+                    {archive}
+                ), new_claims AS (
+                    UPDATE claims SET
+                        ticker = $5,
+                        amount = CASE WHEN unit = 'shares' THEN round(amount * $6, 8) ELSE amount END,
+                        limit_price = round(limit_price / $6, 8)
+                    WHERE id IN (SELECT id FROM old_claims) AND id NOT IN (SELECT id FROM conflicting_claims)
+                    RETURNING id, amount, limit_price
+                ), old_dependent_trades AS (
+                    SELECT id, qty, COALESCE(limit_price, stop_price) AS price FROM dependent_trades WHERE ticker = $2
+                ), new_dependent_trades AS (
+                    UPDATE dependent_trades SET
+                        ticker = $5,
+                        qty = trunc(qty * $6),
+                        limit_price = round(limit_price / $6, 2),
+                        stop_price = round(stop_price / $6, 2),
+                        trail_price = round(trail_price / $6, 2),
+                        trigger_quantity = trunc(trigger_quantity * $6)
+                    WHERE ticker = $2 RETURNING id, qty, COALESCE(limit_price, stop_price) AS price
+                ), old_bracket_legs AS (
+                    SELECT id, price FROM bracket_legs WHERE ticker = $2
+                ), new_bracket_legs AS (
+                    UPDATE bracket_legs SET ticker = $5, price = round(price / $6, 2)
+                    WHERE ticker = $2 RETURNING id, price
+                ), old_scheduled_intents AS (
+                    SELECT id, amount, limit_price FROM scheduled_intents WHERE ticker = $2 AND cancelled_at IS NULL
+                ), new_scheduled_intents AS (
+                    UPDATE scheduled_intents SET
+                        ticker = $5,
+                        amount = CASE WHEN unit = 'shares' THEN round(amount * $6, 8) ELSE amount END,
+                        decision_price = round(decision_price / $6, 8),
+                        limit_price = round(limit_price / $6, 8),
+                        stop_price = round(stop_price / $6, 8)
+                    WHERE ticker = $2 AND cancelled_at IS NULL RETURNING id, amount, limit_price
+                ), old_order_instructions AS (
+                    SELECT intent_id, COALESCE(take_profit, stop_loss, trail_price) AS price FROM order_instructions
+                    WHERE (take_profit IS NOT NULL OR stop_loss IS NOT NULL OR trail_price IS NOT NULL)
+                    AND intent_id IN (
+                        SELECT intent_id FROM old_claims WHERE id NOT IN (SELECT id FROM conflicting_claims)
+                        UNION
+                        SELECT id FROM old_scheduled_intents
+                    )
+                ), new_order_instructions AS (
+                    UPDATE order_instructions SET
+                        take_profit = round(take_profit / $6, 2),
+                        stop_loss = round(stop_loss / $6, 2),
+                        trail_price = round(trail_price / $6, 2)
+                    WHERE intent_id IN (SELECT intent_id FROM old_order_instructions)
+                    RETURNING intent_id, COALESCE(take_profit, stop_loss, trail_price) AS price
+                ), cancelled_trades AS (
+                    UPDATE trades SET cancel_requested = TRUE
+                    WHERE ticker = $2 AND status IN ('unreported', 'accepted', 'partially_filled') AND NOT cancel_requested
+                    RETURNING id, pending_quantity
+                ), dropped_queued_trades AS (
+                    DELETE FROM queued_trades WHERE ticker = $2 RETURNING id, (intent::jsonb ->> 'qty')::numeric AS qty
+                )
+                INSERT INTO corporate_action_adjustments (corporate_action_id, entity, entity_id, ticker, quantity, adjusted_quantity, price, adjusted_price)
+                SELECT action.id, 'lot', old.id, $2, old.shares, new.shares, old.price, new.price
+                FROM action, old_lots old JOIN new_lots new USING (id)
+                UNION ALL
+                SELECT action.id, 'allocation', old.id, $2, old.shares, new.shares, NULL, NULL
+                FROM action, old_allocations old JOIN new_allocations new USING (id)
+                UNION ALL
+                SELECT action.id, 'claim', old.id, $2, old.amount, new.amount, old.limit_price, new.limit_price
+                FROM action, old_claims old JOIN new_claims new USING (id)
+                UNION ALL
+                SELECT action.id, 'superseded_claim', superseded.id, $2, superseded.amount, NULL, superseded.limit_price, NULL
+                FROM action, superseded_claims superseded
+                UNION ALL
+                SELECT action.id, 'dependent_trade', old.id, $2, old.qty, new.qty, old.price, new.price
+                FROM action, old_dependent_trades old JOIN new_dependent_trades new USING (id)
+                UNION ALL
+                SELECT action.id, 'bracket_leg', old.id, $2, NULL, NULL, old.price, new.price
+                FROM action, old_bracket_legs old JOIN new_bracket_legs new USING (id)
+                UNION ALL
+                SELECT action.id, 'scheduled_intent', old.id, $2, old.amount, new.amount, old.limit_price, new.limit_price
+                FROM action, old_scheduled_intents old JOIN new_scheduled_intents new USING (id)
+                UNION ALL
+                SELECT action.id, 'order_instructions', old.intent_id, $2, NULL, NULL, old.price, new.price
+                FROM action, old_order_instructions old JOIN new_order_instructions new USING (intent_id)
+                UNION ALL
+                SELECT action.id, 'trade', cancelled.id, $2, cancelled.pending_quantity, NULL, NULL, NULL
+                FROM action, cancelled_trades cancelled
+                UNION ALL
+                SELECT action.id, 'queued_trade', dropped.id, $2, dropped.qty, NULL, NULL, NULL
+                FROM action, dropped_queued_trades dropped",
+                archive = archive_statement("superseded_claims", "'superseded'")
+            );
+            client
+                .execute(
+                    statement.as_str(),
+                    &[
+                        &action.id,
+                        &action.ticker,
+                        &kind,
+                        &action.applied_at,
+                        &action.new_ticker(),
+                        &action.kind.share_factor(),
+                    ],
+                )
+                .await?
+        }
+    };
+    Ok(adjusted)
+}
+
+/// The working trades a corporate action marked for cancellation, which still need their
+/// cancels sent to the broker.
+#[tracing::instrument(skip(client))]
+pub async fn get_corporate_action_cancelled_trades<T: GenericClient>(client: &T, id: Uuid) -> Result<Vec<Uuid>> {
+    trace!("Getting trades cancelled by corporate action");
+    client
+        .query(
+            "SELECT entity_id FROM corporate_action_adjustments WHERE corporate_action_id = $1 AND entity = 'trade'",
+            &[&id],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Ok(row.try_get("entity_id")?))
+        .collect()
+}
@@ -1,13 +1,18 @@
-use anyhow::{anyhow, Result};
+use crate::types::{DependentTrade, PendingDependentTrade};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::convert::TryInto;
 use tokio_postgres::GenericClient;
 use tracing::trace;
-use trading_base::{OrderType, TradeIntent};
+use trading_base::OrderType;
 use uuid::Uuid;
 
-#[tracing::instrument(skip(client, id, dependent_trade))]
-pub async fn save_dependent_trade<T: GenericClient>(client: &T, id: Uuid, dependent_trade: &TradeIntent) -> Result<()> {
-    trace!(%id, "Saving dependent trade");
-    let (order_type, limit_price, stop_price) = match dependent_trade.order_type {
+#[tracing::instrument(skip(client, dependent_trade), fields(id = %dependent_trade.trade.intent.id))]
+pub async fn save_dependent_trade<T: GenericClient>(client: &T, dependent_trade: &DependentTrade) -> Result<()> {
+    trace!(parent_id = %dependent_trade.parent_id, "Saving dependent trade");
+    let trade = &dependent_trade.trade.intent;
+    let instructions = &dependent_trade.instructions;
+    let (order_type, limit_price, stop_price) = match trade.order_type {
         OrderType::Market => ("market", None, None),
         OrderType::Limit { limit_price } => ("limit", Some(limit_price), None),
         OrderType::Stop { stop_price } => ("stop", None, Some(stop_price)),
@@ -17,55 +22,140 @@ pub async fn save_dependent_trade<T: GenericClient>(client: &T, id: Uuid, depend
         } => ("stoplimit", Some(limit_price), Some(stop_price)),
     };
     client.execute(
-                "INSERT INTO dependent_trades (dependent_id, id, ticker, qty, order_type, limit_price, stop_price, time_in_force) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                "INSERT INTO dependent_trades (dependent_id, id, ticker, qty, notional, account, order_type, limit_price, stop_price, time_in_force, order_kind, trail_price, trail_percent, extended_hours, trigger_condition, trigger_quantity, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)",
                 &[
-                    &id,
-                    &dependent_trade.id,
-                    &dependent_trade.ticker,
-                    &(dependent_trade.qty as i32),
+                    &dependent_trade.parent_id,
+                    &trade.id,
+                    &trade.ticker,
+                    &dependent_trade.trade.quantity(),
+                    &dependent_trade.trade.extensions.notional,
+                    &dependent_trade.trade.account,
                     &order_type,
                     &limit_price,
                     &stop_price,
-                    &serde_plain::to_string(&dependent_trade.time_in_force)?,
+                    &serde_plain::to_string(&trade.time_in_force)?,
+                    &serde_plain::to_string(&instructions.order_kind)?,
+                    &instructions.trail_price,
+                    &instructions.trail_percent,
+                    &instructions.extended_hours,
+                    &serde_plain::to_string(&dependent_trade.trigger)?,
+                    &dependent_trade.trigger_quantity,
+                    &dependent_trade.expires_at,
                 ],
             )
             .await?;
     Ok(())
 }
 
+/// Takes all trades that depend on the trade with the given id.
 #[tracing::instrument(skip(client, id))]
-pub async fn take_dependent_trades<T: GenericClient>(client: &T, id: Uuid) -> Result<Vec<TradeIntent>> {
-    trace!(%id, "Fetching and deleting dependent trade");
+pub async fn take_dependent_trades<T: GenericClient>(client: &T, id: Uuid) -> Result<Vec<DependentTrade>> {
+    trace!(%id, "Fetching and deleting dependent trades");
     client
         .query(
             "DELETE FROM dependent_trades WHERE dependent_id = $1 RETURNING *",
             &[&id],
         )
         .await?
-        .iter()
-        .map(|row| -> Result<TradeIntent> {
-            let order_type = match (row.try_get(4)?, row.try_get(5)?, row.try_get(6)?) {
-                ("market", _, _) => OrderType::Market,
-                ("limit", Some(limit_price), _) => OrderType::Limit { limit_price },
-                ("stop", None, Some(stop_price)) => OrderType::Stop { stop_price },
-                ("stoplimit", Some(limit_price), Some(stop_price)) => OrderType::StopLimit {
-                    limit_price,
-                    stop_price,
-                },
-                _ => {
-                    return Err(anyhow!(
-                        "Can only deal with market, limit, stop and stop-limit trades currently"
-                    ))
-                }
-            };
-            let time_in_force = serde_plain::from_str(row.try_get(7)?)?;
-            Ok(TradeIntent {
-                id: row.try_get(1)?,
-                ticker: row.try_get(2)?,
-                qty: row.try_get::<usize, i32>(3)? as isize,
-                order_type,
-                time_in_force,
-            })
-        })
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_dependent_trades<T: GenericClient>(client: &T) -> Result<Vec<PendingDependentTrade>> {
+    trace!("Getting dependent trades");
+    client
+        .query(
+            "SELECT dependent_trades.*, trades.status AS parent_status FROM dependent_trades LEFT JOIN trades ON trades.id = dependent_trades.dependent_id",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Takes the dependent trade with the given id, regardless of its trigger condition.
+#[tracing::instrument(skip(client, id))]
+pub async fn take_dependent_trade<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<DependentTrade>> {
+    trace!(%id, "Fetching and deleting dependent trade");
+    client
+        .query_opt("DELETE FROM dependent_trades WHERE id = $1 RETURNING *", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+/// Takes the trades that depend on a partial fill of the trade with the given id, and whose
+/// trigger quantity has been reached.
+#[tracing::instrument(skip(client, id, filled))]
+pub async fn take_partial_fill_dependent_trades<T: GenericClient>(
+    client: &T,
+    id: Uuid,
+    filled: Decimal,
+) -> Result<Vec<DependentTrade>> {
+    trace!(%id, %filled, "Fetching and deleting triggered partial fill dependent trades");
+    client
+        .query(
+            "DELETE FROM dependent_trades WHERE dependent_id = $1 AND trigger_condition = 'on_partial_fill' AND trigger_quantity <= $2 RETURNING *",
+            &[&id, &filled],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Deletes the trades that depend on the trade with the given id, together with everything that
+/// depends on them in turn.
+#[tracing::instrument(skip(client, id))]
+pub async fn cancel_dependent_trades<T: GenericClient>(client: &T, id: Uuid) -> Result<u64> {
+    trace!(%id, "Cancelling dependent trade chain");
+    let cancelled = client
+        .execute(
+            "WITH RECURSIVE chain AS (
+                SELECT id FROM dependent_trades WHERE dependent_id = $1
+                UNION
+                SELECT dependent_trades.id FROM dependent_trades JOIN chain ON dependent_trades.dependent_id = chain.id
+            )
+            DELETE FROM dependent_trades WHERE id IN (SELECT id FROM chain)",
+            &[&id],
+        )
+        .await?;
+    Ok(cancelled)
+}
+
+/// Deletes the dependent trade with the given id, together with everything that depends on it.
+/// Returns the number of deleted dependent trades.
+#[tracing::instrument(skip(client, id))]
+pub async fn cancel_dependent_trade<T: GenericClient>(client: &T, id: Uuid) -> Result<u64> {
+    trace!(%id, "Cancelling dependent trade");
+    let cancelled = client
+        .execute(
+            "WITH RECURSIVE chain AS (
+                SELECT id FROM dependent_trades WHERE id = $1
+                UNION
+                SELECT dependent_trades.id FROM dependent_trades JOIN chain ON dependent_trades.dependent_id = chain.id
+            )
+            DELETE FROM dependent_trades WHERE id IN (SELECT id FROM chain)",
+            &[&id],
+        )
+        .await?;
+    Ok(cancelled)
+}
+
+/// Takes the dependent trades whose expiry has passed.
+#[tracing::instrument(skip(client))]
+pub async fn take_expired_dependent_trades<T: GenericClient>(client: &T) -> Result<Vec<DependentTrade>> {
+    trace!("Fetching and deleting expired dependent trades");
+    client
+        .query(
+            "DELETE FROM dependent_trades WHERE expires_at <= now() RETURNING *",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
         .collect()
 }
@@ -0,0 +1,153 @@
+use crate::types::{Execution, ExecutionPolicy};
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_execution_policies<T: GenericClient>(client: &T) -> Result<Vec<ExecutionPolicy>> {
+    trace!("Fetching all execution policies");
+    client
+        .query("SELECT * FROM execution_policies", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, strategy, sub_strategy))]
+pub async fn get_execution_policy<T: GenericClient>(
+    client: &T,
+    strategy: &str,
+    sub_strategy: Option<&str>,
+) -> Result<Option<ExecutionPolicy>> {
+    trace!(strategy, ?sub_strategy, "Fetching execution policy for strategy");
+    // A policy for the sub-strategy takes precedence over one for the whole strategy
+    client
+        .query_opt(
+            "SELECT * FROM execution_policies WHERE strategy = $1 AND (sub_strategy = $2 OR sub_strategy IS NULL) ORDER BY sub_strategy NULLS LAST LIMIT 1",
+            &[&strategy, &sub_strategy],
+        )
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, policy))]
+pub async fn upsert_execution_policy<T: GenericClient>(client: &T, policy: &ExecutionPolicy) -> Result<()> {
+    trace!(strategy = %policy.strategy, sub_strategy = ?policy.sub_strategy, "Saving execution policy");
+    client
+        .execute(
+            "INSERT INTO execution_policies (strategy, sub_strategy, algorithm, min_notional, duration_seconds, slices, participation_rate, display_size) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (strategy, COALESCE(sub_strategy, ' ')) DO UPDATE SET algorithm = EXCLUDED.algorithm, min_notional = EXCLUDED.min_notional, duration_seconds = EXCLUDED.duration_seconds, slices = EXCLUDED.slices, participation_rate = EXCLUDED.participation_rate, display_size = EXCLUDED.display_size;",
+            &[
+                &policy.strategy,
+                &policy.sub_strategy,
+                &serde_plain::to_string(&policy.algorithm)?,
+                &policy.min_notional,
+                &policy.duration_seconds,
+                &policy.slices,
+                &policy.participation_rate,
+                &policy.display_size,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_executions<T: GenericClient>(client: &T) -> Result<Vec<Execution>> {
+    trace!("Fetching all executions");
+    client
+        .query("SELECT * FROM executions", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, ticker))]
+pub async fn get_executions_by_ticker<T: GenericClient>(client: &T, ticker: &str) -> Result<Vec<Execution>> {
+    trace!(ticker, "Fetching executions for ticker");
+    client
+        .query(
+            "SELECT * FROM executions WHERE ticker = $1 AND abandoned_at IS NULL",
+            &[&ticker],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, id))]
+pub async fn get_execution_by_id<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<Execution>> {
+    trace!(%id, "Fetching execution for id");
+    client
+        .query_opt("SELECT * FROM executions WHERE id = $1", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, working_id))]
+pub async fn get_execution_by_working_id<T: GenericClient>(client: &T, working_id: Uuid) -> Result<Option<Execution>> {
+    trace!(%working_id, "Fetching execution for working child trade");
+    client
+        .query_opt(
+            "SELECT * FROM executions WHERE working_id = $1 AND abandoned_at IS NULL",
+            &[&working_id],
+        )
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, execution))]
+pub async fn save_execution<T: GenericClient>(client: &T, execution: &Execution) -> Result<()> {
+    trace!(id = %execution.id, "Saving execution");
+    client
+        .execute(
+            "INSERT INTO executions (id, claim_id, ticker, account, algorithm, total_quantity, remaining_quantity, slices_remaining, interval_seconds, participation_rate, display_size, limit_price, stop_price, last_volume, working_id, next_release_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16) ON CONFLICT (id) DO UPDATE SET remaining_quantity = EXCLUDED.remaining_quantity, slices_remaining = EXCLUDED.slices_remaining, last_volume = EXCLUDED.last_volume, working_id = EXCLUDED.working_id, next_release_at = EXCLUDED.next_release_at;",
+            &[
+                &execution.id,
+                &execution.claim_id,
+                &execution.ticker,
+                &execution.account,
+                &serde_plain::to_string(&execution.algorithm)?,
+                &execution.total_quantity,
+                &execution.remaining_quantity,
+                &execution.slices_remaining,
+                &execution.interval_seconds,
+                &execution.participation_rate,
+                &execution.display_size,
+                &execution.limit_price,
+                &execution.stop_price,
+                &execution.last_volume,
+                &execution.working_id,
+                &execution.next_release_at,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Marks an execution as abandoned, keeping it as a record of what was worked.
+#[tracing::instrument(skip(client, id))]
+pub async fn abandon_execution<T: GenericClient>(client: &T, id: Uuid) -> Result<()> {
+    trace!(%id, "Abandoning execution");
+    client
+        .execute(
+            "UPDATE executions SET abandoned_at = now(), next_release_at = NULL WHERE id = $1",
+            &[&id],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client, id))]
+pub async fn delete_execution<T: GenericClient>(client: &T, id: Uuid) -> Result<()> {
+    trace!(%id, "Deleting execution");
+    client.execute("DELETE FROM executions WHERE id = $1", &[&id]).await?;
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use crate::types::{HouseAction, HouseActionState};
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_house_actions<T: GenericClient>(client: &T) -> Result<Vec<HouseAction>> {
+    trace!("Getting house actions");
+    client
+        .query("SELECT * FROM house_actions ORDER BY created_at DESC", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn get_house_actions_by_state<T: GenericClient>(
+    client: &T,
+    state: HouseActionState,
+) -> Result<Vec<HouseAction>> {
+    trace!(?state, "Getting house actions by state");
+    client
+        .query(
+            "SELECT * FROM house_actions WHERE state = $1 ORDER BY created_at",
+            &[&serde_plain::to_string(&state)?],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Gets the last action proposed for a house position. An action that is still waiting to be
+/// approved or applied is always the last one.
+#[tracing::instrument(skip(client, account, ticker))]
+pub async fn get_last_house_action<T: GenericClient>(
+    client: &T,
+    account: &str,
+    ticker: &str,
+) -> Result<Option<HouseAction>> {
+    trace!(account, ticker, "Getting last house action");
+    client
+        .query_opt(
+            "SELECT * FROM house_actions WHERE account = $1 AND ticker = $2 ORDER BY created_at DESC LIMIT 1",
+            &[&account, &ticker],
+        )
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, action), fields(id = %action.id))]
+pub async fn save_house_action<T: GenericClient>(client: &T, action: &HouseAction) -> Result<()> {
+    trace!("Saving house action");
+    client
+        .execute(
+            "INSERT INTO house_actions (id, account, ticker, policy, shares, state, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[
+                &action.id,
+                &action.account,
+                &action.ticker,
+                &serde_plain::to_string(&action.policy)?,
+                &action.shares,
+                &serde_plain::to_string(&action.state)?,
+                &action.created_at,
+                &action.updated_at,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Moves a house action from one state to another, returning the number of actions updated,
+/// which is zero if the action was not in the `from` state.
+#[tracing::instrument(skip(client))]
+pub async fn transition_house_action<T: GenericClient>(
+    client: &T,
+    id: Uuid,
+    from: HouseActionState,
+    to: HouseActionState,
+) -> Result<u64> {
+    trace!("Updating house action state");
+    let updated = client
+        .execute(
+            "UPDATE house_actions SET state = $1, updated_at = now() WHERE id = $2 AND state = $3",
+            &[&serde_plain::to_string(&to)?, &id, &serde_plain::to_string(&from)?],
+        )
+        .await?;
+    Ok(updated)
+}
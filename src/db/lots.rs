@@ -22,16 +22,7 @@ pub async fn get_lots_by_order_id<T: GenericClient>(client: &T, order_id: Uuid)
         .query("SELECT * FROM lots WHERE order_id = $1", &[&order_id])
         .await?
         .into_iter()
-        .map(|row| -> Result<Lot, Error> {
-            Ok(Lot {
-                id: row.try_get(0)?,
-                order_id: row.try_get(1)?,
-                ticker: row.try_get(2)?,
-                fill_time: row.try_get(3)?,
-                price: row.try_get(4)?,
-                shares: row.try_get(5)?,
-            })
-        })
+        .map(TryInto::try_into)
         .collect()
 }
 
@@ -40,7 +31,7 @@ pub async fn save_lot<T: GenericClient>(client: &T, lot: &Lot) -> Result<(), Err
     trace!(id = %lot.id, "Saving lot");
     client
         .execute(
-            "INSERT INTO lots (id, order_id, ticker, fill_time, price, shares) VALUES ($1, $2, $3, $4, $5, $6);",
+            "INSERT INTO lots (id, order_id, ticker, fill_time, price, shares, fees, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8);",
             &[
                 &lot.id,
                 &lot.order_id,
@@ -48,6 +39,8 @@ pub async fn save_lot<T: GenericClient>(client: &T, lot: &Lot) -> Result<(), Err
                 &lot.fill_time,
                 &lot.price,
                 &lot.shares,
+                &lot.fees,
+                &lot.account,
             ],
         )
         .await?;
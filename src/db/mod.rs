@@ -1,15 +1,43 @@
+mod accounts;
 mod allocations;
+mod brackets;
+mod budgets;
+mod cash;
 mod claims;
+mod corporate_actions;
 mod dependent_trades;
+mod executions;
+mod house_actions;
 mod lots;
+mod order_instructions;
+mod owners;
 mod positions;
+mod queued_trades;
+mod recurring_intents;
+mod risk_check_requests;
 mod scheduled_intents;
+mod stale_trade_resolutions;
+mod trade_events;
 mod trades;
 mod utils;
+pub use accounts::*;
 pub use allocations::*;
+pub use brackets::*;
+pub use budgets::*;
+pub use cash::*;
 pub use claims::*;
+pub use corporate_actions::*;
 pub use dependent_trades::*;
+pub use executions::*;
+pub use house_actions::*;
 pub use lots::*;
+pub use order_instructions::*;
+pub use owners::*;
 pub use positions::*;
+pub use queued_trades::*;
+pub use recurring_intents::*;
+pub use risk_check_requests::*;
 pub use scheduled_intents::*;
+pub use stale_trade_resolutions::*;
+pub use trade_events::*;
 pub use trades::*;
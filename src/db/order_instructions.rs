@@ -0,0 +1,60 @@
+use crate::types::OrderInstructions;
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_order_instructions<T: GenericClient>(
+    client: &T,
+    intent_id: Uuid,
+) -> Result<Option<OrderInstructions>> {
+    trace!(%intent_id, "Fetching order instructions for intent");
+    client
+        .query_opt("SELECT * FROM order_instructions WHERE intent_id = $1", &[&intent_id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, instructions))]
+pub async fn save_order_instructions<T: GenericClient>(
+    client: &T,
+    intent_id: Uuid,
+    instructions: &OrderInstructions,
+) -> Result<()> {
+    trace!(%intent_id, "Saving order instructions");
+    let time_in_force = instructions
+        .time_in_force
+        .map(|time_in_force| serde_plain::to_string(&time_in_force))
+        .transpose()?;
+    client
+        .execute(
+            "INSERT INTO order_instructions (intent_id, order_kind, trail_price, trail_percent, time_in_force, extended_hours, take_profit, stop_loss, trigger_condition, trigger_quantity) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (intent_id) DO UPDATE SET
+            order_kind = EXCLUDED.order_kind,
+            trail_price = EXCLUDED.trail_price,
+            trail_percent = EXCLUDED.trail_percent,
+            time_in_force = EXCLUDED.time_in_force,
+            extended_hours = EXCLUDED.extended_hours,
+            take_profit = EXCLUDED.take_profit,
+            stop_loss = EXCLUDED.stop_loss,
+            trigger_condition = EXCLUDED.trigger_condition,
+            trigger_quantity = EXCLUDED.trigger_quantity",
+            &[
+                &intent_id,
+                &serde_plain::to_string(&instructions.order_kind)?,
+                &instructions.trail_price,
+                &instructions.trail_percent,
+                &time_in_force,
+                &instructions.extended_hours,
+                &instructions.take_profit,
+                &instructions.stop_loss,
+                &serde_plain::to_string(&instructions.trigger)?,
+                &instructions.trigger_quantity,
+            ],
+        )
+        .await?;
+    Ok(())
+}
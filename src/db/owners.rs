@@ -0,0 +1,16 @@
+use crate::types::Owner;
+use tokio_postgres::{Error, GenericClient};
+use tracing::trace;
+
+/// Returns the id of the owner, registering it first if it is new.
+#[tracing::instrument(skip(client, owner))]
+pub async fn get_or_create_owner_id<T: GenericClient>(client: &T, owner: &Owner) -> Result<i32, Error> {
+    trace!(%owner, "Fetching owner id");
+    client
+        .query_one(
+            "INSERT INTO owners (owner) VALUES ($1) ON CONFLICT (owner) DO UPDATE SET owner = EXCLUDED.owner RETURNING id",
+            &[owner],
+        )
+        .await?
+        .try_get("id")
+}
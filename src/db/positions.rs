@@ -3,25 +3,22 @@ use std::convert::TryInto;
 use tokio_postgres::{Error, GenericClient};
 use tracing::trace;
 
+/// A strategy owner without a sub-strategy matches the positions of all its sub-strategies.
 #[tracing::instrument(skip(client, owner))]
 pub async fn get_positions_by_owner<T: GenericClient>(client: &T, owner: &Owner) -> Result<Vec<Position>, Error> {
     trace!(%owner, "Fetching positions for owner");
-    let (owner, sub_owner) = match owner {
-        Owner::House => ("House", None),
-        Owner::Strategy(owner, sub_owner) => (owner.as_str(), sub_owner.as_ref()),
-    };
-    let res = match sub_owner {
-        Some(sub_owner) => {
+    let res = match owner {
+        Owner::Strategy(strategy, None) => {
             client
                 .query(
-                    "SELECT * FROM positions WHERE owner = $1 AND sub_owner = $2",
-                    &[&owner, &sub_owner],
+                    "SELECT * FROM positions WHERE (owner).kind = 'strategy' AND (owner).strategy = $1",
+                    &[strategy],
                 )
                 .await?
         }
-        None => {
+        owner => {
             client
-                .query("SELECT * FROM positions WHERE owner = $1", &[&owner])
+                .query("SELECT * FROM positions WHERE owner = $1", &[owner])
                 .await?
         }
     };
@@ -29,43 +26,50 @@ pub async fn get_positions_by_owner<T: GenericClient>(client: &T, owner: &Owner)
     res.into_iter().map(TryInto::try_into).collect()
 }
 
-#[tracing::instrument(skip(client, ticker))]
-pub async fn get_positions_by_ticker<T: GenericClient>(client: &T, ticker: &str) -> Result<Vec<Position>, Error> {
-    trace!(ticker, "Fetching positions for ticker");
+#[tracing::instrument(skip(client, account, ticker))]
+pub async fn get_positions_by_ticker<T: GenericClient>(
+    client: &T,
+    account: &str,
+    ticker: &str,
+) -> Result<Vec<Position>, Error> {
+    trace!(account, ticker, "Fetching positions for ticker");
     client
-        .query("SELECT * FROM positions WHERE ticker = $1", &[&ticker])
+        .query(
+            "SELECT * FROM positions WHERE account = $1 AND ticker = $2",
+            &[&account, &ticker],
+        )
         .await?
         .into_iter()
         .map(TryInto::try_into)
         .collect()
 }
 
-#[tracing::instrument(skip(client, owner, sub_owner, ticker))]
+/// A strategy owner without a sub-strategy matches the positions of all its sub-strategies.
+#[tracing::instrument(skip(client, owner, account, ticker))]
 pub async fn get_position_by_owner_and_ticker<T: GenericClient>(
     client: &T,
-    owner: &str,
-    sub_owner: Option<&str>,
+    owner: &Owner,
+    account: &str,
     ticker: &str,
 ) -> Result<Option<Position>, Error> {
-    trace!(%owner, ticker, "Fetching positions for owner and ticker");
-    let res = match sub_owner {
-        Some(sub_owner) => {
-            client
-                .query_opt(
-                    "SELECT * FROM positions WHERE owner = $1 AND sub_owner = $2 AND ticker = $3",
-                    &[&owner, &sub_owner, &ticker],
-                )
-                .await?
-        }
-        None => {
-            client
+    trace!(%owner, account, ticker, "Fetching positions for owner and ticker");
+    let res =
+        match owner {
+            Owner::Strategy(strategy, None) => client
                 .query_opt(
-                    "SELECT * FROM positions WHERE owner = $1 AND ticker = $2",
-                    &[&owner, &ticker],
+                    "SELECT * FROM positions WHERE (owner).kind = 'strategy' AND (owner).strategy = $1 AND account = $2 AND ticker = $3",
+                    &[strategy, &account, &ticker],
                 )
-                .await?
-        }
-    };
+                .await?,
+            owner => {
+                client
+                    .query_opt(
+                        "SELECT * FROM positions WHERE owner = $1 AND account = $2 AND ticker = $3",
+                        &[owner, &account, &ticker],
+                    )
+                    .await?
+            }
+        };
 
     res.map(TryInto::try_into).transpose()
 }
@@ -0,0 +1,50 @@
+use crate::types::QueuedTrade;
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_queued_trades<T: GenericClient>(client: &T) -> Result<Vec<QueuedTrade>> {
+    trace!("Fetching all queued trades");
+    client
+        .query("SELECT * FROM queued_trades ORDER BY queued_at", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, queued_trade))]
+pub async fn save_queued_trade<T: GenericClient>(client: &T, queued_trade: &QueuedTrade) -> Result<()> {
+    trace!(id = %queued_trade.id, "Saving queued trade");
+    client
+        .execute(
+            "INSERT INTO queued_trades (id, ticker, intent, extensions, account, claim_id, queued_at, risk_checked) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[
+                &queued_trade.id,
+                &queued_trade.order.intent.ticker,
+                &serde_json::to_string(&queued_trade.order.intent)?,
+                &serde_json::to_string(&queued_trade.order.extensions)?,
+                &queued_trade.order.account,
+                &queued_trade.claim_id,
+                &queued_trade.queued_at,
+                &queued_trade.risk_checked,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn take_queued_trades<T: GenericClient>(client: &T) -> Result<Vec<QueuedTrade>> {
+    trace!("Fetching and deleting queued trades");
+    let mut queued_trades: Vec<QueuedTrade> = client
+        .query("DELETE FROM queued_trades RETURNING *", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_>>()?;
+    queued_trades.sort_by_key(|queued_trade| queued_trade.queued_at);
+    Ok(queued_trades)
+}
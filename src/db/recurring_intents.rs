@@ -0,0 +1,74 @@
+use crate::types::RecurringIntent;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_recurring_intents<T: GenericClient>(client: &T) -> Result<Vec<RecurringIntent>> {
+    trace!("Fetching all recurring intents");
+    client
+        .query("SELECT * FROM recurring_intents", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, id))]
+pub async fn get_recurring_intent<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<RecurringIntent>> {
+    trace!(%id, "Fetching recurring intent for id");
+    client
+        .query_opt("SELECT * FROM recurring_intents WHERE id = $1", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+/// Saves the schedule and template of a recurring intent. Its next run is recalculated when the
+/// scheduler picks it up.
+#[tracing::instrument(skip(client, recurring_intent), fields(id = %recurring_intent.id))]
+pub async fn upsert_recurring_intent<T: GenericClient>(client: &T, recurring_intent: &RecurringIntent) -> Result<()> {
+    trace!("Saving recurring intent");
+    client
+        .execute(
+            "INSERT INTO recurring_intents (id, strategy, schedule, intent, instructions) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (id) DO UPDATE SET strategy = EXCLUDED.strategy, schedule = EXCLUDED.schedule, intent = EXCLUDED.intent, instructions = EXCLUDED.instructions, next_run_at = NULL",
+            &[
+                &recurring_intent.id,
+                &recurring_intent.intent.strategy,
+                &serde_json::to_string(&recurring_intent.schedule)?,
+                &serde_json::to_string(&recurring_intent.intent)?,
+                &serde_json::to_string(&recurring_intent.instructions)?,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client, id, next_run_at, last_run_at))]
+pub async fn update_recurring_intent_runs<T: GenericClient>(
+    client: &T,
+    id: Uuid,
+    next_run_at: Option<DateTime<Utc>>,
+    last_run_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+    trace!(%id, ?next_run_at, ?last_run_at, "Updating recurring intent runs");
+    client
+        .execute(
+            "UPDATE recurring_intents SET next_run_at = $1, last_run_at = $2 WHERE id = $3",
+            &[&next_run_at, &last_run_at, &id],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client, id))]
+pub async fn delete_recurring_intent<T: GenericClient>(client: &T, id: Uuid) -> Result<bool> {
+    trace!(%id, "Deleting recurring intent");
+    let deleted = client
+        .execute("DELETE FROM recurring_intents WHERE id = $1", &[&id])
+        .await?;
+    Ok(deleted > 0)
+}
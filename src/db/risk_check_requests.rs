@@ -0,0 +1,67 @@
+use crate::types::RiskCheckRequest;
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_risk_check_requests<T: GenericClient>(client: &T) -> Result<Vec<RiskCheckRequest>> {
+    trace!("Fetching all risk check requests");
+    client
+        .query("SELECT * FROM risk_check_requests", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// The outstanding risk check requests for trades in a ticker in an account.
+#[tracing::instrument(skip(client, account, ticker))]
+pub async fn get_risk_check_requests_by_ticker<T: GenericClient>(
+    client: &T,
+    account: &str,
+    ticker: &str,
+) -> Result<Vec<RiskCheckRequest>> {
+    trace!(account, ticker, "Fetching risk check requests for ticker");
+    client
+        .query(
+            "SELECT * FROM risk_check_requests WHERE account = $1 AND ticker = $2",
+            &[&account, &ticker],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, request))]
+pub async fn save_risk_check_request<T: GenericClient>(client: &T, request: &RiskCheckRequest) -> Result<()> {
+    trace!(id = %request.id, "Saving risk check request");
+    client
+        .execute(
+            "INSERT INTO risk_check_requests (id, ticker, intent, extensions, account, claim_id, sent_at, attempts) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (id) DO UPDATE SET sent_at = EXCLUDED.sent_at, attempts = EXCLUDED.attempts;",
+            &[
+                &request.id,
+                &request.order.intent.ticker,
+                &serde_json::to_string(&request.order.intent)?,
+                &serde_json::to_string(&request.order.extensions)?,
+                &request.order.account,
+                &request.claim_id,
+                &request.sent_at,
+                &request.attempts,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client, id))]
+pub async fn take_risk_check_request<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<RiskCheckRequest>> {
+    trace!(%id, "Fetching and deleting risk check request");
+    client
+        .query_opt("DELETE FROM risk_check_requests WHERE id = $1 RETURNING *", &[&id])
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
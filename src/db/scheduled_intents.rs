@@ -1,40 +1,56 @@
 use super::utils::{split_amount_spec, unite_amount_spec};
 use anyhow::Result;
-use tokio_postgres::GenericClient;
+use chrono::{DateTime, Utc};
+use tokio_postgres::{GenericClient, Row};
 use tracing::trace;
 use trading_base::{Identifier, PositionIntent};
 use uuid::Uuid;
 
+fn intent_from_row(row: Row) -> Result<PositionIntent> {
+    let identifier = match row.try_get("ticker")? {
+        "all_" => Identifier::All,
+        s => Identifier::Ticker(s.to_string()),
+    };
+    Ok(PositionIntent {
+        id: row.try_get("id")?,
+        strategy: row.try_get("strategy")?,
+        sub_strategy: row.try_get("sub_strategy")?,
+        timestamp: row.try_get("time_stamp")?,
+        identifier,
+        amount: unite_amount_spec(row.try_get("amount")?, row.try_get("unit")?),
+        update_policy: serde_plain::from_str(row.try_get("update_policy")?)?,
+        decision_price: row.try_get("decision_price")?,
+        limit_price: row.try_get("limit_price")?,
+        stop_price: row.try_get("stop_price")?,
+        before: row.try_get("before")?,
+        after: row.try_get("after")?,
+    })
+}
+
 #[tracing::instrument(skip(client))]
-pub async fn get_scheduled_indents<T: GenericClient>(client: &T) -> Result<Vec<PositionIntent>> {
+pub async fn get_scheduled_intents<T: GenericClient>(client: &T) -> Result<Vec<PositionIntent>> {
     trace!("Fetching all scheduled intents");
     client
-        .query("SELECT * FROM scheduled_intents", &[])
+        .query("SELECT * FROM scheduled_intents WHERE cancelled_at IS NULL", &[])
         .await?
         .into_iter()
-        .map(|row| {
-            let identifier = match row.try_get("ticker")? {
-                "all_" => Identifier::All,
-                s => Identifier::Ticker(s.to_string()),
-            };
-            Ok(PositionIntent {
-                id: row.try_get("id")?,
-                strategy: row.try_get("strategy")?,
-                sub_strategy: row.try_get("sub_strategy")?,
-                timestamp: row.try_get("time_stamp")?,
-                identifier,
-                amount: unite_amount_spec(row.try_get("amount")?, row.try_get("unit")?),
-                update_policy: serde_plain::from_str(row.try_get("update_policy")?)?,
-                decision_price: row.try_get("decision_price")?,
-                limit_price: row.try_get("limit_price")?,
-                stop_price: row.try_get("stop_price")?,
-                before: row.try_get("before")?,
-                after: row.try_get("after")?,
-            })
-        })
+        .map(intent_from_row)
         .collect()
 }
 
+#[tracing::instrument(skip(client, id))]
+pub async fn get_scheduled_intent<T: GenericClient>(client: &T, id: Uuid) -> Result<Option<PositionIntent>> {
+    trace!(%id, "Fetching scheduled intent for id");
+    client
+        .query_opt(
+            "SELECT * FROM scheduled_intents WHERE id = $1 AND cancelled_at IS NULL",
+            &[&id],
+        )
+        .await?
+        .map(intent_from_row)
+        .transpose()
+}
+
 #[tracing::instrument(skip(client, scheduled_intent))]
 pub async fn save_scheduled_intent<T: GenericClient>(client: &T, scheduled_intent: &PositionIntent) -> Result<()> {
     trace!("Saving scheduled intent");
@@ -88,3 +104,41 @@ pub async fn delete_scheduled_intent<T: GenericClient>(client: &T, id: Uuid) ->
         .await?;
     Ok(())
 }
+
+#[tracing::instrument(skip(client, id))]
+pub async fn cancel_scheduled_intent<T: GenericClient>(client: &T, id: Uuid) -> Result<bool> {
+    trace!(%id, "Cancelling scheduled intent for id");
+    let cancelled = client
+        .execute(
+            "UPDATE scheduled_intents SET cancelled_at = now() WHERE id = $1 AND cancelled_at IS NULL",
+            &[&id],
+        )
+        .await?;
+    Ok(cancelled > 0)
+}
+
+#[tracing::instrument(skip(client, strategy))]
+pub async fn cancel_scheduled_intents_by_strategy<T: GenericClient>(client: &T, strategy: &str) -> Result<Vec<Uuid>> {
+    trace!(strategy, "Cancelling scheduled intents for strategy");
+    client
+        .query(
+            "UPDATE scheduled_intents SET cancelled_at = now() WHERE strategy = $1 AND cancelled_at IS NULL RETURNING id",
+            &[&strategy],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Ok(row.try_get("id")?))
+        .collect()
+}
+
+#[tracing::instrument(skip(client, id, after))]
+pub async fn reschedule_scheduled_intent<T: GenericClient>(client: &T, id: Uuid, after: DateTime<Utc>) -> Result<bool> {
+    trace!(%id, %after, "Rescheduling scheduled intent for id");
+    let rescheduled = client
+        .execute(
+            "UPDATE scheduled_intents SET after = $1 WHERE id = $2 AND cancelled_at IS NULL",
+            &[&after, &id],
+        )
+        .await?;
+    Ok(rescheduled > 0)
+}
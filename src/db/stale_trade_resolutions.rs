@@ -0,0 +1,54 @@
+use crate::types::StaleTradeResolution;
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::GenericClient;
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_stale_trade_resolutions<T: GenericClient>(client: &T) -> Result<Vec<StaleTradeResolution>> {
+    trace!("Getting stale trade resolutions");
+    client
+        .query("SELECT * FROM stale_trade_resolutions ORDER BY datetime DESC", &[])
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, trade_id))]
+pub async fn get_last_stale_trade_resolution<T: GenericClient>(
+    client: &T,
+    trade_id: Uuid,
+) -> Result<Option<StaleTradeResolution>> {
+    trace!(%trade_id, "Getting last stale trade resolution");
+    client
+        .query_opt(
+            "SELECT * FROM stale_trade_resolutions WHERE trade_id = $1 ORDER BY datetime DESC LIMIT 1",
+            &[&trade_id],
+        )
+        .await?
+        .map(TryInto::try_into)
+        .transpose()
+}
+
+#[tracing::instrument(skip(client, resolution), fields(trade_id = %resolution.trade_id))]
+pub async fn save_stale_trade_resolution<T: GenericClient>(
+    client: &T,
+    resolution: &StaleTradeResolution,
+) -> Result<()> {
+    trace!("Saving stale trade resolution");
+    client
+        .execute(
+            "INSERT INTO stale_trade_resolutions (trade_id, ticker, status, resolution, datetime) VALUES ($1, $2, $3, $4, $5)",
+            &[
+                &resolution.trade_id,
+                &resolution.ticker,
+                &resolution.status,
+                &serde_plain::to_string(&resolution.resolution)?,
+                &resolution.datetime,
+            ],
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::types::TradeEvent;
+use anyhow::Result;
+use std::convert::TryInto;
+use tokio_postgres::{Error, GenericClient};
+use tracing::trace;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(client))]
+pub async fn get_trade_events<T: GenericClient>(client: &T, trade_id: Uuid) -> Result<Vec<TradeEvent>, Error> {
+    trace!(%trade_id, "Fetching events for trade");
+    client
+        .query(
+            "SELECT * FROM trade_events WHERE trade_id = $1 ORDER BY datetime",
+            &[&trade_id],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+#[tracing::instrument(skip(client, event))]
+pub async fn save_trade_event<T: GenericClient>(client: &T, event: &TradeEvent) -> Result<()> {
+    trace!(trade_id = %event.trade_id, "Saving trade event");
+    client
+        .execute(
+            "INSERT INTO trade_events (trade_id, from_status, to_status, rejected, datetime) VALUES ($1, $2, $3, $4, $5)",
+            &[
+                &event.trade_id,
+                &event.from_status,
+                &event.to_status,
+                &event.rejected,
+                &event.datetime,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn count_rejected_trade_events<T: GenericClient>(client: &T) -> Result<i64> {
+    trace!("Counting rejected trade events");
+    let row = client
+        .query_one("SELECT count(*) FROM trade_events WHERE rejected", &[])
+        .await?;
+    Ok(row.try_get(0)?)
+}
@@ -1,25 +1,39 @@
 use crate::types::{Status, Trade};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use std::convert::TryInto;
 use tokio_postgres::{Error, GenericClient};
 use tracing::trace;
 use uuid::Uuid;
 
-#[tracing::instrument(skip(client, ticker))]
-pub async fn get_active_trade_amount_by_ticker<T: GenericClient>(client: &T, ticker: &str) -> Result<i32, Error> {
-    trace!(ticker, "Fetching pending trade amount for ticker");
+#[tracing::instrument(skip(client, account, ticker))]
+pub async fn get_active_trade_amount_by_ticker<T: GenericClient>(
+    client: &T,
+    account: &str,
+    ticker: &str,
+) -> Result<Decimal, Error> {
+    trace!(account, ticker, "Fetching pending trade amount for ticker");
     Ok(client
         .query(
             r#"
 SELECT pending_quantity
 FROM trades
-WHERE ticker = $1 AND status IN ('unreported', 'accepted', 'partially_filled')
+WHERE account = $1 AND ticker = $2 AND status IN ('unreported', 'accepted', 'partially_filled')
+-- Only one leg of a bracket can fill, so stop-losses with a working take-profit are not counted
+AND id NOT IN (
+    SELECT stop_loss.id
+    FROM bracket_legs stop_loss
+    JOIN bracket_legs take_profit ON take_profit.parent_id = stop_loss.parent_id AND take_profit.kind = 'take_profit'
+    JOIN trades ON trades.id = take_profit.id
+    WHERE stop_loss.kind = 'stop_loss' AND trades.status IN ('unreported', 'accepted', 'partially_filled')
+)
         "#,
-            &[&ticker],
+            &[&account, &ticker],
         )
         .await?
         .into_iter()
-        .fold(0, |acc, x| acc + x.get::<usize, i32>(0)))
+        .fold(Decimal::ZERO, |acc, x| acc + x.get::<usize, Decimal>(0)))
 }
 
 #[tracing::instrument(skip(client))]
@@ -33,6 +47,33 @@ pub async fn get_trades<T: GenericClient>(client: &T) -> Result<Vec<Trade>, Erro
         .collect()
 }
 
+/// Trades in the given status that have not had an accepted update since `since`.
+#[tracing::instrument(skip(client))]
+pub async fn get_stale_trades<T: GenericClient>(
+    client: &T,
+    status: Status,
+    since: DateTime<Utc>,
+) -> Result<Vec<Trade>, Error> {
+    trace!(?status, %since, "Fetching stale trades");
+    client
+        .query(
+            r#"
+SELECT *
+FROM trades
+WHERE status = $1
+AND COALESCE(
+    (SELECT MAX(datetime) FROM trade_events WHERE trade_id = trades.id AND NOT rejected),
+    trades.datetime
+) < $2
+        "#,
+            &[&status, &since],
+        )
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
 #[tracing::instrument(skip(client))]
 pub async fn get_trades_by_ticker<T: GenericClient>(client: &T, ticker: &str) -> Result<Vec<Trade>, Error> {
     trace!(ticker, "Fetching trades for ticker");
@@ -55,8 +96,8 @@ pub async fn get_trade_by_id<T: GenericClient>(client: &T, id: Uuid) -> Result<O
 }
 
 #[tracing::instrument(skip(client, id, quantity))]
-pub async fn update_trade_quantity<T: GenericClient>(client: &T, id: Uuid, quantity: i32) -> Result<()> {
-    trace!(%id, quantity, "Updating trade quantity");
+pub async fn update_trade_quantity<T: GenericClient>(client: &T, id: Uuid, quantity: Decimal) -> Result<()> {
+    trace!(%id, %quantity, "Updating trade quantity");
     client
         .execute(
             "UPDATE trades SET pending_quantity = $1 WHERE id = $2",
@@ -66,6 +107,23 @@ pub async fn update_trade_quantity<T: GenericClient>(client: &T, id: Uuid, quant
     Ok(())
 }
 
+#[tracing::instrument(skip(client, trade), fields(id = %trade.id))]
+pub async fn update_trade_fill<T: GenericClient>(client: &T, trade: &Trade) -> Result<()> {
+    trace!(filled_quantity = %trade.filled_quantity, "Updating trade fill");
+    client
+        .execute(
+            "UPDATE trades SET filled_quantity = $1, average_fill_price = $2, fees = $3 WHERE id = $4",
+            &[
+                &trade.filled_quantity,
+                &trade.average_fill_price,
+                &trade.fees,
+                &trade.id,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip(client, id, status))]
 pub async fn update_status<T: GenericClient>(client: &T, id: Uuid, status: Status) -> Result<()> {
     trace!(%id, ?status, "Updating trade status");
@@ -75,11 +133,41 @@ pub async fn update_status<T: GenericClient>(client: &T, id: Uuid, status: Statu
     Ok(())
 }
 
+#[tracing::instrument(skip(client, id))]
+pub async fn request_trade_cancel<T: GenericClient>(client: &T, id: Uuid) -> Result<()> {
+    trace!(%id, "Marking trade for cancellation");
+    client
+        .execute("UPDATE trades SET cancel_requested = TRUE WHERE id = $1", &[&id])
+        .await?;
+    Ok(())
+}
+
+/// Inserts a trade that is about to be sent. Returns false if a trade with the same id exists
+/// already, in which case it has been sent before and is left untouched.
+#[tracing::instrument(skip(client, trade))]
+pub async fn insert_trade<T: GenericClient>(client: &T, trade: &Trade) -> Result<bool> {
+    trace!(id = %trade.id, "Inserting trade");
+    let inserted = client.execute(
+        "INSERT INTO trades (id, broker_id, ticker, quantity, pending_quantity, datetime, status, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (id) DO NOTHING;",
+        &[
+            &trade.id,
+            &trade.broker_id,
+            &trade.ticker,
+            &trade.quantity,
+            &trade.pending_quantity,
+            &trade.datetime,
+            &trade.status,
+            &trade.account
+        ]
+    ).await?;
+    Ok(inserted == 1)
+}
+
 #[tracing::instrument(skip(client, trade))]
 pub async fn save_trade<T: GenericClient>(client: &T, trade: Trade) -> Result<()> {
     trace!(id = %trade.id, "Saving trade");
     client.execute(
-        "INSERT INTO trades (id, broker_id, ticker, quantity, pending_quantity, datetime, status) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (id) DO UPDATE SET broker_id = EXCLUDED.broker_id, pending_quantity = EXCLUDED.pending_quantity, status = EXCLUDED.status WHERE trades.status NOT IN ('filled', 'cancelled', 'dead');",
+        "INSERT INTO trades (id, broker_id, ticker, quantity, pending_quantity, datetime, status, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (id) DO UPDATE SET broker_id = EXCLUDED.broker_id, pending_quantity = EXCLUDED.pending_quantity, status = EXCLUDED.status WHERE trades.status NOT IN ('filled', 'cancelled', 'dead');",
         &[
             &trade.id,
             &trade.broker_id,
@@ -87,7 +175,8 @@ pub async fn save_trade<T: GenericClient>(client: &T, trade: Trade) -> Result<()
             &trade.quantity,
             &trade.pending_quantity,
             &trade.datetime,
-            &trade.status
+            &trade.status,
+            &trade.account
         ]
     ).await?;
     Ok(())
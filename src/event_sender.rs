@@ -1,5 +1,9 @@
-use crate::types::{Allocation, Claim, Lot};
+use crate::types::{
+    trade_topic, Allocation, BudgetDenial, Claim, IntentStatus, Lot, OrderExtensions, TradeOrder, DEFAULT_ACCOUNT,
+    ORDER_EXTENSIONS_HEADER, ORDER_EXTENSIONS_VERSION, ORDER_EXTENSIONS_VERSION_HEADER,
+};
 use anyhow::Result;
+use rdkafka::message::OwnedHeaders;
 use rdkafka::producer::{FutureProducer, FutureRecord};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -9,7 +13,9 @@ use trading_base::{TradeIntent, TradeMessage};
 
 struct EventSender {
     producer: FutureProducer,
-    receiver: mpsc::Receiver<Event>,
+    /// Events together with the broker account that trade messages are routed to, and the
+    /// extensions of new orders
+    receiver: mpsc::Receiver<(Event, Option<String>, Option<OrderExtensions>)>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -20,34 +26,53 @@ pub enum Event {
     Claim(Claim),
     Lot(Lot),
     RiskCheckRequest(TradeIntent),
+    BudgetDenial(BudgetDenial),
+    IntentStatus(IntentStatus),
 }
 
 impl EventSender {
-    fn new(producer: FutureProducer, receiver: mpsc::Receiver<Event>) -> Self {
+    fn new(
+        producer: FutureProducer,
+        receiver: mpsc::Receiver<(Event, Option<String>, Option<OrderExtensions>)>,
+    ) -> Self {
         Self { producer, receiver }
     }
 
     #[tracing::instrument(skip(self))]
     pub async fn run(&mut self) {
         info!("Starting EventSender");
-        while let Some(event) = self.receiver.recv().await {
-            info!("Sending event {:?}", event);
+        while let Some((event, account, extensions)) = self.receiver.recv().await {
+            info!(?account, ?extensions, "Sending event {:?}", event);
             let payload = serde_json::to_string(&event);
             if let Err(e) = payload {
                 return error!("{:?}", e);
             }
             let payload = payload.unwrap();
+            let trade_topic = trade_topic(account.as_deref().unwrap_or(DEFAULT_ACCOUNT));
             let (topic, key) = match event {
                 Event::TradeMessage(ref tm) => match tm {
-                    TradeMessage::New { intent } => ("trade-intents", intent.ticker.as_str()),
-                    TradeMessage::Cancel { .. } => ("trade-intents", ""),
+                    TradeMessage::New { intent } => (trade_topic.as_str(), intent.ticker.as_str()),
+                    TradeMessage::Cancel { .. } => (trade_topic.as_str(), ""),
                 },
                 Event::Allocation(ref alloc) => ("allocations", alloc.ticker.as_str()),
                 Event::Claim(ref claim) => ("claims", claim.ticker.as_str()),
                 Event::Lot(ref lot) => ("lots", lot.ticker.as_str()),
                 Event::RiskCheckRequest(ref intent) => ("risk-check-request", intent.ticker.as_str()),
+                Event::BudgetDenial(ref denial) => ("budget-denials", denial.ticker.as_str()),
+                Event::IntentStatus(ref status) => ("position-intent-status", status.strategy.as_str()),
             };
-            let record = FutureRecord::to(topic).key(key).payload(&payload);
+            let mut record = FutureRecord::to(topic).key(key).payload(&payload);
+            if let Some(extensions) = extensions {
+                match serde_json::to_string(&extensions) {
+                    Ok(extensions) => {
+                        let headers = OwnedHeaders::new()
+                            .add(ORDER_EXTENSIONS_VERSION_HEADER, ORDER_EXTENSIONS_VERSION)
+                            .add(ORDER_EXTENSIONS_HEADER, &extensions);
+                        record = record.headers(headers);
+                    }
+                    Err(e) => return error!("{:?}", e),
+                }
+            }
             let send = self.producer.send(record, Duration::ZERO).await;
             if let Err((e, m)) = send {
                 error!("Error: {:?}\nMessage: {:?}", e, m)
@@ -58,7 +83,7 @@ impl EventSender {
 }
 
 pub struct EventSenderHandle {
-    sender: mpsc::Sender<Event>,
+    sender: mpsc::Sender<(Event, Option<String>, Option<OrderExtensions>)>,
 }
 
 impl EventSenderHandle {
@@ -70,7 +95,23 @@ impl EventSenderHandle {
     }
 
     pub async fn send(&self, msg: Event) -> Result<()> {
-        self.sender.send(msg).await?;
+        self.sender.send((msg, None, None)).await?;
+        Ok(())
+    }
+
+    /// Sends an event for a broker account. Trade messages are routed to the topic of the
+    /// account, all other events are sent as usual.
+    pub async fn send_for_account(&self, account: &str, msg: Event) -> Result<()> {
+        self.sender.send((msg, Some(account.to_string()), None)).await?;
+        Ok(())
+    }
+
+    /// Sends a new order to the topic of its broker account. Its extensions are sent in the
+    /// `order-extensions` header, which is left out for orders without extensions.
+    pub async fn send_order(&self, order: TradeOrder) -> Result<()> {
+        let extensions = Some(order.extensions).filter(|extensions| !extensions.is_default());
+        let msg = Event::TradeMessage(TradeMessage::New { intent: order.intent });
+        self.sender.send((msg, Some(order.account), extensions)).await?;
         Ok(())
     }
 }
@@ -1,21 +1,60 @@
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use futures::stream::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio_util::time::delay_queue::DelayQueue;
+use tokio_util::time::delay_queue::{DelayQueue, Key};
 use tracing::{debug, error, info};
 use trading_base::PositionIntent;
+use uuid::Uuid;
+
+/// Requests sent to the scheduler
+#[derive(Debug)]
+pub enum ScheduleCommand {
+    /// Holds an intent until its `after` time
+    PositionIntent(PositionIntent),
+    /// Release of the next child trade of an execution
+    ExecutionSlice { id: Uuid, at: DateTime<Utc> },
+    /// Next run of a recurring intent
+    RecurringIntent { id: Uuid, at: DateTime<Utc> },
+    /// Next run of the reconciliation checks
+    Reconcile { at: DateTime<Utc> },
+    /// Drops a scheduled intent
+    Cancel { id: Uuid },
+    /// Moves the trigger time of a scheduled intent
+    Reschedule { id: Uuid, at: DateTime<Utc> },
+}
+
+/// Notifications sent by the scheduler once something it holds is due
+#[derive(Debug)]
+pub enum Scheduled {
+    PositionIntent(PositionIntent),
+    ExecutionSlice {
+        id: Uuid,
+    },
+    RecurringIntent {
+        id: Uuid,
+        at: DateTime<Utc>,
+    },
+    Reconcile,
+    /// A scheduled intent that was dropped before it triggered
+    Dropped(PositionIntent),
+}
 
 pub struct IntentScheduler {
-    scheduled_intents: DelayQueue<PositionIntent>,
-    receiver: UnboundedReceiver<PositionIntent>,
-    sender: UnboundedSender<PositionIntent>,
+    scheduled_intents: DelayQueue<Scheduled>,
+    /// Queue keys of the scheduled position intents, by intent id
+    keys: HashMap<Uuid, Key>,
+    receiver: UnboundedReceiver<ScheduleCommand>,
+    sender: UnboundedSender<Scheduled>,
 }
 
 impl IntentScheduler {
-    pub fn new(sender: UnboundedSender<PositionIntent>, receiver: UnboundedReceiver<PositionIntent>) -> Self {
+    pub fn new(sender: UnboundedSender<Scheduled>, receiver: UnboundedReceiver<ScheduleCommand>) -> Self {
         Self {
             scheduled_intents: DelayQueue::new(),
+            keys: HashMap::new(),
             sender,
             receiver,
         }
@@ -37,7 +76,11 @@ impl IntentScheduler {
                 intent = self.scheduled_intents.next(), if !self.scheduled_intents.is_empty() => {
                     if let Some(Ok(intent)) = intent {
                         debug!("Scheduled intent triggered");
-                        if let Err(e) = self.sender.send(intent.into_inner()) {
+                        let intent = intent.into_inner();
+                        if let Scheduled::PositionIntent(ref intent) = intent {
+                            self.keys.remove(&intent.id);
+                        }
+                        if let Err(e) = self.sender.send(intent) {
                             error!("{}", e)
                         }
                     }
@@ -46,15 +89,103 @@ impl IntentScheduler {
         }
     }
 
-    #[tracing::instrument(skip(self, intent), fields(id = %intent.id))]
-    fn schedule(&mut self, mut intent: PositionIntent) -> Result<()> {
-        let trigger_time = intent
-            .after
-            .take() // We take the field so that there's no longer an `after` condition
-            .expect("schedule_position_intent called with intent lacking `after` field");
-        debug!("Scheduling intent for {}", trigger_time);
-        self.scheduled_intents
-            .insert(intent, (trigger_time - Utc::now()).to_std()?);
+    #[tracing::instrument(skip(self, command))]
+    fn schedule(&mut self, command: ScheduleCommand) -> Result<()> {
+        match command {
+            ScheduleCommand::PositionIntent(mut intent) => {
+                let trigger_time = intent
+                    .after
+                    .take() // We take the field so that there's no longer an `after` condition
+                    .ok_or_else(|| anyhow!("Intent {} scheduled without an `after` field", intent.id))?;
+                debug!(id = %intent.id, "Scheduling intent for {}", trigger_time);
+                let id = intent.id;
+                // Intents that are overdue, for example after a restart, are released immediately
+                let key = self
+                    .scheduled_intents
+                    .insert(Scheduled::PositionIntent(intent), delay_until(trigger_time));
+                self.keys.insert(id, key);
+            }
+            ScheduleCommand::ExecutionSlice { id, at } => {
+                debug!(%id, "Scheduling execution slice for {}", at);
+                // Slices that are overdue, for example after a restart, are released immediately
+                self.scheduled_intents
+                    .insert(Scheduled::ExecutionSlice { id }, delay_until(at));
+            }
+            ScheduleCommand::RecurringIntent { id, at } => {
+                debug!(%id, "Scheduling recurring intent for {}", at);
+                self.scheduled_intents
+                    .insert(Scheduled::RecurringIntent { id, at }, delay_until(at));
+            }
+            ScheduleCommand::Reconcile { at } => {
+                self.scheduled_intents.insert(Scheduled::Reconcile, delay_until(at));
+            }
+            ScheduleCommand::Cancel { id } => {
+                if let Some(key) = self.keys.remove(&id) {
+                    debug!(%id, "Dropping scheduled intent");
+                    let dropped = self.scheduled_intents.remove(&key).into_inner();
+                    if let Scheduled::PositionIntent(intent) = dropped {
+                        self.sender.send(Scheduled::Dropped(intent))?;
+                    }
+                }
+            }
+            ScheduleCommand::Reschedule { id, at } => {
+                if let Some(key) = self.keys.get(&id) {
+                    debug!(%id, "Rescheduling intent for {}", at);
+                    self.scheduled_intents.reset(key, delay_until(at));
+                }
+            }
+        }
         Ok(())
     }
 }
+
+fn delay_until(at: DateTime<Utc>) -> Duration {
+    (at - Utc::now()).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+    use trading_base::Amount;
+
+    fn scheduler() -> (IntentScheduler, UnboundedReceiver<Scheduled>) {
+        let (sender, receiver) = unbounded_channel();
+        let (_command_sender, command_receiver) = unbounded_channel();
+        (IntentScheduler::new(sender, command_receiver), receiver)
+    }
+
+    fn intent_after(after: DateTime<Utc>) -> PositionIntent {
+        PositionIntent::builder("A", "AAPL", Amount::Zero)
+            .after(after)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_drops_intent() {
+        let (mut scheduler, mut receiver) = scheduler();
+        let intent = intent_after(Utc::now() + chrono::Duration::hours(1));
+        let id = intent.id;
+        scheduler.schedule(ScheduleCommand::PositionIntent(intent)).unwrap();
+        scheduler.schedule(ScheduleCommand::Cancel { id }).unwrap();
+        assert!(matches!(receiver.try_recv(), Ok(Scheduled::Dropped(intent)) if intent.id == id));
+        assert!(scheduler.scheduled_intents.is_empty());
+        // Cancelling again is a no-op
+        scheduler.schedule(ScheduleCommand::Cancel { id }).unwrap();
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reschedule_releases_intent() {
+        let (mut scheduler, _receiver) = scheduler();
+        let intent = intent_after(Utc::now() + chrono::Duration::hours(1));
+        let id = intent.id;
+        scheduler.schedule(ScheduleCommand::PositionIntent(intent)).unwrap();
+        scheduler
+            .schedule(ScheduleCommand::Reschedule { id, at: Utc::now() })
+            .unwrap();
+        let released = scheduler.scheduled_intents.next().await.unwrap().unwrap().into_inner();
+        assert!(matches!(released, Scheduled::PositionIntent(intent) if intent.id == id && intent.after.is_none()));
+    }
+}
@@ -29,6 +29,7 @@ pub async fn run(settings: Settings) -> Result<()> {
     let producer = producer(&settings.kafka).context("Failed to create kafka producer")?;
     let (scheduled_intents_tx1, scheduled_intents_rx1) = unbounded_channel();
     let (scheduled_intents_tx2, scheduled_intents_rx2) = unbounded_channel();
+    let (commands_tx, commands_rx) = unbounded_channel();
     let event_sender_handle = EventSenderHandle::new(producer);
     let intent_scheduler = IntentScheduler::new(scheduled_intents_tx1, scheduled_intents_rx2);
     let (mut client, connection) =
@@ -42,15 +43,22 @@ pub async fn run(settings: Settings) -> Result<()> {
     let client = Arc::new(client);
     let order_manager = OrderManager::new(
         consumer,
-        scheduled_intents_tx2,
+        scheduled_intents_tx2.clone(),
         scheduled_intents_rx1,
+        commands_rx,
         event_sender_handle,
         client.clone(),
-        settings.datastore.base_url,
+        settings.datastore.base_url.clone(),
         settings.app,
     );
     tokio::join!(
-        webserver::run(settings.webserver.port, client),
+        webserver::run(
+            settings.webserver.port,
+            client,
+            scheduled_intents_tx2,
+            commands_tx,
+            settings.datastore.base_url
+        ),
         order_manager.run(),
         intent_scheduler.run()
     );
@@ -0,0 +1,18 @@
+use super::OrderManager;
+use crate::db;
+use crate::types::default_account;
+use anyhow::{Context, Result};
+use tracing::trace;
+
+impl OrderManager {
+    /// The broker account that the claims of a strategy are traded in.
+    #[tracing::instrument(skip(self, strategy, sub_strategy))]
+    pub(super) async fn get_strategy_account(&self, strategy: &str, sub_strategy: Option<&str>) -> Result<String> {
+        let maybe_route = db::get_account_route(self.db_client.as_ref(), strategy, sub_strategy)
+            .await
+            .context("Failed to get account route")?;
+        let account = maybe_route.map(|route| route.account).unwrap_or_else(default_account);
+        trace!(strategy, ?sub_strategy, %account, "Routing strategy");
+        Ok(account)
+    }
+}
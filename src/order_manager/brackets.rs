@@ -0,0 +1,149 @@
+use super::OrderManager;
+use crate::db;
+use crate::types::{
+    default_account, leg_quantity, validate_bracket, BracketLeg, BracketLegKind, Claim, DependentTrade,
+    OrderInstructions, TimeInForce, TradeOrder,
+};
+use anyhow::{Context, Result};
+use rust_decimal::prelude::*;
+use tracing::debug;
+use trading_base::TradeIntent;
+use uuid::Uuid;
+
+impl OrderManager {
+    /// Saves the take-profit and stop-loss legs protecting the position of a claim as dependent
+    /// trades of `parent`, so that they are sent once it has filled. A claim has at most one
+    /// bracket: legs waiting on an earlier trade of the claim are replaced, and no legs are
+    /// attached once the claim's bracket has been sent.
+    #[tracing::instrument(skip(self, parent, claim, instructions), fields(parent_id = %parent.id, claim_id = %claim.id))]
+    pub(super) async fn attach_bracket(
+        &self,
+        parent: &TradeIntent,
+        claim: &Claim,
+        shares: Decimal,
+        instructions: &OrderInstructions,
+    ) -> Result<()> {
+        let quantity = leg_quantity(shares, self.settings.is_fractional(&parent.ticker));
+        if let Some(quantity) = quantity {
+            validate_bracket(-quantity, instructions.take_profit, instructions.stop_loss)?;
+        }
+        let replaced = db::delete_pending_bracket_legs(self.db_client.as_ref(), claim.id)
+            .await
+            .context("Failed to delete pending bracket legs")?;
+        debug!(replaced, "Replaced pending bracket legs");
+        let live = db::get_bracket_legs_by_claim(self.db_client.as_ref(), claim.id).await?;
+        if !live.is_empty() {
+            debug!("Bracket of claim already sent");
+            return Ok(());
+        }
+        let quantity = match quantity {
+            Some(quantity) => quantity,
+            None => {
+                debug!(%shares, "Claim too small for bracket");
+                return Ok(());
+            }
+        };
+        let levels = [
+            (BracketLegKind::TakeProfit, instructions.take_profit),
+            (BracketLegKind::StopLoss, instructions.stop_loss),
+        ];
+        for (kind, maybe_price) in levels.iter() {
+            if let Some(price) = maybe_price {
+                let leg = BracketLeg::new(
+                    parent.id,
+                    claim.strategy.clone(),
+                    claim.sub_strategy.clone(),
+                    parent.ticker.clone(),
+                    *kind,
+                    *price,
+                )
+                .with_claim(claim.id);
+                debug!(leg_id = %leg.id, ?kind, %price, %quantity, "Attaching bracket leg");
+                db::save_bracket_leg(self.db_client.as_ref(), &leg)
+                    .await
+                    .context("Failed to save bracket leg")?;
+                let trade = leg_trade(&leg, quantity)?.with_account(claim.account.clone());
+                let dependent_trade = DependentTrade::new(parent.id, trade, Default::default());
+                db::save_dependent_trade(self.db_client.as_ref(), &dependent_trade)
+                    .await
+                    .context("Failed to save bracket leg trade")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Called when a trade has (partially) filled. If it is a bracket leg, the other legs of the
+    /// bracket are cancelled, and resized to the unfilled quantity if the fill was partial.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn settle_bracket_leg(&self, id: Uuid) -> Result<()> {
+        let leg = match db::get_bracket_leg(self.db_client.as_ref(), id).await? {
+            Some(leg) => leg,
+            None => return Ok(()),
+        };
+        let trade = match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(trade) => trade,
+            None => return Ok(()),
+        };
+        let resize_to = if trade.is_active() && !trade.pending_quantity.is_zero() {
+            Some(trade.pending_quantity)
+        } else {
+            None
+        };
+        let siblings = db::get_bracket_legs_by_parent(self.db_client.as_ref(), leg.parent_id)
+            .await?
+            .into_iter()
+            .filter(|sibling| sibling.id != leg.id);
+        for sibling in siblings {
+            let sibling_trade = match db::get_trade_by_id(self.db_client.as_ref(), sibling.id).await? {
+                Some(sibling_trade) if sibling_trade.is_active() => sibling_trade,
+                _ => continue,
+            };
+            debug!(sibling_id = %sibling.id, ?resize_to, "Cancelling bracket sibling");
+            db::set_bracket_leg_resize(self.db_client.as_ref(), sibling.id, resize_to).await?;
+            if sibling_trade.cancel_requested {
+                continue;
+            }
+            db::request_trade_cancel(self.db_client.as_ref(), sibling.id).await?;
+            if let Some(broker_id) = sibling_trade.broker_id {
+                self.cancel_trade(broker_id, &sibling_trade.account).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Called when a trade has been cancelled. Resends a bracket leg that was cancelled to be
+    /// resized after a partial fill of its sibling.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn resize_bracket_leg(&self, id: Uuid) -> Result<()> {
+        let leg = match db::get_bracket_leg(self.db_client.as_ref(), id).await? {
+            Some(leg) => leg,
+            None => return Ok(()),
+        };
+        if let Some(quantity) = leg.resize_to.filter(|quantity| !quantity.is_zero()) {
+            let replacement = leg.replacement();
+            debug!(replacement_id = %replacement.id, %quantity, "Resending resized bracket leg");
+            db::save_bracket_leg(self.db_client.as_ref(), &replacement)
+                .await
+                .context("Failed to save bracket leg")?;
+            // The replacement is sent to the account of the leg it replaces
+            let account = db::get_trade_by_id(self.db_client.as_ref(), leg.id)
+                .await?
+                .map(|trade| trade.account)
+                .unwrap_or_else(default_account);
+            self.send_trade(leg_trade(&replacement, quantity)?.with_account(account))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Bracket legs stay in place until one of them fills, rather than expiring at the end of the day.
+fn leg_trade(leg: &BracketLeg, quantity: Decimal) -> Result<TradeOrder> {
+    let instructions = OrderInstructions {
+        time_in_force: Some(TimeInForce::Gtc),
+        ..Default::default()
+    };
+    let mut order = leg.trade(quantity)?;
+    order.intent = instructions.apply_time_in_force(order.intent)?;
+    Ok(order)
+}
@@ -0,0 +1,132 @@
+use super::intents::get_last_price;
+use super::OrderManager;
+use crate::db;
+use crate::event_sender::Event;
+use crate::types::{BudgetDenial, BudgetViolation, Exposure, IntentState, IntentStatus, Owner};
+use anyhow::{Context, Result};
+use rust_decimal::prelude::*;
+use tracing::{debug, warn};
+use trading_base::{Amount, PositionIntent};
+
+impl OrderManager {
+    /// Checks the claim that `intent` would create in `account` against the budgets of the
+    /// strategy and sub-strategy. Returns `false` and records a denial if any budget would be
+    /// breached.
+    #[tracing::instrument(skip(self, intent, strategy_shares, amount, maybe_price), fields(id = %intent.id))]
+    pub(super) async fn check_budgets(
+        &self,
+        intent: &PositionIntent,
+        account: &str,
+        ticker: &str,
+        strategy_shares: Decimal,
+        amount: &Amount,
+        maybe_price: Option<Decimal>,
+    ) -> Result<bool> {
+        let budgets: Vec<_> = db::get_budgets_by_strategy(self.db_client.as_ref(), &intent.strategy)
+            .await
+            .context("Failed to get budgets")?
+            .into_iter()
+            .filter(|budget| budget.sub_strategy.is_none() || budget.sub_strategy == intent.sub_strategy)
+            .collect();
+        if budgets.is_empty() {
+            return Ok(true);
+        }
+        for budget in budgets {
+            let violation = match maybe_price {
+                Some(price) => {
+                    let owner = Owner::Strategy(budget.strategy.clone(), budget.sub_strategy.clone());
+                    let positions = db::get_positions_by_owner(self.db_client.as_ref(), &owner)
+                        .await
+                        .context("Failed to get positions")?;
+                    let current = Exposure::from_positions(&positions, ticker, price);
+                    let diff_notional = match amount {
+                        Amount::Dollars(dollars) => *dollars,
+                        Amount::Shares(shares) => shares * price,
+                        Amount::Zero => -strategy_shares * price,
+                    };
+                    let projected = current.with_ticker_notional(current.ticker + diff_notional);
+                    match budget.check(&current, &projected) {
+                        None if budget.enforce_buying_power => {
+                            let cash = db::get_cash_balance_by_owner(self.db_client.as_ref(), &owner, account)
+                                .await
+                                .context("Failed to get cash balance")?;
+                            let outstanding = self
+                                .get_outstanding_buy_notional(intent, &owner, account, ticker, price)
+                                .await?;
+                            match outstanding {
+                                Some(outstanding) => budget.check_buying_power(cash, outstanding, diff_notional),
+                                None => Some(BudgetViolation::MissingPrice),
+                            }
+                        }
+                        violation => violation,
+                    }
+                }
+                None => Some(BudgetViolation::MissingPrice),
+            };
+            if let Some(violation) = violation {
+                warn!(%violation, "Budget check failed");
+                let denial = BudgetDenial::new(
+                    intent.id,
+                    intent.strategy.clone(),
+                    intent.sub_strategy.clone(),
+                    ticker.to_string(),
+                    violation.to_string(),
+                );
+                db::save_budget_denial(self.db_client.as_ref(), &denial)
+                    .await
+                    .context("Failed to save budget denial")?;
+                self.event_sender.send(Event::BudgetDenial(denial)).await?;
+                let status = IntentStatus::for_intent(intent, IntentState::RiskDenied, Some(violation.to_string()));
+                self.event_sender.send(Event::IntentStatus(status)).await?;
+                return Ok(false);
+            }
+        }
+        debug!("Budget check passed");
+        Ok(true)
+    }
+
+    /// The cash still needed for the purchases of the live claims of `owner` in `account`. The
+    /// remaining amount of a claim includes the unfilled part of the trades working for it. The
+    /// claim of the intent's own strategy in `ticker` is left out, since the intent replaces it.
+    /// Returns `None` if a claim can't be priced.
+    async fn get_outstanding_buy_notional(
+        &self,
+        intent: &PositionIntent,
+        owner: &Owner,
+        account: &str,
+        ticker: &str,
+        price: Decimal,
+    ) -> Result<Option<Decimal>> {
+        let claims = db::get_buy_claims_by_owner(self.db_client.as_ref(), owner, account)
+            .await
+            .context("Failed to get buy claims")?;
+        let mut outstanding = Decimal::ZERO;
+        for claim in claims {
+            if claim.ticker == ticker && claim.strategy == intent.strategy && claim.sub_strategy == intent.sub_strategy
+            {
+                continue;
+            }
+            let notional = match claim.amount {
+                Amount::Dollars(dollars) => dollars,
+                Amount::Shares(shares) => {
+                    // Limit orders are never filled above their limit
+                    let maybe_price = match claim.limit_price {
+                        Some(limit_price) => Some(limit_price),
+                        None if claim.ticker == ticker => Some(price),
+                        None => get_last_price(&self.datastore_url, &claim.ticker).await.ok(),
+                    };
+                    match maybe_price {
+                        Some(price) => shares * price,
+                        None => {
+                            warn!(claim_id = %claim.id, "Failed to price outstanding claim");
+                            return Ok(None);
+                        }
+                    }
+                }
+                Amount::Zero => Decimal::ZERO,
+            };
+            outstanding += notional;
+        }
+        Ok(Some(outstanding))
+    }
+}
@@ -0,0 +1,106 @@
+use super::OrderManager;
+use crate::db;
+use crate::types::Trade;
+use anyhow::{Context, Result};
+use rust_decimal::prelude::*;
+use tracing::debug;
+use uuid::Uuid;
+
+impl OrderManager {
+    /// The working trades that were sent for a claim. Trades in a ticker are netted across
+    /// strategies, so only these can be cancelled when the claim is replaced.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn get_active_claim_trades(&self, claim_id: Uuid) -> Result<Vec<Trade>> {
+        let trades = db::get_claim_trades(self.db_client.as_ref(), claim_id)
+            .await
+            .context("Failed to get claim trades")?;
+        Ok(trades.into_iter().filter(Trade::is_active).collect())
+    }
+
+    /// Cancels trades that are working towards a superseded claim. Their replacements are sent
+    /// by `complete_replacement` once the broker has confirmed the cancellations.
+    #[tracing::instrument(skip(self, trades))]
+    pub(super) async fn cancel_for_replacement(&self, trades: Vec<Trade>) -> Result<()> {
+        for trade in trades {
+            db::request_trade_cancel(self.db_client.as_ref(), trade.id)
+                .await
+                .context("Failed to mark trade for cancellation")?;
+            // Any dependent trades were generated for the superseded claim
+            db::cancel_dependent_trades(self.db_client.as_ref(), trade.id)
+                .await
+                .context("Failed to delete dependent trades")?;
+            match trade.broker_id {
+                Some(broker_id) => self.cancel_trade(broker_id, &trade.account).await?,
+                None => debug!(id = %trade.id, "Deferring cancel until trade has been reported"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancels a working trade as requested through the API, e.g. after a corporate action
+    /// changed the shares it was sized for. It is replaced from the claims in its ticker like any
+    /// other cancelled trade.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn cancel_trade_manually(&self, id: Uuid) -> Result<()> {
+        match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(trade) if trade.is_active() => self.cancel_for_replacement(vec![trade]).await,
+            _ => {
+                debug!("Trade no longer active");
+                Ok(())
+            }
+        }
+    }
+
+    /// Called on every order update. Sends deferred cancels for trades that have just been
+    /// reported, and sends the residual trades once all cancelled trades in the ticker are done in
+    /// the account of the trade.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn complete_replacement(&self, id: Uuid, ticker: &str) -> Result<()> {
+        let trade = match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(trade) if trade.cancel_requested => trade,
+            _ => return Ok(()),
+        };
+        if trade.is_active() {
+            if let Some(broker_id) = trade.broker_id {
+                debug!("Sending deferred cancel");
+                self.cancel_trade(broker_id, &trade.account).await?;
+            }
+            return Ok(());
+        }
+        let account = &trade.account;
+        let active_amount = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), account, ticker).await?;
+        let outstanding_requests =
+            db::get_risk_check_requests_by_ticker(self.db_client.as_ref(), account, ticker).await?;
+        if !active_amount.is_zero() || !outstanding_requests.is_empty() {
+            debug!("Waiting for remaining trades before replacing");
+            return Ok(());
+        }
+        let claims = db::get_claims_by_ticker(self.db_client.as_ref(), ticker)
+            .await
+            .context("Failed to get claims")?;
+        let claims = claims
+            .into_iter()
+            .filter(|claim| &claim.account == account && !claim.amount.is_zero() && !claim.is_backing_off());
+        for claim in claims {
+            let instructions = self.get_order_instructions(claim.intent_id).await?;
+            let maybe_trade = self
+                .generate_trades(
+                    account,
+                    ticker,
+                    &claim.amount,
+                    claim.limit_price,
+                    None,
+                    &instructions,
+                    Some(&claim),
+                )
+                .await?;
+            if let Some(order) = maybe_trade {
+                debug!(claim_id = %claim.id, "Sending replacement trade");
+                // Only one trade is kept in flight per ticker, remaining claims are picked up
+                // by reconciliation once it is done.
+                return self.request_risk_check(order, Some(claim.id)).await;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,142 @@
+use super::intents::get_last_price;
 use super::OrderManager;
 use crate::db;
+use crate::types::{DependentTrade, DependentTrigger, OrderKind, TradeOrder};
 use anyhow::{Context, Result};
+use rust_decimal::prelude::*;
 use tracing::debug;
 use uuid::Uuid;
 
 impl OrderManager {
+    /// Called when a trade has completely filled. Sends the trades that depend on it filling and
+    /// cancels the ones that only depended on it being cancelled.
     #[tracing::instrument(skip(self, id))]
     pub async fn trigger_dependent_trades(&self, id: Uuid) -> Result<()> {
-        let trades = db::take_dependent_trades(self.db_client.as_ref(), id)
+        let dependent_trades = db::take_dependent_trades(self.db_client.as_ref(), id)
             .await
             .context("Failed to take and delete dependent trader")?;
-        if !trades.is_empty() {
+        if !dependent_trades.is_empty() {
             debug!(%id, "Triggering dependent trades");
-            for trade in trades {
-                self.send_trade(trade).await?
+            for dependent_trade in dependent_trades {
+                match dependent_trade.trigger {
+                    DependentTrigger::OnFill | DependentTrigger::OnPartialFill => {
+                        let trade = dependent_trade.trade.clone();
+                        self.release_dependent_trade(&dependent_trade, trade).await?
+                    }
+                    DependentTrigger::OnCancel => self.drop_dependent_trade(&dependent_trade).await?,
+                }
             }
         }
         Ok(())
     }
+
+    /// Called when a trade has partially filled. Sends the trades whose trigger quantity has been
+    /// reached.
+    #[tracing::instrument(skip(self, id))]
+    pub(super) async fn trigger_partial_fill_dependent_trades(&self, id: Uuid) -> Result<()> {
+        let filled = match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(trade) => trade.filled_quantity.abs(),
+            None => return Ok(()),
+        };
+        let dependent_trades = db::take_partial_fill_dependent_trades(self.db_client.as_ref(), id, filled)
+            .await
+            .context("Failed to take partial fill dependent trades")?;
+        for dependent_trade in dependent_trades {
+            debug!(dependent_id = %dependent_trade.trade.intent.id, %filled, "Triggering partial fill dependent trade");
+            let trade = dependent_trade.trade.clone();
+            self.release_dependent_trade(&dependent_trade, trade).await?
+        }
+        Ok(())
+    }
+
+    /// Called when a trade has been cancelled, has expired or has been rejected. Sends the trades
+    /// that depend on it being cancelled and resizes the ones that depended on it filling, see
+    /// `DependentTrade::on_parent_ended`. Those left without shares to trade are cancelled.
+    #[tracing::instrument(skip(self, id))]
+    pub(super) async fn settle_dependent_trades(&self, id: Uuid) -> Result<()> {
+        let dependent_trades = db::take_dependent_trades(self.db_client.as_ref(), id)
+            .await
+            .context("Failed to take dependent trades")?;
+        if dependent_trades.is_empty() {
+            return Ok(());
+        }
+        let (filled, quantity, fractional) = match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(trade) => (
+                trade.quantity - trade.pending_quantity,
+                trade.quantity,
+                self.settings.is_fractional(&trade.ticker),
+            ),
+            None => (Decimal::ZERO, Decimal::ZERO, false),
+        };
+        for dependent_trade in dependent_trades {
+            let is_bracket_leg = db::get_bracket_leg(self.db_client.as_ref(), dependent_trade.trade.intent.id)
+                .await
+                .context("Failed to get bracket leg")?
+                .is_some();
+            let maybe_trade = dependent_trade.on_parent_ended(filled, quantity, is_bracket_leg, fractional);
+            match maybe_trade {
+                Some(trade) => {
+                    debug!(dependent_id = %trade.intent.id, qty = %trade.quantity(), "Sending dependent trade of ended parent");
+                    self.release_dependent_trade(&dependent_trade, trade).await?
+                }
+                None => self.drop_dependent_trade(&dependent_trade).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancels dependent trades whose expiry has passed, together with their own dependent trades.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn expire_dependent_trades(&self) -> Result<()> {
+        let dependent_trades = db::take_expired_dependent_trades(self.db_client.as_ref())
+            .await
+            .context("Failed to take expired dependent trades")?;
+        for dependent_trade in dependent_trades {
+            self.drop_dependent_trade(&dependent_trade).await?
+        }
+        Ok(())
+    }
+
+    /// Sends a dependent trade without waiting for its trigger, as requested through the API. The
+    /// trades that depend on it stay in place and trigger on its own updates.
+    #[tracing::instrument(skip(self, id))]
+    pub(super) async fn release_dependent_trade_manually(&self, id: Uuid) -> Result<()> {
+        match db::take_dependent_trade(self.db_client.as_ref(), id)
+            .await
+            .context("Failed to take dependent trade")?
+        {
+            Some(dependent_trade) => {
+                debug!(%id, parent_id = %dependent_trade.parent_id, "Manually releasing dependent trade");
+                let trade = dependent_trade.trade.clone();
+                self.release_dependent_trade(&dependent_trade, trade).await
+            }
+            None => {
+                debug!(%id, "Dependent trade no longer pending");
+                Ok(())
+            }
+        }
+    }
+
+    async fn release_dependent_trade(&self, dependent_trade: &DependentTrade, trade: TradeOrder) -> Result<()> {
+        if dependent_trade.is_expired() {
+            return self.drop_dependent_trade(dependent_trade).await;
+        }
+        let instructions = &dependent_trade.instructions;
+        let last_price = match instructions.order_kind {
+            OrderKind::TrailingStop => get_last_price(&self.datastore_url, &trade.intent.ticker).await.ok(),
+            _ => None,
+        };
+        let order = instructions
+            .apply_order_kind(trade, last_price)
+            .context("Failed to apply order instructions")?;
+        self.send_trade(order).await
+    }
+
+    async fn drop_dependent_trade(&self, dependent_trade: &DependentTrade) -> Result<()> {
+        debug!(dependent_id = %dependent_trade.trade.intent.id, "Cancelling dependent trade");
+        db::cancel_dependent_trades(self.db_client.as_ref(), dependent_trade.trade.intent.id)
+            .await
+            .context("Failed to cancel dependent trades")?;
+        Ok(())
+    }
 }
@@ -0,0 +1,187 @@
+use super::intents::get_last_price;
+use super::OrderManager;
+use crate::db;
+use crate::intent_scheduler::ScheduleCommand;
+use crate::types::{Execution, ExecutionAlgorithm, TradeOrder};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::*;
+use tracing::{debug, warn};
+use trading_base::OrderType;
+use uuid::Uuid;
+
+impl OrderManager {
+    /// Sends a trade generated for a claim, working it as an `Execution` if the claim's strategy
+    /// has an execution policy and the trade is large enough. Notional, trailing stop and extended
+    /// hours orders are sent whole, since their slices would be sent without them.
+    #[tracing::instrument(skip(self, order), fields(id = %order.intent.id))]
+    pub(super) async fn execute_trade(&self, order: TradeOrder, claim_id: Uuid) -> Result<()> {
+        if !order.extensions.can_be_sliced() {
+            return self.request_risk_check(order, Some(claim_id)).await;
+        }
+        let maybe_claim = db::find_claim_by_id(self.db_client.as_ref(), claim_id)
+            .await
+            .context("Failed to get claim")?;
+        let maybe_policy = match maybe_claim {
+            Some(claim) => {
+                db::get_execution_policy(self.db_client.as_ref(), &claim.strategy, claim.sub_strategy.as_deref())
+                    .await
+                    .context("Failed to get execution policy")?
+            }
+            None => None,
+        };
+        let policy = match maybe_policy {
+            Some(policy) => policy,
+            None => return self.request_risk_check(order, Some(claim_id)).await,
+        };
+        let trade = &order.intent;
+        let price = match trade.order_type {
+            OrderType::Limit { limit_price } | OrderType::StopLimit { limit_price, .. } => Some(limit_price),
+            _ => get_last_price(&self.datastore_url, &trade.ticker).await.ok(),
+        };
+        let notional = price.map(|price| price * order.quantity().abs());
+        match notional {
+            Some(notional) if notional >= policy.min_notional => {
+                let execution = Execution::new(&order, Some(claim_id), &policy);
+                debug!(algorithm = ?execution.algorithm, %notional, "Starting execution");
+                db::save_execution(self.db_client.as_ref(), &execution)
+                    .await
+                    .context("Failed to save execution")?;
+                self.schedule_execution_slice(execution.id, Utc::now())
+            }
+            _ => self.request_risk_check(order, Some(claim_id)).await,
+        }
+    }
+
+    pub(super) fn schedule_execution_slice(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        self.scheduler_sender
+            .send(ScheduleCommand::ExecutionSlice { id, at })
+            .context("Failed to send execution slice to scheduler")
+    }
+
+    /// Sends the next child trade of an execution.
+    #[tracing::instrument(skip(self))]
+    pub async fn release_execution_slice(&self, id: Uuid) -> Result<()> {
+        let mut execution = match db::get_execution_by_id(self.db_client.as_ref(), id).await? {
+            Some(execution) => execution,
+            None => {
+                debug!("Execution no longer exists");
+                return Ok(());
+            }
+        };
+        if execution.is_complete() || execution.is_abandoned() {
+            return Ok(());
+        }
+        if !self.is_market_open() {
+            debug!("Market closed, holding slice until the open");
+            let next_open = self.calendar.next_open(Utc::now());
+            return self.reschedule_execution(execution, next_open).await;
+        }
+        let interval = Duration::seconds(execution.interval_seconds as i64);
+        if let Some(working_id) = execution.working_id {
+            let working_trade = db::get_trade_by_id(self.db_client.as_ref(), working_id).await?;
+            let outstanding_request =
+                db::get_risk_check_requests_by_ticker(self.db_client.as_ref(), &execution.account, &execution.ticker)
+                    .await?
+                    .iter()
+                    .any(|request| request.id == working_id);
+            if outstanding_request || working_trade.map(|trade| trade.is_active()).unwrap_or(false) {
+                debug!(%working_id, "Previous slice still working");
+                if execution.algorithm != ExecutionAlgorithm::Iceberg {
+                    return self.reschedule_execution(execution, Utc::now() + interval).await;
+                }
+                return Ok(());
+            }
+        }
+        let volume = if execution.algorithm == ExecutionAlgorithm::Pov {
+            match get_cumulative_volume(&self.datastore_url, &execution.ticker).await {
+                Ok(volume) => Some(volume),
+                Err(e) => {
+                    warn!(%e, "Failed to get volume");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let quantity = execution.next_slice_quantity(volume);
+        if volume.is_some() {
+            execution.last_volume = volume;
+        }
+        if quantity.is_zero() {
+            debug!("Not enough volume for a slice");
+            return self.reschedule_execution(execution, Utc::now() + interval).await;
+        }
+        let child = execution.child_trade(quantity)?;
+        debug!(child_id = %child.intent.id, %quantity, "Releasing slice");
+        execution.working_id = Some(child.intent.id);
+        execution.remaining_quantity -= quantity;
+        execution.slices_remaining = (execution.slices_remaining - 1).max(1);
+        let next_release_at = match execution.algorithm {
+            _ if execution.is_complete() => None,
+            ExecutionAlgorithm::Iceberg => None,
+            ExecutionAlgorithm::Twap | ExecutionAlgorithm::Pov => Some(Utc::now() + interval),
+        };
+        match next_release_at {
+            Some(at) => self.reschedule_execution(execution.clone(), at).await?,
+            None => {
+                execution.next_release_at = None;
+                db::save_execution(self.db_client.as_ref(), &execution)
+                    .await
+                    .context("Failed to save execution")?
+            }
+        }
+        self.request_risk_check(child, execution.claim_id).await
+    }
+
+    async fn reschedule_execution(&self, mut execution: Execution, at: DateTime<Utc>) -> Result<()> {
+        execution.next_release_at = Some(at);
+        db::save_execution(self.db_client.as_ref(), &execution)
+            .await
+            .context("Failed to save execution")?;
+        self.schedule_execution_slice(execution.id, at)
+    }
+
+    /// Called when a trade has filled. Completes the execution it belongs to, or releases the
+    /// next slice of an iceberg.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn advance_execution(&self, id: Uuid) -> Result<()> {
+        let execution = match db::get_execution_by_working_id(self.db_client.as_ref(), id).await? {
+            Some(execution) => execution,
+            None => return Ok(()),
+        };
+        if execution.is_complete() {
+            debug!(execution_id = %execution.id, "Execution complete");
+            db::delete_execution(self.db_client.as_ref(), execution.id)
+                .await
+                .context("Failed to delete execution")?;
+            self.trigger_dependent_trades(execution.id).await
+        } else if execution.algorithm == ExecutionAlgorithm::Iceberg {
+            self.release_execution_slice(execution.id).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Abandons the execution of a child trade that ended without filling. The remainder of the
+    /// claim is picked up by reconciliation.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn abandon_execution(&self, id: Uuid) -> Result<()> {
+        if let Some(execution) = db::get_execution_by_working_id(self.db_client.as_ref(), id).await? {
+            warn!(execution_id = %execution.id, "Abandoning execution");
+            db::abandon_execution(self.db_client.as_ref(), execution.id)
+                .await
+                .context("Failed to abandon execution")?;
+            db::cancel_dependent_trades(self.db_client.as_ref(), execution.id)
+                .await
+                .context("Failed to delete dependent trades")?;
+        }
+        Ok(())
+    }
+}
+
+async fn get_cumulative_volume(base_url: &str, ticker: &str) -> Result<Decimal> {
+    let url = format!("{}/volume/{}", base_url, ticker);
+    let volume: Decimal = reqwest::get(url).await?.json().await?;
+    Ok(volume)
+}
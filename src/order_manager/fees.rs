@@ -0,0 +1,54 @@
+use crate::settings::FeeSettings;
+use rust_decimal::prelude::*;
+
+/// Fees charged on a fill of `shares` (negative for sales) at `price`. Regulatory fees are only
+/// charged on sales.
+pub fn calculate_fees(settings: &FeeSettings, price: Decimal, shares: Decimal) -> Decimal {
+    let quantity = shares.abs();
+    let commission = if settings.commission_per_share.is_zero() {
+        Decimal::ZERO
+    } else {
+        (settings.commission_per_share * quantity).max(settings.minimum_commission)
+    };
+    let regulatory_fees = if shares.is_sign_negative() {
+        let sec_fee = settings.sec_fee_rate * price * quantity;
+        let taf = settings.finra_taf_per_share * quantity;
+        let taf = match settings.finra_taf_maximum {
+            Some(maximum) => taf.min(maximum),
+            None => taf,
+        };
+        // Regulatory fees are rounded up to the nearest cent
+        (sec_fee + taf).round_dp_with_strategy(2, RoundingStrategy::AwayFromZero)
+    } else {
+        Decimal::ZERO
+    };
+    commission + regulatory_fees
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_calculate_fees() {
+        let settings = FeeSettings {
+            commission_per_share: Decimal::new(5, 3),
+            minimum_commission: Decimal::ONE,
+            sec_fee_rate: Decimal::new(229, 7),
+            finra_taf_per_share: Decimal::new(119, 6),
+            finra_taf_maximum: Some(Decimal::new(595, 2)),
+        };
+        let price = Decimal::new(100, 0);
+        // Buys only pay the minimum commission
+        assert_eq!(calculate_fees(&settings, price, Decimal::new(10, 0)), Decimal::ONE);
+        // 1000 * 0.005 commission, 100_000 * 0.0000229 SEC fee and 1000 * 0.000119 TAF
+        assert_eq!(
+            calculate_fees(&settings, price, Decimal::new(-1000, 0)),
+            Decimal::new(741, 2)
+        );
+        assert_eq!(
+            calculate_fees(&FeeSettings::default(), price, Decimal::new(-10, 0)),
+            Decimal::ZERO
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use super::intents::get_last_price;
+use crate::db;
+use crate::types::{
+    needs_house_action, reallocation_owner, HouseAction, HouseActionState, HousePolicy, Owner, Position,
+};
+use crate::OrderManager;
+use anyhow::{Context, Result};
+use rust_decimal::prelude::*;
+use tracing::{debug, warn};
+use trading_base::Amount;
+
+/// The result of applying a house policy to a position.
+#[derive(Debug, PartialEq)]
+enum PolicyOutcome {
+    Applied,
+    /// The policy can't be applied yet, such as while trades in the ticker are working
+    Deferred,
+    /// Part of the position could not be handled by the policy
+    Incomplete,
+}
+
+impl OrderManager {
+    /// Applies the house policy to the positions held by the house. In approval mode, an action
+    /// is recorded for each position instead, and applied once an operator approves it.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn reconcile_house_positions(&self) -> Result<()> {
+        self.apply_approved_house_actions().await?;
+        let settings = &self.settings.house;
+        if settings.policy == HousePolicy::Hold {
+            return Ok(());
+        }
+        let house_positions = db::get_positions_by_owner(self.db_client.as_ref(), &Owner::House).await?;
+        let house_positions = house_positions.iter().filter(|pos| pos.shares != Decimal::ZERO);
+        for position in house_positions {
+            let last_price = match settings.min_notional {
+                Some(_) => get_last_price(&self.datastore_url, &position.ticker).await.ok(),
+                None => None,
+            };
+            if !settings.is_actionable(position.shares, last_price) {
+                continue;
+            }
+            if settings.require_approval {
+                let last_action =
+                    db::get_last_house_action(self.db_client.as_ref(), &position.account, &position.ticker).await?;
+                if needs_house_action(last_action.as_ref(), position.shares) {
+                    debug!(ticker = %position.ticker, shares = %position.shares, "Holding house action for approval");
+                    let action = HouseAction::new(position, settings.policy);
+                    db::save_house_action(self.db_client.as_ref(), &action)
+                        .await
+                        .context("Failed to save house action")?;
+                }
+                continue;
+            }
+            if self.apply_house_policy(settings.policy, position).await? == PolicyOutcome::Incomplete {
+                warn!(ticker = %position.ticker, "House policy could only partly be applied");
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the actions approved by an operator to the house positions they were approved
+    /// for. Actions that cannot be applied yet stay approved until a later reconciliation, while
+    /// actions for positions that have since changed are superseded, so that the changed position
+    /// is proposed to the operator again.
+    #[tracing::instrument(skip(self))]
+    async fn apply_approved_house_actions(&self) -> Result<()> {
+        let actions = db::get_house_actions_by_state(self.db_client.as_ref(), HouseActionState::Approved).await?;
+        for action in actions {
+            let maybe_position = db::get_position_by_owner_and_ticker(
+                self.db_client.as_ref(),
+                &Owner::House,
+                &action.account,
+                &action.ticker,
+            )
+            .await?;
+            let next_state = match maybe_position.filter(|pos| pos.shares != Decimal::ZERO) {
+                Some(position) if action.applies_to(position.shares) => {
+                    match self.apply_house_policy(action.policy, &position).await? {
+                        PolicyOutcome::Applied => HouseActionState::Executed,
+                        PolicyOutcome::Deferred => continue,
+                        PolicyOutcome::Incomplete => {
+                            warn!(id = %action.id, "House action could only partly be applied");
+                            HouseActionState::Failed
+                        }
+                    }
+                }
+                Some(position) => {
+                    debug!(id = %action.id, shares = %position.shares, "House position changed since approval");
+                    HouseActionState::Superseded
+                }
+                None => {
+                    debug!(id = %action.id, "House position no longer open");
+                    HouseActionState::Superseded
+                }
+            };
+            db::transition_house_action(
+                self.db_client.as_ref(),
+                action.id,
+                HouseActionState::Approved,
+                next_state,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Applies a policy to a house position.
+    #[tracing::instrument(skip(self, position), fields(ticker = %position.ticker, shares = %position.shares))]
+    async fn apply_house_policy(&self, policy: HousePolicy, position: &Position) -> Result<PolicyOutcome> {
+        match policy {
+            HousePolicy::Liquidate => self.liquidate_house_position(position).await,
+            HousePolicy::Hold => Ok(PolicyOutcome::Applied),
+            HousePolicy::ErrorAccount => {
+                debug!(error_account = %self.settings.house.error_account, "Reassigning house position");
+                let error_account = Owner::ErrorAccount(self.settings.house.error_account.clone());
+                db::reassign_allocations(
+                    self.db_client.as_ref(),
+                    &Owner::House,
+                    &error_account,
+                    &position.account,
+                    &position.ticker,
+                )
+                .await
+                .context("Failed to reassign house allocations")?;
+                Ok(PolicyOutcome::Applied)
+            }
+            HousePolicy::Reallocate => {
+                let allocations = db::get_allocations_by_owner_and_ticker(
+                    self.db_client.as_ref(),
+                    &Owner::House,
+                    &position.account,
+                    &position.ticker,
+                )
+                .await?;
+                let mut outcome = PolicyOutcome::Applied;
+                for allocation in allocations {
+                    let lot_allocations =
+                        db::get_allocations_by_lot_id(self.db_client.as_ref(), allocation.lot_id).await?;
+                    match reallocation_owner(&lot_allocations) {
+                        Some(owner) => {
+                            debug!(id = %allocation.id, %owner, "Reallocating house allocation");
+                            db::set_allocation_owner(self.db_client.as_ref(), allocation.id, &owner)
+                                .await
+                                .context("Failed to reallocate house allocation")?;
+                        }
+                        None => {
+                            warn!(id = %allocation.id, "No claim to reallocate house allocation to");
+                            outcome = PolicyOutcome::Incomplete;
+                        }
+                    }
+                }
+                Ok(outcome)
+            }
+        }
+    }
+
+    async fn liquidate_house_position(&self, position: &Position) -> Result<PolicyOutcome> {
+        let active_trade_amount =
+            db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), &position.account, &position.ticker).await?;
+        if !active_trade_amount.is_zero() {
+            return Ok(PolicyOutcome::Deferred);
+        }
+        // Since shares are stored with 8 decimal points of precision, adding 1e-9
+        // guarantees that we will be left with < 1 share in the house position.
+        let mut shares_to_liquidate = (position.shares.abs() - Decimal::ONE + Decimal::new(1, 9))
+            .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
+        if shares_to_liquidate <= Decimal::ZERO {
+            debug!("House position is less than a share");
+            return Ok(PolicyOutcome::Applied);
+        }
+        debug!("Reducing size of house position");
+        shares_to_liquidate.set_sign_positive(position.shares.is_sign_positive());
+        let maybe_trade = self
+            .generate_trades(
+                &position.account,
+                &position.ticker,
+                &Amount::Shares(-shares_to_liquidate),
+                None,
+                None,
+                &Default::default(),
+                None,
+            )
+            .await?;
+        if let Some(order) = maybe_trade {
+            self.request_risk_check(order, None).await?
+        }
+        Ok(PolicyOutcome::Applied)
+    }
+}
@@ -1,12 +1,17 @@
 use super::OrderManager;
 use crate::db;
+use crate::event_sender::Event;
+use crate::intent_scheduler::Scheduled;
+use crate::types::{IntentState, IntentStatus, OrderInstructions};
 use alpaca::AlpacaMessage;
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use rdkafka::Message;
 use risk_manager::RiskCheckResponse;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 use trading_base::PositionIntent;
+use uuid::Uuid;
 
 #[derive(Deserialize, Serialize)]
 #[serde(tag = "state", rename_all = "lowercase")]
@@ -15,6 +20,16 @@ pub enum State {
     Closed { next_open: usize },
 }
 
+/// Requests that the webserver hands to the order manager, which owns the connections needed to
+/// act on them
+#[derive(Debug)]
+pub enum Command {
+    /// Sends a dependent trade without waiting for its trigger
+    ReleaseDependentTrade { id: Uuid },
+    /// Cancels a working trade so that its claims are traded again
+    CancelTrade { id: Uuid },
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 #[allow(clippy::large_enum_variant)]
@@ -23,6 +38,29 @@ pub enum Input {
     AlpacaMessage(AlpacaMessage),
     RiskCheckResponse(RiskCheckResponse),
     Time(State),
+    #[serde(skip)]
+    ScheduledIntent(PositionIntent),
+    #[serde(skip)]
+    DroppedIntent(PositionIntent),
+    /// An intent sent with order instructions that can't be applied
+    #[serde(skip)]
+    InvalidIntent {
+        intent: PositionIntent,
+        reason: String,
+    },
+    #[serde(skip)]
+    ExecutionSlice(Uuid),
+    #[serde(skip)]
+    Reconcile,
+    #[serde(skip)]
+    ReleaseDependentTrade(Uuid),
+    #[serde(skip)]
+    CancelTrade(Uuid),
+    #[serde(skip)]
+    RecurringIntent {
+        id: Uuid,
+        at: DateTime<Utc>,
+    },
 }
 
 impl OrderManager {
@@ -33,23 +71,66 @@ impl OrderManager {
                 debug!("Message received from kafka");
                 let message = kafka_message?;
                 let payload = message.payload().ok_or_else(|| anyhow!("Empty payload"))?;
-                Ok(serde_json::from_slice(payload)?)
+                let input = serde_json::from_slice(payload)?;
+                if let Input::PositionIntent(intent) = input {
+                    // Order instructions are sent alongside the fields of the intent
+                    let instructions: OrderInstructions = match serde_json::from_slice(payload) {
+                        Ok(instructions) => instructions,
+                        Err(e) => return Ok(Input::InvalidIntent { intent, reason: e.to_string() }),
+                    };
+                    if let Err(e) = instructions.validate() {
+                        return Ok(Input::InvalidIntent { intent, reason: e.to_string() });
+                    }
+                    if !instructions.is_default() {
+                        db::save_order_instructions(self.db_client.as_ref(), intent.id, &instructions).await?;
+                    }
+                    return Ok(Input::PositionIntent(intent));
+                }
+                Ok(input)
             },
             scheduled_intent = self.scheduler_receiver.recv() => {
                 debug!("Message received from scheduler");
-                let intent = scheduled_intent.ok_or_else(|| anyhow!("Channel closed"))?;
-                db::delete_scheduled_intent(self.db_client.as_ref(), intent.id).await?;
-                Ok(Input::PositionIntent(intent))
+                match scheduled_intent.ok_or_else(|| anyhow!("Channel closed"))? {
+                    Scheduled::PositionIntent(intent) => Ok(Input::ScheduledIntent(intent)),
+                    Scheduled::Dropped(intent) => Ok(Input::DroppedIntent(intent)),
+                    Scheduled::ExecutionSlice { id } => Ok(Input::ExecutionSlice(id)),
+                    Scheduled::Reconcile => Ok(Input::Reconcile),
+                    Scheduled::RecurringIntent { id, at } => Ok(Input::RecurringIntent { id, at }),
+                }
+            }
+            command = self.command_receiver.recv() => {
+                debug!("Command received from webserver");
+                match command.ok_or_else(|| anyhow!("Channel closed"))? {
+                    Command::ReleaseDependentTrade { id } => Ok(Input::ReleaseDependentTrade(id)),
+                    Command::CancelTrade { id } => Ok(Input::CancelTrade(id)),
+                }
             }
         }
     }
 
-    pub async fn handle_input(&self, input: Result<Input>) -> Result<()> {
+    pub async fn handle_input(&mut self, input: Result<Input>) -> Result<()> {
         match input {
             Ok(Input::PositionIntent(intent)) => self
                 .triage_intent(intent)
                 .await
                 .context("Failed to triage PositionIntent")?,
+            Ok(Input::ScheduledIntent(intent)) => self
+                .trigger_scheduled_intent(intent)
+                .await
+                .context("Failed to trigger scheduled intent")?,
+            Ok(Input::DroppedIntent(intent)) => {
+                let status = IntentStatus::for_intent(
+                    &intent,
+                    IntentState::Cancelled,
+                    Some("Cancelled while scheduled".into()),
+                );
+                self.event_sender.send(Event::IntentStatus(status)).await?;
+            }
+            Ok(Input::InvalidIntent { intent, reason }) => {
+                warn!(id = %intent.id, %reason, "Invalid order instructions");
+                let status = IntentStatus::for_intent(&intent, IntentState::Ignored, Some(reason));
+                self.event_sender.send(Event::IntentStatus(status)).await?;
+            }
             Ok(Input::AlpacaMessage(AlpacaMessage::TradeUpdates(oe))) => self
                 .handle_order_update(oe)
                 .await
@@ -57,14 +138,34 @@ impl OrderManager {
             Ok(Input::AlpacaMessage(_)) => unreachable!(),
             Ok(Input::Time(State::Open { .. })) => {
                 debug!("Handling time update");
+                self.market_open = Some(true);
                 self.reconcile().await.context("Failed to reconcile")?;
             }
-            Ok(Input::Time(State::Closed { .. })) => {}
+            Ok(Input::Time(State::Closed { .. })) => {
+                self.market_open = Some(false);
+            }
+            Ok(Input::Reconcile) => self
+                .run_scheduled_reconciliation()
+                .await
+                .context("Failed to reconcile")?,
             Ok(Input::RiskCheckResponse(response)) => {
                 self.handle_risk_check_response(response)
                     .await
                     .context("Failed to handle RiskCheckResponse")?;
             }
+            Ok(Input::ExecutionSlice(id)) => self
+                .release_execution_slice(id)
+                .await
+                .context("Failed to release execution slice")?,
+            Ok(Input::ReleaseDependentTrade(id)) => self
+                .release_dependent_trade_manually(id)
+                .await
+                .context("Failed to release dependent trade")?,
+            Ok(Input::CancelTrade(id)) => self.cancel_trade_manually(id).await.context("Failed to cancel trade")?,
+            Ok(Input::RecurringIntent { id, at }) => self
+                .run_recurring_intent(id, at)
+                .await
+                .context("Failed to run recurring intent")?,
             Err(e) => return Err(e),
         };
         debug!("Finished handling input");
@@ -1,13 +1,18 @@
 use super::OrderManager;
 use crate::db;
 use crate::event_sender::Event;
-use crate::types::{calculate_claim_amount, Claim, Owner, Position, Trade};
+use crate::intent_scheduler::ScheduleCommand;
+use crate::types::{
+    calculate_claim_amount, Claim, ClaimState, DependentTrade, IntentState, IntentStatus, OrderInstructions, OrderKind,
+    Owner, Position, TimeInForce, TradeOrder,
+};
 use anyhow::{anyhow, Context, Result};
 use chrono::Utc;
 use num_traits::Signed;
 use rust_decimal::prelude::*;
 use tracing::{debug, trace, warn};
 use trading_base::{Amount, Identifier, OrderType, PositionIntent, TradeIntent, UpdatePolicy};
+use uuid::Uuid;
 
 pub(crate) trait PositionIntentExt {
     fn is_expired(&self) -> bool;
@@ -39,33 +44,63 @@ impl OrderManager {
         if intent.is_expired() {
             // Intent has already expired, so don't do anything
             debug!("Expired intent");
-            Ok(())
+            let status = IntentStatus::for_intent(&intent, IntentState::Expired, None);
+            self.event_sender.send(Event::IntentStatus(status)).await
         } else if !intent.is_active() {
             // Not ready to transmit intent yet
             debug!("Sending intent to scheduler");
             db::save_scheduled_intent(self.db_client.as_ref(), &intent)
                 .await
                 .context("Failed to save scheduled intent")?;
-            self.schedule_position_intent(intent)
+            let status = IntentStatus::for_intent(&intent, IntentState::Scheduled, None);
+            self.schedule_position_intent(intent)?;
+            self.event_sender.send(Event::IntentStatus(status)).await
         } else {
             debug!("Evaluating intent");
-            let maybe_trade_intent = self.evaluate_intent(intent).await?;
-            if let Some(trade_intent) = maybe_trade_intent {
-                self.event_sender.send(Event::RiskCheckRequest(trade_intent)).await?
+            let maybe_order = self.evaluate_intent(intent).await?;
+            if let Some((order, claim_id)) = maybe_order {
+                self.execute_trade(order, claim_id).await?
             }
             Ok(())
         }
     }
 
+    /// Called when the scheduler releases an intent. Intents that were cancelled in the meantime
+    /// are dropped, and intents whose trigger time was moved later are scheduled again.
+    #[tracing::instrument(skip(self, intent), fields(id = %intent.id))]
+    pub(super) async fn trigger_scheduled_intent(&self, intent: PositionIntent) -> Result<()> {
+        let scheduled = match db::get_scheduled_intent(self.db_client.as_ref(), intent.id).await? {
+            Some(scheduled) => scheduled,
+            None => {
+                debug!("Scheduled intent was cancelled");
+                let status = IntentStatus::for_intent(
+                    &intent,
+                    IntentState::Cancelled,
+                    Some("Cancelled while scheduled".into()),
+                );
+                return self.event_sender.send(Event::IntentStatus(status)).await;
+            }
+        };
+        if !scheduled.is_active() {
+            debug!("Scheduled intent was rescheduled");
+            return self.schedule_position_intent(scheduled);
+        }
+        db::delete_scheduled_intent(self.db_client.as_ref(), intent.id).await?;
+        let status = IntentStatus::for_intent(&intent, IntentState::Triggered, None);
+        self.event_sender.send(Event::IntentStatus(status)).await?;
+        // The stored intent includes any corporate actions applied while it was scheduled
+        self.triage_intent(scheduled).await
+    }
+
     #[tracing::instrument(skip(self, intent))]
     pub fn schedule_position_intent(&self, intent: PositionIntent) -> Result<()> {
         self.scheduler_sender
-            .send(intent)
+            .send(ScheduleCommand::PositionIntent(intent))
             .context("Failed to send intent to scheduler")
     }
 
     #[tracing::instrument(skip(self, intent))]
-    async fn evaluate_intent(&self, intent: PositionIntent) -> Result<Option<TradeIntent>> {
+    async fn evaluate_intent(&self, intent: PositionIntent) -> Result<Option<(TradeOrder, Uuid)>> {
         trace!("Evaluating intent");
         match &intent.identifier {
             Identifier::Ticker(ticker) => self.evaluate_single_ticker_intent(&intent, ticker).await,
@@ -73,11 +108,17 @@ impl OrderManager {
         }
     }
 
-    async fn get_strategy_shares(&self, ticker: &str, strategy: &str, sub_strategy: Option<&str>) -> Result<Decimal> {
-        let maybe_position =
-            db::get_position_by_owner_and_ticker(self.db_client.as_ref(), strategy, sub_strategy, ticker)
-                .await
-                .context("Failed to get positions")?;
+    async fn get_strategy_shares(
+        &self,
+        account: &str,
+        ticker: &str,
+        strategy: &str,
+        sub_strategy: Option<&str>,
+    ) -> Result<Decimal> {
+        let owner = Owner::Strategy(strategy.to_string(), sub_strategy.map(ToString::to_string));
+        let maybe_position = db::get_position_by_owner_and_ticker(self.db_client.as_ref(), &owner, account, ticker)
+            .await
+            .context("Failed to get positions")?;
         Ok(maybe_position.as_ref().map(|x| x.shares).unwrap_or(Decimal::ZERO))
     }
 
@@ -86,23 +127,34 @@ impl OrderManager {
         &self,
         intent: &PositionIntent,
         ticker: &str,
-    ) -> Result<Option<TradeIntent>> {
+    ) -> Result<Option<(TradeOrder, Uuid)>> {
+        let account = self
+            .get_strategy_account(&intent.strategy, intent.sub_strategy.as_deref())
+            .await?;
         let strategy_shares = self
-            .get_strategy_shares(ticker, &intent.strategy, intent.sub_strategy.as_deref())
+            .get_strategy_shares(&account, ticker, &intent.strategy, intent.sub_strategy.as_deref())
             .await?;
         if !should_position_be_updated(intent, strategy_shares) {
+            let status = IntentStatus::for_intent(
+                intent,
+                IntentState::Ignored,
+                Some("Position retained by update policy".into()),
+            );
+            self.event_sender.send(Event::IntentStatus(status)).await?;
             return Ok(None);
         }
         if let Amount::Zero = intent.amount {
             // If there's no active trades for ticker, cancel any claim for this strategy and ticker
-            let active_amount = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), ticker).await?;
+            let active_amount =
+                db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), &account, ticker).await?;
             if active_amount.is_zero() {
                 debug!("Cancelling claim that is no longer active");
-                db::delete_claims_by_strategy_and_ticker(
+                db::archive_claims_by_strategy_and_ticker(
                     self.db_client.as_ref(),
                     &intent.strategy,
                     intent.sub_strategy.as_deref(),
                     ticker,
+                    ClaimState::Cancelled,
                 )
                 .await?;
             }
@@ -112,54 +164,140 @@ impl OrderManager {
             .ok()
             .or(intent.limit_price);
         let diff_amount = calculate_claim_amount(&intent.amount, strategy_shares, maybe_price);
+        let instructions = self.get_order_instructions(Some(intent.id)).await?;
         match diff_amount {
             Some(amount) if !amount.is_zero() => {
+                if !self
+                    .check_budgets(intent, &account, ticker, strategy_shares, &amount, maybe_price)
+                    .await
+                    .context("Failed to check budgets")?
+                {
+                    return Ok(None);
+                }
                 let active_trades: Vec<_> = db::get_trades_by_ticker(self.db_client.as_ref(), ticker)
                     .await?
                     .into_iter()
-                    .filter(Trade::is_active)
+                    .filter(|trade| trade.is_active() && trade.account == account)
                     .collect();
                 if !active_trades.is_empty() {
-                    let maybe_trade = self
-                        .generate_trades(ticker, &amount, intent.limit_price, intent.stop_price)
-                        .await?;
-                    if let Some(trade) = maybe_trade {
-                        let id = active_trades.first().expect("Guaranteed to be non-empty").id;
-                        db::save_dependent_trade(self.db_client.as_ref(), id, &trade).await?
+                    let maybe_superseded = db::get_claim_by_strategy_and_ticker(
+                        self.db_client.as_ref(),
+                        &intent.strategy,
+                        intent.sub_strategy.as_deref(),
+                        ticker,
+                    )
+                    .await
+                    .context("Failed to get claim")?;
+                    if let Some(superseded) = maybe_superseded {
+                        if superseded.amount == amount && superseded.limit_price == intent.limit_price {
+                            debug!("Claim is unchanged, leaving working trades in place");
+                        } else {
+                            debug!("Replacing working trades of superseded claim");
+                            let replaced = self.get_active_claim_trades(superseded.id).await?;
+                            self.save_claim(intent, &account, ticker, amount).await?;
+                            self.cancel_for_replacement(replaced).await?;
+                            return Ok(None);
+                        }
+                    } else {
+                        let maybe_trade = self
+                            .generate_trades(
+                                &account,
+                                ticker,
+                                &amount,
+                                intent.limit_price,
+                                intent.stop_price,
+                                &instructions,
+                                None,
+                            )
+                            .await?;
+                        if let Some(order) = maybe_trade {
+                            let id = active_trades.first().expect("Guaranteed to be non-empty").id;
+                            // The order kind is applied again at the prices at which the trade is
+                            // sent
+                            let dependent_trade = DependentTrade::new(id, order, instructions.clone())
+                                .with_trigger(instructions.trigger, instructions.trigger_quantity);
+                            db::save_dependent_trade(self.db_client.as_ref(), &dependent_trade).await?
+                        }
                     }
+                    let status = IntentStatus::for_intent(intent, IntentState::Accepted, None);
+                    self.event_sender.send(Event::IntentStatus(status)).await?;
                     return Ok(None);
                 }
-                let claim = Claim::new(
-                    intent.strategy.clone(),
-                    intent.sub_strategy.clone(),
-                    ticker.to_string(),
-                    amount,
-                    intent.limit_price,
-                    intent.before,
-                );
-                db::upsert_claim(self.db_client.as_ref(), &claim)
-                    .await
-                    .context("Failed to upsert claim")?;
-                self.event_sender.send(Event::Claim(claim.clone())).await?;
-                self.generate_trades(ticker, &claim.amount, intent.limit_price, intent.stop_price)
-                    .await
+                let claim = self.save_claim(intent, &account, ticker, amount).await?;
+                let maybe_trade = self
+                    .generate_trades(
+                        &account,
+                        ticker,
+                        &claim.amount,
+                        intent.limit_price,
+                        intent.stop_price,
+                        &instructions,
+                        Some(&claim),
+                    )
+                    .await?;
+                Ok(maybe_trade.map(|trade| (trade, claim.id)))
+            }
+            Some(_) => {
+                trace!("No trade generated");
+                let status = IntentStatus::for_intent(intent, IntentState::Completed, None);
+                self.event_sender.send(Event::IntentStatus(status)).await?;
+                Ok(None)
             }
-            _ => {
+            None => {
                 trace!("No trade generated");
+                let status = IntentStatus::for_intent(intent, IntentState::Ignored, Some("Missing price".into()));
+                self.event_sender.send(Event::IntentStatus(status)).await?;
                 Ok(None)
             }
         }
     }
 
-    #[tracing::instrument(skip(self))]
+    #[tracing::instrument(skip(self, intent, amount))]
+    async fn save_claim(&self, intent: &PositionIntent, account: &str, ticker: &str, amount: Amount) -> Result<Claim> {
+        let mut claim = Claim::new(
+            intent.strategy.clone(),
+            intent.sub_strategy.clone(),
+            ticker.to_string(),
+            amount,
+            intent.limit_price,
+            intent.before,
+        );
+        claim.set_intent_id(intent.id);
+        claim.set_account(account.to_string());
+        db::upsert_claim(self.db_client.as_ref(), &claim)
+            .await
+            .context("Failed to upsert claim")?;
+        self.event_sender.send(Event::Claim(claim.clone())).await?;
+        let status = IntentStatus::for_intent(intent, IntentState::Accepted, None);
+        self.event_sender.send(Event::IntentStatus(status)).await?;
+        Ok(claim)
+    }
+
+    /// Returns the order instructions that were sent along with the intent, if any.
+    pub(super) async fn get_order_instructions(&self, intent_id: Option<Uuid>) -> Result<OrderInstructions> {
+        let maybe_instructions = match intent_id {
+            Some(intent_id) => db::get_order_instructions(self.db_client.as_ref(), intent_id)
+                .await
+                .context("Failed to get order instructions")?,
+            None => None,
+        };
+        Ok(maybe_instructions.unwrap_or_default())
+    }
+
+    /// Generates the trade for `amount` in an account, netted against the positions and active
+    /// trades in that account.
+    #[tracing::instrument(skip(self, instructions, claim))]
     pub async fn generate_trades(
         &self,
+        account: &str,
         ticker: &str,
         amount: &Amount,
         limit_price: Option<Decimal>,
         stop_price: Option<Decimal>,
-    ) -> Result<Option<TradeIntent>> {
-        let positions = db::get_positions_by_ticker(self.db_client.as_ref(), ticker).await?;
+        instructions: &OrderInstructions,
+        claim: Option<&Claim>,
+    ) -> Result<Option<TradeOrder>> {
+        let positions = db::get_positions_by_ticker(self.db_client.as_ref(), account, ticker).await?;
         let diff_shares = match amount {
             Amount::Shares(shares) => *shares,
             Amount::Dollars(dollars) => {
@@ -178,10 +316,9 @@ impl OrderManager {
             }
             _ => unreachable!(),
         };
-        let active_shares: Decimal = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), ticker)
+        let active_shares: Decimal = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), account, ticker)
             .await
-            .context("Failed to get active trade amount")?
-            .into();
+            .context("Failed to get active trade amount")?;
         let owned_shares: Decimal = positions.iter().map(|pos| pos.shares).sum();
 
         let (sent, maybe_saved) = make_trades(
@@ -190,10 +327,48 @@ impl OrderManager {
             owned_shares + active_shares,
             limit_price,
             stop_price,
+            self.settings.is_fractional(ticker),
         )?;
+        let last_price = match instructions.order_kind {
+            OrderKind::TrailingStop => get_last_price(&self.datastore_url, ticker).await.ok(),
+            _ => None,
+        };
+        let mut sent = instructions
+            .apply(sent.with_account(account.to_string()), last_price)
+            .context("Failed to apply order instructions")?;
+        let maybe_saved = maybe_saved
+            .map(|mut saved| -> Result<TradeOrder> {
+                saved.intent = instructions.apply_time_in_force(saved.intent)?;
+                Ok(saved.with_account(account.to_string()))
+            })
+            .transpose()
+            .context("Failed to apply order instructions")?;
+        if let Amount::Dollars(dollars) = amount {
+            // A flip is sent in shares, since its legs are sized by the shares held
+            if maybe_saved.is_none()
+                && self.settings.accepts_notional(account)
+                && self.settings.is_fractional(ticker)
+                && can_send_notional(limit_price, stop_price, instructions)
+            {
+                debug!(%dollars, "Sending as notional order");
+                sent = sent.with_notional(*dollars);
+            }
+        }
+        if let Some(claim) = claim.filter(|_| instructions.has_bracket()) {
+            // When the trade flips the position, the bracket waits for the leg opening the new
+            // position, but only protects the shares of the claim
+            let parent = &maybe_saved.as_ref().unwrap_or(&sent).intent;
+            self.attach_bracket(parent, claim, diff_shares, instructions)
+                .await
+                .context("Failed to attach bracket")?;
+        }
         if let Some(saved) = maybe_saved {
             debug!("Saving dependent trade");
-            db::save_dependent_trade(self.db_client.as_ref(), sent.id, &saved)
+            // The second leg of a flip is not sent once the claim it was generated for has expired
+            let dependent_trade = DependentTrade::new(sent.intent.id, saved, instructions.clone())
+                .with_trigger(instructions.trigger, instructions.trigger_quantity)
+                .expires_at(claim.and_then(|c| c.before));
+            db::save_dependent_trade(self.db_client.as_ref(), &dependent_trade)
                 .await
                 .context("Failed to save dependent trade")?;
         }
@@ -205,11 +380,11 @@ impl OrderManager {
     async fn evaluate_multi_ticker_intent(&self, intent: PositionIntent) -> Result<()> {
         trace!("Evaluating multi-ticker intent");
         if let Amount::Zero = intent.amount {
-            let owner = Owner::Strategy(intent.strategy, intent.sub_strategy);
-            let positions_to_close = match intent.update_policy {
+            let owner = Owner::Strategy(intent.strategy.clone(), intent.sub_strategy.clone());
+            let positions_to_close: Vec<Position> = match intent.update_policy {
                 UpdatePolicy::Retain => {
                     debug!("UpdatePolicy::Retain: No trading needed");
-                    return Ok(());
+                    Vec::new()
                 }
                 UpdatePolicy::RetainLong => db::get_positions_by_owner(self.db_client.as_ref(), &owner)
                     .await
@@ -227,6 +402,9 @@ impl OrderManager {
                     .await
                     .context("Failed to get positions")?,
             };
+            // Every position closed creates a claim, so the status follows what is closed
+            let status = IntentStatus::for_multi_ticker_intent(&intent, positions_to_close.len());
+            self.event_sender.send(Event::IntentStatus(status)).await?;
             for position in positions_to_close {
                 self.close_position(position)
                     .await
@@ -241,11 +419,11 @@ impl OrderManager {
     #[tracing::instrument(skip(self, position), fields(position.ticker))]
     pub async fn close_position(&self, position: Position) -> Result<()> {
         let ticker = &position.ticker;
-        let positions = db::get_positions_by_ticker(self.db_client.as_ref(), ticker).await?;
-        let active_shares: Decimal = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), ticker)
+        let account = &position.account;
+        let positions = db::get_positions_by_ticker(self.db_client.as_ref(), account, ticker).await?;
+        let active_shares: Decimal = db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), account, ticker)
             .await
-            .context("Failed to get active trade amount")?
-            .into();
+            .context("Failed to get active trade amount")?;
         let owned_shares: Decimal = positions.iter().map(|pos| pos.shares).sum();
         let (sent, maybe_saved) = make_trades(
             &position.ticker,
@@ -253,16 +431,20 @@ impl OrderManager {
             owned_shares + active_shares,
             None,
             None,
+            self.settings.is_fractional(ticker),
         )?;
+        let sent = sent.with_account(account.clone());
         if let Some(saved) = maybe_saved {
             debug!("Saving dependent trade");
-            db::save_dependent_trade(self.db_client.as_ref(), sent.id, &saved)
+            let saved = saved.with_account(account.clone());
+            let dependent_trade = DependentTrade::new(sent.intent.id, saved, Default::default());
+            db::save_dependent_trade(self.db_client.as_ref(), &dependent_trade)
                 .await
                 .context("Failed to save dependent trade")?;
         }
 
         if let Owner::Strategy(strategy, sub_strategy) = position.owner {
-            let claim = Claim::new(
+            let mut claim = Claim::new(
                 strategy,
                 sub_strategy,
                 position.ticker.clone(),
@@ -270,9 +452,11 @@ impl OrderManager {
                 None,
                 None,
             );
+            claim.set_account(position.account.clone());
             db::upsert_claim(self.db_client.as_ref(), &claim)
                 .await
                 .context("Failed to save claim")?;
+            self.link_claim_trade(Some(claim.id), sent.intent.id).await?;
             self.event_sender.send(Event::Claim(claim)).await?;
         };
         self.send_trade(sent).await
@@ -313,18 +497,36 @@ fn make_trades(
     total_shares: Decimal,
     limit_price: Option<Decimal>,
     stop_price: Option<Decimal>,
-) -> Result<(TradeIntent, Option<TradeIntent>)> {
+    fractional: bool,
+) -> Result<(TradeOrder, Option<TradeOrder>)> {
     let signum_product = total_shares.signum() * (diff_shares + total_shares).signum();
     if !signum_product.is_sign_negative() {
-        let sent = make_trade_intent(ticker, diff_shares, limit_price, stop_price)?;
+        let sent = make_trade_order(ticker, diff_shares, limit_price, stop_price, fractional)?;
         Ok((sent, None))
     } else {
-        let sent = make_trade_intent(ticker, -total_shares, limit_price, stop_price)?;
-        let saved = make_trade_intent(ticker, diff_shares + total_shares, limit_price, stop_price)?;
+        let sent = make_trade_order(ticker, -total_shares, limit_price, stop_price, fractional)?;
+        let saved = make_trade_order(ticker, diff_shares + total_shares, limit_price, stop_price, fractional)?;
         Ok((sent, Some(saved)))
     }
 }
 
+/// Makes the order for `qty` shares, which keeps its fractional part in tickers traded in
+/// fractions.
+fn make_trade_order(
+    ticker: &str,
+    qty: Decimal,
+    limit_price: Option<Decimal>,
+    stop_price: Option<Decimal>,
+    fractional: bool,
+) -> Result<TradeOrder> {
+    let order = TradeOrder::new(make_trade_intent(ticker, qty, limit_price, stop_price)?);
+    if fractional {
+        order.with_quantity(qty)
+    } else {
+        Ok(order)
+    }
+}
+
 #[tracing::instrument(skip(ticker, qty, limit_price, stop_price))]
 fn make_trade_intent(
     ticker: &str,
@@ -352,7 +554,19 @@ fn make_trade_intent(
     Ok(intent)
 }
 
-async fn get_last_price(base_url: &str, ticker: &str) -> Result<Decimal> {
+/// Notional orders are market day orders.
+fn can_send_notional(
+    limit_price: Option<Decimal>,
+    stop_price: Option<Decimal>,
+    instructions: &OrderInstructions,
+) -> bool {
+    limit_price.is_none()
+        && stop_price.is_none()
+        && instructions.order_kind == OrderKind::Standard
+        && matches!(instructions.time_in_force, None | Some(TimeInForce::Day))
+}
+
+pub(crate) async fn get_last_price(base_url: &str, ticker: &str) -> Result<Decimal> {
     let url = format!("{}/last/{}", base_url, ticker);
     let price: Decimal = reqwest::get(url).await?.json().await?;
     Ok(price)
@@ -434,17 +648,53 @@ mod test {
         assert_eq!(large_round_down.qty, -1);
     }
 
+    #[test]
+    fn test_can_send_notional() {
+        let standard = OrderInstructions::default();
+        assert!(can_send_notional(None, None, &standard));
+        assert!(!can_send_notional(Some(Decimal::ONE), None, &standard));
+        assert!(!can_send_notional(None, Some(Decimal::ONE), &standard));
+        let day = OrderInstructions {
+            time_in_force: Some(TimeInForce::Day),
+            ..Default::default()
+        };
+        assert!(can_send_notional(None, None, &day));
+        let gtc = OrderInstructions {
+            time_in_force: Some(TimeInForce::Gtc),
+            ..Default::default()
+        };
+        assert!(!can_send_notional(None, None, &gtc));
+        let market_on_close = OrderInstructions {
+            order_kind: OrderKind::MarketOnClose,
+            ..Default::default()
+        };
+        assert!(!can_send_notional(None, None, &market_on_close));
+    }
+
     #[test]
     fn test_make_trades() {
         // No change in sign leads to one trade
-        let (sent, maybe_saved) = make_trades("AAPL", Decimal::TWO, Decimal::ONE, None, None).unwrap();
-        assert_eq!(sent.qty, 2);
+        let (sent, maybe_saved) = make_trades("AAPL", Decimal::TWO, Decimal::ONE, None, None, false).unwrap();
+        assert_eq!(sent.intent.qty, 2);
         assert_eq!(maybe_saved, None);
 
         // Change in sign leads to two trades
-        let (sent, maybe_saved) = make_trades("AAPL", -Decimal::TWO, Decimal::ONE, None, None).unwrap();
-        assert_eq!(sent.qty, -1);
+        let (sent, maybe_saved) = make_trades("AAPL", -Decimal::TWO, Decimal::ONE, None, None, false).unwrap();
+        assert_eq!(sent.intent.qty, -1);
         let saved = maybe_saved.unwrap();
-        assert_eq!(saved.qty, -1);
+        assert_eq!(saved.intent.qty, -1);
+
+        // Fractional tickers keep the fractions of both legs
+        let (sent, maybe_saved) =
+            make_trades("AAPL", Decimal::new(-25, 1), Decimal::new(5, 1), None, None, true).unwrap();
+        assert_eq!(sent.quantity(), Decimal::new(-5, 1));
+        assert_eq!(sent.intent.qty, -1);
+        let saved = maybe_saved.unwrap();
+        assert_eq!(saved.quantity(), -Decimal::TWO);
+        assert!(saved.extensions.is_default());
+
+        // Other tickers trade whole shares
+        let (sent, _) = make_trades("AAPL", Decimal::new(5, 1), Decimal::ONE, None, None, false).unwrap();
+        assert_eq!(sent.quantity(), Decimal::ONE);
     }
 }
@@ -1,39 +1,59 @@
 use crate::db;
 use crate::event_sender::Event;
+use crate::intent_scheduler::{ScheduleCommand, Scheduled};
 use crate::settings::AppSettings;
-use crate::types::Trade;
+use crate::types::{can_send_outside_market_hours, QueuedTrade, Trade, TradeEvent, TradeOrder, TradingCalendar};
 use crate::EventSenderHandle;
 use anyhow::{Context, Result};
+use chrono::Utc;
 use rdkafka::consumer::StreamConsumer;
 use std::sync::Arc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio_postgres::Client;
 use tracing::{debug, error, info};
-use trading_base::{PositionIntent, TradeIntent, TradeMessage};
+use trading_base::TradeMessage;
 use uuid::Uuid;
 
+mod accounts;
+mod brackets;
+mod budgets;
+mod cancel_replace;
 mod dependent_trades;
+mod executions;
+mod fees;
+mod house;
 mod input;
 mod intents;
 mod order_updates;
+mod queued_trades;
 mod reconciliation;
+mod recurring_intents;
 mod risk_check;
+mod risk_rules;
+
+pub(crate) use input::Command;
+pub(crate) use intents::get_last_price;
 
 pub struct OrderManager {
     kafka_consumer: StreamConsumer,
-    scheduler_sender: UnboundedSender<PositionIntent>,
-    scheduler_receiver: UnboundedReceiver<PositionIntent>,
+    scheduler_sender: UnboundedSender<ScheduleCommand>,
+    scheduler_receiver: UnboundedReceiver<Scheduled>,
+    command_receiver: UnboundedReceiver<Command>,
     event_sender: EventSenderHandle,
     db_client: Arc<Client>,
     datastore_url: String,
     settings: AppSettings,
+    calendar: TradingCalendar,
+    /// Market state of the last clock message
+    market_open: Option<bool>,
 }
 
 impl OrderManager {
     pub fn new(
         kafka_consumer: StreamConsumer,
-        scheduler_sender: UnboundedSender<PositionIntent>,
-        scheduler_receiver: UnboundedReceiver<PositionIntent>,
+        scheduler_sender: UnboundedSender<ScheduleCommand>,
+        scheduler_receiver: UnboundedReceiver<Scheduled>,
+        command_receiver: UnboundedReceiver<Command>,
         event_sender: EventSenderHandle,
         db_client: Arc<Client>,
         datastore_url: String,
@@ -43,10 +63,13 @@ impl OrderManager {
             kafka_consumer,
             scheduler_sender,
             scheduler_receiver,
+            command_receiver,
             event_sender,
             db_client,
             datastore_url,
             settings,
+            calendar: TradingCalendar::nyse(),
+            market_open: None,
         }
     }
 
@@ -64,36 +87,76 @@ impl OrderManager {
         }
     }
 
-    async fn initalize(&self) -> Result<()> {
+    async fn initalize(&mut self) -> Result<()> {
+        if let Some(path) = &self.settings.calendar.path {
+            debug!(%path, "Loading trading calendar");
+            self.calendar = TradingCalendar::from_file(path).context("Failed to load trading calendar")?;
+        }
+        self.schedule_reconciliation(Utc::now())?;
         debug!("Populating scheduled intents");
-        let scheduled_intents = db::get_scheduled_indents(self.db_client.as_ref())
+        let scheduled_intents = db::get_scheduled_intents(self.db_client.as_ref())
             .await
             .context("Failed to get scheduled intents")?;
         for intent in scheduled_intents {
             self.schedule_position_intent(intent)
                 .context("Failed to schedule position intent")?
         }
+        debug!("Populating executions");
+        let executions = db::get_executions(self.db_client.as_ref())
+            .await
+            .context("Failed to get executions")?;
+        for execution in executions {
+            if let Some(at) = execution.next_release_at {
+                self.schedule_execution_slice(execution.id, at)?
+            }
+        }
+        debug!("Populating recurring intents");
+        let recurring_intents = db::get_recurring_intents(self.db_client.as_ref())
+            .await
+            .context("Failed to get recurring intents")?;
+        for recurring_intent in recurring_intents {
+            let at = recurring_intent.next_run_at.unwrap_or_else(Utc::now);
+            self.schedule_recurring_intent(recurring_intent.id, at)?
+        }
         Ok(())
     }
 
-    async fn send_trade(&self, intent: TradeIntent) -> Result<()> {
-        db::save_trade(
-            self.db_client.as_ref(),
-            Trade::new(intent.id, intent.ticker.clone(), intent.qty as i32),
-        )
-        .await
-        .context("Failed to save pending trade")?;
+    async fn send_trade(&self, order: TradeOrder) -> Result<()> {
+        let intent = &order.intent;
+        if !self.is_market_open() && !can_send_outside_market_hours(intent)? {
+            // The trade passed its risk check already, so it is sent as is once the market opens
+            debug!(id = %intent.id, "Market closed, queueing market order");
+            let claim_id = db::get_trade_claim_id(self.db_client.as_ref(), intent.id).await?;
+            return db::save_queued_trade(
+                self.db_client.as_ref(),
+                &QueuedTrade::new(order, claim_id).risk_checked(),
+            )
+            .await
+            .context("Failed to save queued trade");
+        }
+        let mut trade = Trade::new(intent.id, intent.ticker.clone(), order.quantity());
+        trade.account = order.account.clone();
+        let event = TradeEvent::new(trade.id, None, trade.status);
+        let inserted = db::insert_trade(self.db_client.as_ref(), &trade)
+            .await
+            .context("Failed to save pending trade")?;
+        if !inserted {
+            debug!(id = %intent.id, "Trade has already been sent");
+            return Ok(());
+        }
+        db::save_trade_event(self.db_client.as_ref(), &event)
+            .await
+            .context("Failed to save trade event")?;
 
         self.event_sender
-            .send(Event::TradeMessage(TradeMessage::New { intent }))
+            .send_order(order)
             .await
-            .context("Failed to send trade")?;
-        Ok(())
+            .context("Failed to send trade")
     }
 
-    async fn cancel_trade(&self, broker_id: Uuid) -> Result<()> {
+    async fn cancel_trade(&self, broker_id: Uuid, account: &str) -> Result<()> {
         self.event_sender
-            .send(Event::TradeMessage(TradeMessage::Cancel { id: broker_id }))
+            .send_for_account(account, Event::TradeMessage(TradeMessage::Cancel { id: broker_id }))
             .await
             .context("Failed to send cancellation message")?;
         Ok(())
@@ -1,12 +1,16 @@
+use super::fees::calculate_fees;
 use super::OrderManager;
 use crate::db;
 use crate::event_sender::Event;
-use crate::types::{split_lot, Allocation, Lot};
+use crate::types::{
+    allocate_lot, default_account, split_lot, Allocation, ClaimState, IntentState, IntentStatus, Lot, Owner, Trade,
+    TradeEvent,
+};
 use alpaca::{Event as AlpacaEvent, OrderEvent, Side};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use rust_decimal::prelude::*;
-use tracing::debug;
+use tracing::{debug, warn};
 use trading_base::Amount;
 use uuid::Uuid;
 
@@ -19,23 +23,55 @@ impl OrderManager {
         debug!(status = ?event.event, "Order status update");
         match event.event {
             AlpacaEvent::New => {
-                db::save_trade(self.db_client.as_ref(), From::from(event.order)).await?;
+                if !self.update_trade(From::from(event.order)).await? {
+                    return Ok(());
+                }
+                self.complete_replacement(id, &ticker)
+                    .await
+                    .context("Failed to complete replacement")?;
             }
             AlpacaEvent::Canceled { .. } => {
-                db::save_trade(self.db_client.as_ref(), From::from(event.order)).await?;
+                if !self.update_trade(From::from(event.order)).await? {
+                    return Ok(());
+                }
+                self.resize_bracket_leg(id)
+                    .await
+                    .context("Failed to resize bracket leg")?;
+                self.settle_dependent_trades(id)
+                    .await
+                    .context("Failed to settle dependent trades")?;
+                self.abandon_execution(id)
+                    .await
+                    .context("Failed to abandon execution")?;
+                self.complete_replacement(id, &ticker)
+                    .await
+                    .context("Failed to complete replacement")?;
             }
             AlpacaEvent::Expired { .. } | AlpacaEvent::Rejected { .. } => {
-                db::save_trade(self.db_client.as_ref(), From::from(event.order)).await?;
+                if !self.update_trade(From::from(event.order)).await? {
+                    return Ok(());
+                }
+                self.settle_dependent_trades(id)
+                    .await
+                    .context("Failed to settle dependent trades")?;
+                self.abandon_execution(id)
+                    .await
+                    .context("Failed to abandon execution")?;
+                self.complete_replacement(id, &ticker)
+                    .await
+                    .context("Failed to complete replacement")?;
             }
             AlpacaEvent::Fill {
                 timestamp, qty, price, ..
             } => {
                 debug!("Order filled");
                 let qty = match event.order.side {
-                    Side::Buy => Decimal::from_isize(qty).unwrap(),
-                    Side::Sell => -Decimal::from_isize(qty).unwrap(),
+                    Side::Buy => qty,
+                    Side::Sell => -qty,
                 };
-                db::save_trade(self.db_client.as_ref(), From::from(event.order)).await?;
+                // The fill happened at the broker even if the status update is rejected, so it
+                // is always booked
+                self.update_trade(From::from(event.order)).await?;
                 let new_lot = self
                     .make_lot(id, &ticker, timestamp, price, qty)
                     .await
@@ -50,16 +86,27 @@ impl OrderManager {
                 debug!("Triggering dependent trades");
                 self.trigger_dependent_trades(id)
                     .await
-                    .context("Failed to trigger dependent-trades")?
+                    .context("Failed to trigger dependent-trades")?;
+                self.advance_execution(id)
+                    .await
+                    .context("Failed to advance execution")?;
+                self.settle_bracket_leg(id)
+                    .await
+                    .context("Failed to settle bracket leg")?;
+                self.complete_replacement(id, &ticker)
+                    .await
+                    .context("Failed to complete replacement")?
             }
             AlpacaEvent::PartialFill {
                 timestamp, qty, price, ..
             } => {
                 let qty = match event.order.side {
-                    Side::Buy => Decimal::from_isize(qty).unwrap(),
-                    Side::Sell => -Decimal::from_isize(qty).unwrap(),
+                    Side::Buy => qty,
+                    Side::Sell => -qty,
                 };
-                db::save_trade(self.db_client.as_ref(), From::from(event.order)).await?;
+                // The fill happened at the broker even if the status update is rejected, so it
+                // is always booked
+                self.update_trade(From::from(event.order)).await?;
                 let new_lot = self
                     .make_lot(id, &ticker, timestamp, price, qty)
                     .await
@@ -69,12 +116,51 @@ impl OrderManager {
                     .context("Failed to make lot")?;
                 self.event_sender.send(Event::Lot(new_lot.clone())).await?;
                 self.assign_lot(new_lot).await.context("Failed to assign lot")?;
+                self.trigger_partial_fill_dependent_trades(id)
+                    .await
+                    .context("Failed to trigger partial fill dependent trades")?;
+                self.settle_bracket_leg(id)
+                    .await
+                    .context("Failed to settle bracket leg")?;
             }
             _ => (),
         }
         Ok(())
     }
 
+    /// Applies an update from the broker to the stored trade and records it in the trade's
+    /// history. Returns false if the trade state machine rejected the update.
+    #[tracing::instrument(skip(self, update), fields(id = %update.id))]
+    pub(super) async fn update_trade(&self, update: Trade) -> Result<bool> {
+        let maybe_trade = db::get_trade_by_id(self.db_client.as_ref(), update.id)
+            .await
+            .context("Failed to get trade")?;
+        let (trade, from_status) = match maybe_trade {
+            Some(mut trade) => {
+                let from_status = trade.status;
+                if let Err(error) = trade.apply_update(&update) {
+                    warn!(%error, "Rejected trade update");
+                    db::save_trade_event(self.db_client.as_ref(), &error.into())
+                        .await
+                        .context("Failed to save trade event")?;
+                    return Ok(false);
+                }
+                (trade, Some(from_status))
+            }
+            // Trades are saved before they are sent, so unknown trades were not placed by the order
+            // manager and are booked to the default account
+            None => (update, None),
+        };
+        let event = TradeEvent::new(trade.id, from_status, trade.status);
+        db::save_trade(self.db_client.as_ref(), trade)
+            .await
+            .context("Failed to save trade")?;
+        db::save_trade_event(self.db_client.as_ref(), &event)
+            .await
+            .context("Failed to save trade event")?;
+        Ok(true)
+    }
+
     #[tracing::instrument(skip(self, ticker, timestamp, price, quantity))]
     async fn make_lot(
         &self,
@@ -84,15 +170,35 @@ impl OrderManager {
         price: Decimal,
         quantity: Decimal,
     ) -> Result<Lot> {
-        Ok(Lot::new(id, ticker.to_string(), timestamp, price, quantity))
+        let fees = calculate_fees(&self.settings.fees, price, quantity);
+        let account = match db::get_trade_by_id(self.db_client.as_ref(), id).await? {
+            Some(mut trade) => {
+                trade.record_fill(quantity, price, fees);
+                db::update_trade_fill(self.db_client.as_ref(), &trade)
+                    .await
+                    .context("Failed to update trade fill")?;
+                trade.account
+            }
+            None => default_account(),
+        };
+        Ok(Lot::new(id, ticker.to_string(), timestamp, price, quantity)
+            .with_fees(fees)
+            .with_account(account))
     }
 
     #[tracing::instrument(skip(self, lot))]
     async fn assign_lot(&self, lot: Lot) -> Result<()> {
-        let claims = db::get_claims_by_ticker(self.db_client.as_ref(), &lot.ticker)
-            .await
-            .context("Failed to get claim")?;
-        let allocations = split_lot(&claims, &lot);
+        // Fills of bracket legs close the position of the strategy that owns the bracket, rather
+        // than going to the claims on the ticker
+        let allocations = match db::get_bracket_leg(self.db_client.as_ref(), lot.order_id).await? {
+            Some(leg) => vec![allocate_lot(Owner::Strategy(leg.strategy, leg.sub_strategy), &lot)],
+            None => {
+                let claims = db::get_claims_by_ticker(self.db_client.as_ref(), &lot.ticker)
+                    .await
+                    .context("Failed to get claim")?;
+                split_lot(&claims, &lot, self.settings.allocates_whole_shares(&lot.ticker))
+            }
+        };
         for allocation in allocations {
             self.adjust_claim(&allocation).await.context("Failed to adjust claim")?;
             db::save_allocation(self.db_client.as_ref(), &allocation)
@@ -113,15 +219,31 @@ impl OrderManager {
             db::update_claim_amount(self.db_client.as_ref(), claim_id, &amount)
                 .await
                 .context("Failed to update claim amount")?;
+            let (claim_state, state) = if is_claim_satisfied(&claim.amount, &amount) {
+                (ClaimState::Satisfied, IntentState::Completed)
+            } else {
+                (ClaimState::PartiallySatisfied, IntentState::PartiallyFilled)
+            };
+            db::update_claim_state(self.db_client.as_ref(), claim_id, claim_state)
+                .await
+                .context("Failed to update claim state")?;
+            if let Some(status) = IntentStatus::for_claim(&claim, state, None) {
+                self.event_sender.send(Event::IntentStatus(status)).await?;
+            }
         };
         Ok(())
     }
 }
 
+/// A claim is satisfied once its remaining amount is zero or has crossed over zero.
+fn is_claim_satisfied(old_amount: &Amount, new_amount: &Amount) -> bool {
+    new_amount.is_zero() || new_amount.is_sign_positive() != old_amount.is_sign_positive()
+}
+
 fn calculate_claim_adjustment_amount(claim_amount: &Amount, allocation: &Allocation) -> Amount {
     match claim_amount {
         Amount::Dollars(dollars) => {
-            let new_dollars = dollars - allocation.basis;
+            let new_dollars = dollars - allocation.gross_basis();
             Amount::Dollars(new_dollars)
         }
         Amount::Shares(shares) => {
@@ -0,0 +1,137 @@
+use super::OrderManager;
+use crate::db;
+use crate::types::{OrderInstructions, QueuedTrade, Session, TimeInForce, TradeOrder};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use tracing::debug;
+use trading_base::{OrderType, TradeIntent};
+use uuid::Uuid;
+
+impl OrderManager {
+    /// The market state of the last clock message, or of the trading calendar if none has been
+    /// received yet.
+    pub(super) fn is_market_open(&self) -> bool {
+        if !self.settings.calendar.enforce_market_hours {
+            return true;
+        }
+        self.market_open.unwrap_or_else(|| self.calendar.is_open(Utc::now()))
+    }
+
+    /// Called for trades generated while the market is closed. Extended hours orders are sent in
+    /// the pre- and post-market sessions. If configured, other trades that can be executed in the
+    /// current extended hours session are sent as extended hours orders and trades that can take
+    /// part in the opening auction are sent for it. All others are held until the next open.
+    #[tracing::instrument(skip(self, order, claim_id), fields(id = %order.intent.id))]
+    pub(super) async fn hold_trade(&self, mut order: TradeOrder, claim_id: Option<Uuid>) -> Result<Option<TradeOrder>> {
+        let now = Utc::now();
+        if (order.extensions.extended_hours || self.settings.calendar.convert_to_extended_hours)
+            && can_trade_extended_hours(&order.intent, self.calendar.session(now))?
+        {
+            debug!("Sending trade as extended hours order");
+            order.extensions.extended_hours = true;
+            return Ok(Some(order));
+        }
+        if self.settings.calendar.convert_to_opening_auction
+            && can_join_opening_auction(&order, self.calendar.accepts_opening_auction_orders(now))
+        {
+            debug!("Converting trade to opening auction order");
+            let instructions = OrderInstructions {
+                time_in_force: Some(TimeInForce::Opg),
+                ..Default::default()
+            };
+            order.intent = instructions.apply_time_in_force(order.intent)?;
+            return Ok(Some(order));
+        }
+        debug!("Market closed, queueing trade");
+        db::save_queued_trade(self.db_client.as_ref(), &QueuedTrade::new(order, claim_id))
+            .await
+            .context("Failed to save queued trade")?;
+        Ok(None)
+    }
+
+    /// Sends the trades that were queued while the market was closed. Trades for claims that have
+    /// since been replaced are dropped, since the replacing claim queued its own trade.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn release_queued_trades(&self) -> Result<()> {
+        let queued_trades = db::take_queued_trades(self.db_client.as_ref())
+            .await
+            .context("Failed to take queued trades")?;
+        for queued_trade in queued_trades {
+            if let Some(claim_id) = queued_trade.claim_id {
+                if db::find_claim_by_id(self.db_client.as_ref(), claim_id).await?.is_none() {
+                    debug!(id = %queued_trade.id, %claim_id, "Dropping queued trade of replaced claim");
+                    continue;
+                }
+            }
+            debug!(id = %queued_trade.id, "Releasing queued trade");
+            if queued_trade.risk_checked {
+                self.send_trade(queued_trade.order).await?;
+            } else {
+                self.request_risk_check(queued_trade.order, queued_trade.claim_id)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Market and limit orders can be sent for the opening auction while the broker accepts orders
+/// for it. Extended hours orders must stay day orders, so they wait for the open instead.
+fn can_join_opening_auction(order: &TradeOrder, accepts_opening_auction_orders: bool) -> bool {
+    matches!(order.intent.order_type, OrderType::Market | OrderType::Limit { .. })
+        && !order.extensions.extended_hours
+        && accepts_opening_auction_orders
+}
+
+/// Only limit day orders can be executed in the pre- and post-market sessions.
+fn can_trade_extended_hours(intent: &TradeIntent, session: Session) -> Result<bool> {
+    Ok(matches!(intent.order_type, OrderType::Limit { .. })
+        && TimeInForce::of(intent)? == TimeInForce::Day
+        && matches!(session, Session::PreMarket | Session::PostMarket))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn limit_order() -> TradeIntent {
+        let instructions = OrderInstructions {
+            time_in_force: Some(TimeInForce::Day),
+            ..Default::default()
+        };
+        let trade = TradeIntent::new("AAPL", 10).order_type(OrderType::Limit {
+            limit_price: Decimal::ONE,
+        });
+        instructions.apply_time_in_force(trade).unwrap()
+    }
+
+    #[test]
+    fn test_can_join_opening_auction() {
+        let market = TradeOrder::new(TradeIntent::new("AAPL", 10));
+        assert!(can_join_opening_auction(&market, true));
+        assert!(can_join_opening_auction(&TradeOrder::new(limit_order()), true));
+        assert!(!can_join_opening_auction(&market, false));
+        let stop = TradeIntent::new("AAPL", 10).order_type(OrderType::Stop {
+            stop_price: Decimal::ONE,
+        });
+        assert!(!can_join_opening_auction(&TradeOrder::new(stop), true));
+        let mut extended_hours = TradeOrder::new(limit_order());
+        extended_hours.extensions.extended_hours = true;
+        assert!(!can_join_opening_auction(&extended_hours, true));
+    }
+
+    #[test]
+    fn test_can_trade_extended_hours() {
+        assert!(can_trade_extended_hours(&limit_order(), Session::PreMarket).unwrap());
+        assert!(can_trade_extended_hours(&limit_order(), Session::PostMarket).unwrap());
+        assert!(!can_trade_extended_hours(&limit_order(), Session::Closed).unwrap());
+        assert!(!can_trade_extended_hours(&TradeIntent::new("AAPL", 10), Session::PreMarket).unwrap());
+        let instructions = OrderInstructions {
+            time_in_force: Some(TimeInForce::Gtc),
+            ..Default::default()
+        };
+        let gtc = instructions.apply_time_in_force(limit_order()).unwrap();
+        assert!(!can_trade_extended_hours(&gtc, Session::PreMarket).unwrap());
+    }
+}
@@ -1,18 +1,46 @@
 use crate::db;
-use crate::types::{Owner, Status};
-use crate::Event;
+use crate::event_sender::Event;
+use crate::intent_scheduler::ScheduleCommand;
+use crate::settings::StaleTradeAction;
+use crate::types::{ClaimState, IntentState, IntentStatus, Resolution, StaleTradeResolution, Status, Trade};
 use crate::OrderManager;
-use anyhow::Result;
-use chrono::{Duration, Utc};
-use rust_decimal::prelude::*;
+use alpaca::Order;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
 use tracing::{debug, warn};
-use trading_base::Amount;
+use uuid::Uuid;
 
 impl OrderManager {
+    pub(super) fn schedule_reconciliation(&self, at: DateTime<Utc>) -> Result<()> {
+        self.scheduler_sender
+            .send(ScheduleCommand::Reconcile { at })
+            .context("Failed to send reconciliation to scheduler")
+    }
+
+    /// Reconciles while the market is open, and schedules the next reconciliation from the
+    /// trading calendar, so that reconciliation does not depend on the external clock.
+    #[tracing::instrument(skip(self))]
+    pub async fn run_scheduled_reconciliation(&self) -> Result<()> {
+        let now = Utc::now();
+        if self.calendar.is_open(now) {
+            let next = now + Duration::seconds(self.settings.calendar.reconcile_interval_seconds as i64);
+            self.schedule_reconciliation(next)?;
+            self.reconcile().await
+        } else {
+            self.schedule_reconciliation(self.calendar.next_open(now))
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn reconcile(&self) -> Result<()> {
         debug!("Running reconciliation checks");
+        if self.is_market_open() {
+            self.release_queued_trades().await?;
+        }
         self.cancel_old_unreported_trades().await?;
+        self.resolve_stale_trades().await?;
+        self.expire_risk_check_requests().await?;
+        self.expire_dependent_trades().await?;
         self.reconcile_claims().await?;
         self.reconcile_house_positions().await
     }
@@ -31,6 +59,133 @@ impl OrderManager {
         Ok(())
     }
 
+    /// Applies the staleness policies to accepted and partially filled trades that have stopped
+    /// receiving updates, since they otherwise keep counting towards the active amount of their
+    /// ticker and block the claims in it from being traded.
+    #[tracing::instrument(skip(self))]
+    async fn resolve_stale_trades(&self) -> Result<()> {
+        let policies = [
+            (Status::Accepted, &self.settings.stale_trades.accepted),
+            (Status::PartiallyFilled, &self.settings.stale_trades.partially_filled),
+        ];
+        for (status, policy) in policies.iter() {
+            if policy.action == StaleTradeAction::Ignore {
+                continue;
+            }
+            let stale_since = Utc::now() - Duration::seconds(policy.after_seconds as i64);
+            let trades = db::get_stale_trades(self.db_client.as_ref(), *status, stale_since).await?;
+            for trade in trades {
+                let last_resolution = db::get_last_stale_trade_resolution(self.db_client.as_ref(), trade.id).await?;
+                let cancel_sent = last_resolution
+                    .as_ref()
+                    .map(|last| last.resolution == Resolution::CancelSent)
+                    .unwrap_or(false);
+                match (policy.action, trade.broker_id, last_resolution) {
+                    // Waiting for the broker to answer the cancel, or for an operator to follow up
+                    (_, _, Some(resolution)) if resolution.datetime >= stale_since => continue,
+                    (StaleTradeAction::Cancel, Some(broker_id), _) if !cancel_sent => {
+                        warn!(id = %trade.id, ?status, "Cancelling stale trade");
+                        db::request_trade_cancel(self.db_client.as_ref(), trade.id).await?;
+                        self.cancel_trade(broker_id, &trade.account).await?;
+                        let resolution = StaleTradeResolution::new(&trade, Resolution::CancelSent);
+                        db::save_stale_trade_resolution(self.db_client.as_ref(), &resolution).await?;
+                    }
+                    _ => self.end_stale_trade(trade).await?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Asks the broker for the status of a stale trade and applies it if the trade has ended. The
+    /// trade is marked dead if the broker can't be asked, and left in place if it is still
+    /// working or has fills that were never received.
+    #[tracing::instrument(skip(self, trade), fields(id = %trade.id))]
+    async fn end_stale_trade(&self, trade: Trade) -> Result<()> {
+        let order = self.query_broker_order(&trade).await;
+        let resolution = Resolution::for_broker_order(&trade, order.as_ref());
+        db::save_stale_trade_resolution(self.db_client.as_ref(), &StaleTradeResolution::new(&trade, resolution))
+            .await?;
+        match (resolution, order) {
+            (Resolution::ReportedByBroker, Some(order)) => self.apply_broker_order(order).await,
+            (Resolution::MarkedDead, _) => self.mark_trade_dead(trade).await,
+            (resolution, _) => {
+                warn!(?resolution, "Leaving stale trade in place");
+                Ok(())
+            }
+        }
+    }
+
+    async fn query_broker_order(&self, trade: &Trade) -> Option<Trade> {
+        let base_url = self.settings.stale_trades.broker_url.as_ref()?;
+        let broker_id = trade.broker_id?;
+        match get_broker_order(base_url, broker_id).await {
+            Ok(order) => Some(order),
+            Err(error) => {
+                warn!(%error, "Failed to query broker for stale trade");
+                None
+            }
+        }
+    }
+
+    /// Applies the final status the broker reports for a trade whose update was never received.
+    #[tracing::instrument(skip(self, order), fields(id = %order.id))]
+    async fn apply_broker_order(&self, order: Trade) -> Result<()> {
+        let (id, ticker, status) = (order.id, order.ticker.clone(), order.status);
+        debug!(?status, "Applying status reported by broker");
+        if !self.update_trade(order).await? {
+            return Ok(());
+        }
+        if status == Status::Filled {
+            self.trigger_dependent_trades(id)
+                .await
+                .context("Failed to trigger dependent-trades")?;
+            self.advance_execution(id)
+                .await
+                .context("Failed to advance execution")?;
+            self.settle_bracket_leg(id)
+                .await
+                .context("Failed to settle bracket leg")?;
+        } else {
+            if status == Status::Cancelled {
+                self.resize_bracket_leg(id)
+                    .await
+                    .context("Failed to resize bracket leg")?;
+            }
+            self.settle_dependent_trades(id)
+                .await
+                .context("Failed to settle dependent trades")?;
+            self.abandon_execution(id)
+                .await
+                .context("Failed to abandon execution")?;
+        }
+        self.complete_replacement(id, &ticker)
+            .await
+            .context("Failed to complete replacement")
+    }
+
+    /// Ends a trade without a terminal update from the broker, releasing or cancelling whatever
+    /// was waiting on it.
+    #[tracing::instrument(skip(self, trade), fields(id = %trade.id))]
+    async fn mark_trade_dead(&self, trade: Trade) -> Result<()> {
+        warn!(status = ?trade.status, "Marking stale trade as dead");
+        let (id, ticker) = (trade.id, trade.ticker.clone());
+        let mut update = trade;
+        update.status = Status::Dead;
+        if !self.update_trade(update).await? {
+            return Ok(());
+        }
+        self.settle_dependent_trades(id)
+            .await
+            .context("Failed to settle dependent trades")?;
+        self.abandon_execution(id)
+            .await
+            .context("Failed to abandon execution")?;
+        self.complete_replacement(id, &ticker)
+            .await
+            .context("Failed to complete replacement")
+    }
+
     #[tracing::instrument(skip(self))]
     async fn reconcile_claims(&self) -> Result<()> {
         let claims = db::get_claims(self.db_client.as_ref()).await?;
@@ -38,60 +193,59 @@ impl OrderManager {
         for claim in claims {
             if let Some(before) = claim.before {
                 if before < Utc::now() {
-                    db::delete_claim_by_id(self.db_client.as_ref(), claim.id).await?;
-                    let active_trades = db::get_trades_by_ticker(self.db_client.as_ref(), &claim.ticker)
-                        .await?
-                        .into_iter()
-                        .filter(|trade| trade.is_active());
+                    let active_trades = self.get_active_claim_trades(claim.id).await?;
+                    db::archive_claim_by_id(self.db_client.as_ref(), claim.id, ClaimState::Expired).await?;
+                    if let Some(status) = IntentStatus::for_claim(claim, IntentState::Expired, None) {
+                        self.event_sender.send(Event::IntentStatus(status)).await?;
+                    }
                     for trade in active_trades {
                         if let Some(broker_id) = trade.broker_id {
-                            self.cancel_trade(broker_id).await?;
+                            self.cancel_trade(broker_id, &trade.account).await?;
                         }
                     }
                     continue;
                 }
             }
+            if claim.is_backing_off() {
+                debug!(id = %claim.id, "Claim is backing off after risk denial");
+                continue;
+            }
+            let executions = db::get_executions_by_ticker(self.db_client.as_ref(), &claim.ticker).await?;
+            if !executions.is_empty() {
+                debug!(ticker = %claim.ticker, "Claim is being worked by an execution");
+                continue;
+            }
             let active_trade_amount =
-                db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), &claim.ticker).await?;
+                db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), &claim.account, &claim.ticker).await?;
+            let outstanding_requests =
+                db::get_risk_check_requests_by_ticker(self.db_client.as_ref(), &claim.account, &claim.ticker).await?;
 
-            if active_trade_amount.is_zero() {
+            if active_trade_amount.is_zero() && outstanding_requests.is_empty() {
                 debug!("Unfilled claim, sending new trade");
+                let instructions = self.get_order_instructions(claim.intent_id).await?;
                 let maybe_trade = self
-                    .generate_trades(&claim.ticker, &claim.amount, claim.limit_price, None)
+                    .generate_trades(
+                        &claim.account,
+                        &claim.ticker,
+                        &claim.amount,
+                        claim.limit_price,
+                        None,
+                        &instructions,
+                        Some(claim),
+                    )
                     .await?;
-                if let Some(trade_intent) = maybe_trade {
-                    self.event_sender.send(Event::RiskCheckRequest(trade_intent)).await?
+                if let Some(order) = maybe_trade {
+                    self.request_risk_check(order, Some(claim.id)).await?
                 }
             }
         }
         Ok(())
     }
+}
 
-    #[tracing::instrument(skip(self))]
-    async fn reconcile_house_positions(&self) -> Result<()> {
-        let house_positions = db::get_positions_by_owner(self.db_client.as_ref(), &Owner::House).await?;
-        let house_positions = house_positions.iter().filter(|pos| pos.shares != Decimal::ZERO);
-        for position in house_positions {
-            if position.shares.abs() >= Decimal::ONE {
-                let active_trade_amount =
-                    db::get_active_trade_amount_by_ticker(self.db_client.as_ref(), &position.ticker).await?;
-                if active_trade_amount.is_zero() {
-                    debug!(ticker = %position.ticker, shares = %position.shares, "Reducing size of house position");
-
-                    // Since shares are stored with 8 decimal points of precision, adding 1e-9
-                    // guarantees that we will be left with < 1 share in the house position.
-                    let mut shares_to_liquidate = (position.shares.abs() - Decimal::ONE + Decimal::new(1, 9))
-                        .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
-                    shares_to_liquidate.set_sign_positive(position.shares.is_sign_positive());
-                    let maybe_trade = self
-                        .generate_trades(&position.ticker, &Amount::Shares(-shares_to_liquidate), None, None)
-                        .await?;
-                    if let Some(intent) = maybe_trade {
-                        self.event_sender.send(Event::RiskCheckRequest(intent)).await?
-                    }
-                }
-            }
-        }
-        Ok(())
-    }
+/// Fetches an order from the broker API, as a trade update.
+async fn get_broker_order(base_url: &str, broker_id: Uuid) -> Result<Trade> {
+    let url = format!("{}/orders/{}", base_url, broker_id);
+    let order: Order = reqwest::get(url).await?.error_for_status()?.json().await?;
+    Ok(Trade::from(order))
 }
@@ -0,0 +1,59 @@
+use super::OrderManager;
+use crate::db;
+use crate::intent_scheduler::ScheduleCommand;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+impl OrderManager {
+    pub(super) fn schedule_recurring_intent(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        self.scheduler_sender
+            .send(ScheduleCommand::RecurringIntent { id, at })
+            .context("Failed to send recurring intent to scheduler")
+    }
+
+    /// Called when a run of a recurring intent scheduled for `at` is due. Generates an intent from
+    /// its template and schedules its next run.
+    ///
+    /// Newly registered or updated recurring intents have no next run yet, so they are only
+    /// scheduled, and runs that no longer match the stored next run are dropped.
+    #[tracing::instrument(skip(self))]
+    pub async fn run_recurring_intent(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        let recurring_intent = match db::get_recurring_intent(self.db_client.as_ref(), id).await? {
+            Some(recurring_intent) => recurring_intent,
+            None => {
+                debug!("Recurring intent no longer exists");
+                return Ok(());
+            }
+        };
+        let now = Utc::now();
+        let (intent, last_run_at) = match recurring_intent.next_run_at {
+            Some(next_run_at) if next_run_at != at => {
+                debug!(%next_run_at, "Dropping superseded run of recurring intent");
+                return Ok(());
+            }
+            Some(_) => (Some(recurring_intent.instance()), Some(now)),
+            None => (None, recurring_intent.last_run_at),
+        };
+        // The next run is scheduled first, so that a failure to evaluate this run does not stop
+        // the recurrence
+        let next_run_at = recurring_intent
+            .schedule
+            .next_after(&self.calendar, now)
+            .context("Failed to calculate next run")?;
+        db::update_recurring_intent_runs(self.db_client.as_ref(), id, next_run_at, last_run_at).await?;
+        match next_run_at {
+            Some(next_run_at) => self.schedule_recurring_intent(id, next_run_at)?,
+            None => warn!("Recurring intent has no future runs"),
+        }
+        if let Some(intent) = intent {
+            debug!(intent_id = %intent.id, "Running recurring intent");
+            if !recurring_intent.instructions.is_default() {
+                db::save_order_instructions(self.db_client.as_ref(), intent.id, &recurring_intent.instructions).await?;
+            }
+            self.triage_intent(intent).await?;
+        }
+        Ok(())
+    }
+}
@@ -1,16 +1,179 @@
+use super::intents::get_last_price;
+use super::risk_rules::check_trade;
 use super::OrderManager;
-use anyhow::Result;
+use crate::db;
+use crate::event_sender::Event;
+use crate::settings::RiskCheckMode;
+use crate::types::{ClaimState, IntentState, IntentStatus, RiskCheckRequest, TradeOrder};
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
 use risk_manager::RiskCheckResponse;
-use tracing::warn;
+use rust_decimal::prelude::*;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
 
 impl OrderManager {
+    /// Routes a trade through the configured risk checks, sending it to the broker directly if
+    /// no external check is required.
+    #[tracing::instrument(skip(self, order, claim_id), fields(id = %order.intent.id))]
+    pub async fn request_risk_check(&self, order: TradeOrder, claim_id: Option<Uuid>) -> Result<()> {
+        let order = if self.is_market_open() {
+            order
+        } else {
+            match self.hold_trade(order, claim_id).await? {
+                Some(order) => order,
+                None => return Ok(()),
+            }
+        };
+        let intent = &order.intent;
+        let mode = self.settings.risk.mode;
+        if mode != RiskCheckMode::External {
+            let last_price = get_last_price(&self.datastore_url, &intent.ticker).await.ok();
+            let held_shares: Decimal =
+                db::get_positions_by_ticker(self.db_client.as_ref(), &order.account, &intent.ticker)
+                    .await
+                    .context("Failed to get positions")?
+                    .iter()
+                    .map(|pos| pos.shares)
+                    .sum();
+            if let Err(violation) = check_trade(&self.settings.risk, &order, last_price, held_shares) {
+                warn!(%violation, ?intent, "Local risk check denied");
+                return self.deny_claim(claim_id, violation.to_string()).await;
+            }
+        }
+        match mode {
+            RiskCheckMode::Local => {
+                self.link_claim_trade(claim_id, intent.id).await?;
+                self.send_trade(order).await
+            }
+            RiskCheckMode::External | RiskCheckMode::LocalThenExternal => {
+                let intent = intent.clone();
+                db::save_risk_check_request(self.db_client.as_ref(), &RiskCheckRequest::new(order, claim_id))
+                    .await
+                    .context("Failed to save risk check request")?;
+                self.event_sender.send(Event::RiskCheckRequest(intent)).await
+            }
+        }
+    }
+
     pub async fn handle_risk_check_response(&self, response: RiskCheckResponse) -> Result<()> {
         match response {
-            RiskCheckResponse::Granted { intent } => self.send_trade(intent).await,
+            RiskCheckResponse::Granted { intent } => {
+                let maybe_request = db::take_risk_check_request(self.db_client.as_ref(), intent.id)
+                    .await
+                    .context("Failed to take risk check request")?;
+                // A request that was retried can be granted more than once, but only the first
+                // grant sends the trade
+                let request = match maybe_request {
+                    Some(request) => request,
+                    None => {
+                        debug!(id = %intent.id, "Ignoring grant of a risk check request that was already answered");
+                        return Ok(());
+                    }
+                };
+                self.link_claim_trade(request.claim_id, intent.id).await?;
+                // The risk-manager only answers with the intent, so the extensions are taken from
+                // the request. A quantity changed by the risk-manager replaces the requested one.
+                let resized = intent.qty != request.order.intent.qty;
+                let quantity = Decimal::from(intent.qty);
+                let order = TradeOrder {
+                    intent,
+                    ..request.order
+                };
+                let order = if resized { order.with_quantity(quantity)? } else { order };
+                self.send_trade(order).await
+            }
             RiskCheckResponse::Denied { intent, .. } => {
                 warn!(?intent, "RiskCheck Denied");
-                Ok(())
+                let maybe_request = db::take_risk_check_request(self.db_client.as_ref(), intent.id)
+                    .await
+                    .context("Failed to take risk check request")?;
+                let claim_id = maybe_request.and_then(|request| request.claim_id);
+                self.deny_claim(claim_id, "Denied by risk-manager".into()).await
+            }
+        }
+    }
+
+    /// Resends risk check requests that have not been answered in time, and gives up on those
+    /// that have run out of retries.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn expire_risk_check_requests(&self) -> Result<()> {
+        let timeout = Duration::seconds(self.settings.risk.request_timeout_seconds as i64);
+        let requests = db::get_risk_check_requests(self.db_client.as_ref()).await?;
+        for mut request in requests {
+            if Utc::now() - request.sent_at <= timeout {
+                continue;
+            }
+            if request.attempts as usize > self.settings.risk.max_request_retries {
+                error!(id = %request.id, attempts = request.attempts, "Risk check request failed");
+                db::take_risk_check_request(self.db_client.as_ref(), request.id).await?;
+                self.deny_claim(request.claim_id, "Risk check timed out".into()).await?;
+                continue;
+            }
+            debug!(id = %request.id, attempts = request.attempts, "Retrying risk check request");
+            request.attempts += 1;
+            request.sent_at = Utc::now();
+            db::save_risk_check_request(self.db_client.as_ref(), &request).await?;
+            self.event_sender
+                .send(Event::RiskCheckRequest(request.order.intent))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Records that a trade is about to be sent for a claim.
+    #[tracing::instrument(skip(self, claim_id, trade_id))]
+    pub(super) async fn link_claim_trade(&self, claim_id: Option<Uuid>, trade_id: Uuid) -> Result<()> {
+        if let Some(claim_id) = claim_id {
+            db::save_claim_trade(self.db_client.as_ref(), claim_id, trade_id)
+                .await
+                .context("Failed to save claim trade")?;
+        }
+        Ok(())
+    }
+
+    /// Marks the claim that caused a denied trade so that it is backed off, cancelling it once it
+    /// has been denied too many times.
+    #[tracing::instrument(skip(self, claim_id, reason))]
+    async fn deny_claim(&self, claim_id: Option<Uuid>, reason: String) -> Result<()> {
+        let claim_id = match claim_id {
+            Some(claim_id) => claim_id,
+            None => return Ok(()),
+        };
+        let maybe_claim = db::find_claim_by_id(self.db_client.as_ref(), claim_id)
+            .await
+            .context("Failed to get claim")?;
+        let mut claim = match maybe_claim {
+            Some(claim) => claim,
+            None => {
+                debug!(%claim_id, "Denied claim no longer exists");
+                return Ok(());
+            }
+        };
+        claim.record_denial(
+            reason.clone(),
+            Duration::seconds(self.settings.risk.denial_backoff_seconds as i64),
+        );
+        if let Some(status) = IntentStatus::for_claim(&claim, IntentState::RiskDenied, Some(reason)) {
+            self.event_sender.send(Event::IntentStatus(status)).await?;
+        }
+        if claim.denial_count as usize >= self.settings.risk.max_denials {
+            warn!(%claim_id, denial_count = claim.denial_count, "Cancelling repeatedly denied claim");
+            claim.retry_after = None;
+            claim.state = ClaimState::Cancelled;
+            db::archive_claim_by_id(self.db_client.as_ref(), claim_id, claim.state)
+                .await
+                .context("Failed to archive claim")?;
+            let reason = format!("Cancelled after {} risk denials", claim.denial_count);
+            if let Some(status) = IntentStatus::for_claim(&claim, IntentState::Cancelled, Some(reason)) {
+                self.event_sender.send(Event::IntentStatus(status)).await?;
             }
+        } else {
+            debug!(%claim_id, denial_count = claim.denial_count, "Backing off denied claim");
+            db::update_claim_denial(self.db_client.as_ref(), &claim)
+                .await
+                .context("Failed to update claim denial")?;
         }
+        self.event_sender.send(Event::Claim(claim)).await
     }
 }
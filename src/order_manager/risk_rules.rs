@@ -0,0 +1,173 @@
+use crate::settings::RiskSettings;
+use crate::types::TradeOrder;
+use rust_decimal::prelude::*;
+use std::fmt::{Display, Error, Formatter};
+use trading_base::OrderType;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RiskViolation {
+    RestrictedTicker,
+    MissingPrice,
+    OrderNotional { limit: Decimal, notional: Decimal },
+    OrderShares { limit: Decimal, shares: Decimal },
+    PriceCollar { price: Decimal, last_price: Decimal },
+    FatFinger { shares: Decimal, held_shares: Decimal },
+}
+
+impl Display for RiskViolation {
+    fn fmt(&self, formatter: &mut Formatter) -> std::result::Result<(), Error> {
+        match self {
+            RiskViolation::RestrictedTicker => formatter.write_str("Ticker is restricted"),
+            RiskViolation::MissingPrice => formatter.write_str("Missing price for risk check"),
+            RiskViolation::OrderNotional { limit, notional } => {
+                write!(formatter, "Order notional of {} exceeds limit of {}", notional, limit)
+            }
+            RiskViolation::OrderShares { limit, shares } => {
+                write!(formatter, "Order size of {} shares exceeds limit of {}", shares, limit)
+            }
+            RiskViolation::PriceCollar { price, last_price } => write!(
+                formatter,
+                "Order price of {} is outside of the collar around last price {}",
+                price, last_price
+            ),
+            RiskViolation::FatFinger { shares, held_shares } => write!(
+                formatter,
+                "Order size of {} shares is out of proportion to the {} shares held",
+                shares, held_shares
+            ),
+        }
+    }
+}
+
+fn order_prices(order_type: &OrderType) -> Vec<Decimal> {
+    match *order_type {
+        OrderType::Market => vec![],
+        OrderType::Limit { limit_price } => vec![limit_price],
+        OrderType::Stop { stop_price } => vec![stop_price],
+        OrderType::StopLimit {
+            limit_price,
+            stop_price,
+        } => vec![limit_price, stop_price],
+    }
+}
+
+/// Runs the locally configured pre-trade checks against a trade.
+///
+/// `held_shares` is the total number of shares of the ticker currently held across all owners.
+pub fn check_trade(
+    settings: &RiskSettings,
+    order: &TradeOrder,
+    last_price: Option<Decimal>,
+    held_shares: Decimal,
+) -> Result<(), RiskViolation> {
+    let intent = &order.intent;
+    if settings.is_restricted(&intent.ticker) {
+        return Err(RiskViolation::RestrictedTicker);
+    }
+    let shares = order.quantity().abs();
+    if let Some(limit) = settings.max_order_shares {
+        if shares > limit {
+            return Err(RiskViolation::OrderShares { limit, shares });
+        }
+    }
+    if let Some(multiple) = settings.fat_finger_multiple {
+        if !held_shares.is_zero() && shares > held_shares.abs() * multiple {
+            return Err(RiskViolation::FatFinger { shares, held_shares });
+        }
+    }
+    let prices = order_prices(&intent.order_type);
+    if let Some(collar) = settings.price_collar {
+        let last_price = last_price.ok_or(RiskViolation::MissingPrice)?;
+        for price in prices.iter() {
+            if ((price - last_price) / last_price).abs() > collar {
+                return Err(RiskViolation::PriceCollar {
+                    price: *price,
+                    last_price,
+                });
+            }
+        }
+    }
+    if let Some(limit) = settings.max_order_notional {
+        // Orders are valued at the least favourable of their limit/stop prices and the last price
+        let price = prices
+            .into_iter()
+            .chain(last_price)
+            .max()
+            .ok_or(RiskViolation::MissingPrice)?;
+        let notional = shares * price;
+        if notional > limit {
+            return Err(RiskViolation::OrderNotional { limit, notional });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trading_base::TradeIntent;
+
+    fn settings() -> RiskSettings {
+        RiskSettings {
+            max_order_notional: Some(Decimal::new(10000, 0)),
+            max_order_shares: Some(Decimal::new(500, 0)),
+            price_collar: Some(Decimal::new(5, 2)),
+            fat_finger_multiple: Some(Decimal::TEN),
+            restricted_tickers: "GME, AMC".into(),
+            ..RiskSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_check_trade() {
+        let settings = settings();
+        let price = Some(Decimal::ONE_HUNDRED);
+
+        let okay = TradeOrder::new(TradeIntent::new("AAPL", 50));
+        assert_eq!(check_trade(&settings, &okay, price, Decimal::ZERO), Ok(()));
+
+        let restricted = TradeOrder::new(TradeIntent::new("amc", 1));
+        assert_eq!(
+            check_trade(&settings, &restricted, price, Decimal::ZERO),
+            Err(RiskViolation::RestrictedTicker)
+        );
+
+        let too_many_shares = TradeOrder::new(TradeIntent::new("AAPL", -501));
+        assert!(matches!(
+            check_trade(&settings, &too_many_shares, Some(Decimal::ONE), Decimal::ZERO),
+            Err(RiskViolation::OrderShares { .. })
+        ));
+
+        let too_much_notional = TradeOrder::new(TradeIntent::new("AAPL", 101));
+        assert!(matches!(
+            check_trade(&settings, &too_much_notional, price, Decimal::ZERO),
+            Err(RiskViolation::OrderNotional { .. })
+        ));
+
+        let fat_finger = TradeOrder::new(TradeIntent::new("AAPL", 11));
+        assert!(matches!(
+            check_trade(&settings, &fat_finger, price, Decimal::ONE),
+            Err(RiskViolation::FatFinger { .. })
+        ));
+
+        let outside_collar = TradeOrder::new(TradeIntent::new("AAPL", 1).order_type(OrderType::Limit {
+            limit_price: Decimal::new(106, 0),
+        }));
+        assert!(matches!(
+            check_trade(&settings, &outside_collar, price, Decimal::ZERO),
+            Err(RiskViolation::PriceCollar { .. })
+        ));
+
+        let missing_price = TradeOrder::new(TradeIntent::new("AAPL", 1));
+        assert_eq!(
+            check_trade(&settings, &missing_price, None, Decimal::ZERO),
+            Err(RiskViolation::MissingPrice)
+        );
+
+        // Fractional orders are checked at their exact quantity
+        let fractional = TradeOrder::new(TradeIntent::new("AAPL", 0))
+            .with_quantity(Decimal::new(5, 1))
+            .unwrap();
+        assert_eq!(check_trade(&settings, &fractional, price, Decimal::new(5, 2)), Ok(()));
+    }
+}
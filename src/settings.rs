@@ -1,10 +1,300 @@
+use crate::types::HousePolicy;
 use config::{Config, ConfigError, Environment};
 use kafka_settings::KafkaSettings;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskCheckMode {
+    /// Every trade is sent to the external risk-manager
+    External,
+    /// Trades are only checked against the local rules
+    Local,
+    /// Trades are checked against the local rules before being sent to the external risk-manager
+    LocalThenExternal,
+}
+
+impl Default for RiskCheckMode {
+    fn default() -> Self {
+        RiskCheckMode::External
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RiskSettings {
+    #[serde(default)]
+    pub mode: RiskCheckMode,
+    pub max_order_notional: Option<Decimal>,
+    pub max_order_shares: Option<Decimal>,
+    /// Maximum relative distance of limit and stop prices from the last price
+    pub price_collar: Option<Decimal>,
+    /// Maximum size of an order relative to the total number of shares currently held
+    pub fat_finger_multiple: Option<Decimal>,
+    /// Comma-separated list of tickers that may not be traded
+    #[serde(default)]
+    pub restricted_tickers: String,
+    #[serde(default = "default_risk_check_timeout_seconds")]
+    pub request_timeout_seconds: usize,
+    #[serde(default = "default_risk_check_retries")]
+    pub max_request_retries: usize,
+    /// Initial back-off before a denied claim is retried, doubling with every further denial
+    #[serde(default = "default_denial_backoff_seconds")]
+    pub denial_backoff_seconds: usize,
+    /// Number of denials after which a claim is cancelled
+    #[serde(default = "default_max_denials")]
+    pub max_denials: usize,
+}
+
+impl RiskSettings {
+    pub fn is_restricted(&self, ticker: &str) -> bool {
+        self.restricted_tickers
+            .split(',')
+            .any(|restricted| restricted.trim().eq_ignore_ascii_case(ticker))
+    }
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        Self {
+            mode: RiskCheckMode::default(),
+            max_order_notional: None,
+            max_order_shares: None,
+            price_collar: None,
+            fat_finger_multiple: None,
+            restricted_tickers: String::new(),
+            request_timeout_seconds: default_risk_check_timeout_seconds(),
+            max_request_retries: default_risk_check_retries(),
+            denial_backoff_seconds: default_denial_backoff_seconds(),
+            max_denials: default_max_denials(),
+        }
+    }
+}
+
+fn default_risk_check_timeout_seconds() -> usize {
+    60
+}
+
+fn default_risk_check_retries() -> usize {
+    3
+}
+
+fn default_denial_backoff_seconds() -> usize {
+    60
+}
+
+fn default_max_denials() -> usize {
+    5
+}
+
+/// Commissions and regulatory charges applied to fills. All rates default to zero.
+#[derive(Debug, Default, Deserialize)]
+pub struct FeeSettings {
+    #[serde(default)]
+    pub commission_per_share: Decimal,
+    #[serde(default)]
+    pub minimum_commission: Decimal,
+    /// SEC fee per dollar of sale proceeds
+    #[serde(default)]
+    pub sec_fee_rate: Decimal,
+    /// FINRA trading activity fee per share sold
+    #[serde(default)]
+    pub finra_taf_per_share: Decimal,
+    pub finra_taf_maximum: Option<Decimal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarSettings {
+    /// JSON file of exchange holidays and early closes
+    pub path: Option<String>,
+    /// Whether market orders are rejected and execution slices held outside of market hours
+    #[serde(default = "default_enforce_market_hours")]
+    pub enforce_market_hours: bool,
+    /// Whether market and limit orders generated while the market is closed are sent for the
+    /// opening auction instead of being queued until the open
+    #[serde(default)]
+    pub convert_to_opening_auction: bool,
+    /// Whether limit day orders generated during the pre- or post-market session are sent as
+    /// extended hours orders instead of being queued until the open
+    #[serde(default)]
+    pub convert_to_extended_hours: bool,
+    /// Interval at which reconciliation runs during market hours
+    #[serde(default = "default_reconcile_interval_seconds")]
+    pub reconcile_interval_seconds: usize,
+}
+
+impl Default for CalendarSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            enforce_market_hours: default_enforce_market_hours(),
+            convert_to_opening_auction: false,
+            convert_to_extended_hours: false,
+            reconcile_interval_seconds: default_reconcile_interval_seconds(),
+        }
+    }
+}
+
+fn default_enforce_market_hours() -> bool {
+    true
+}
+
+fn default_reconcile_interval_seconds() -> usize {
+    60
+}
+
+/// What reconciliation does with a trade that has not had an update from the broker for a while.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleTradeAction {
+    /// The trade is left alone
+    Ignore,
+    /// A cancel is sent to the broker, and the trade is marked dead if that goes unanswered too
+    Cancel,
+    /// The trade is marked dead straight away
+    MarkDead,
+}
+
+impl Default for StaleTradeAction {
+    fn default() -> Self {
+        StaleTradeAction::Ignore
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StaleTradePolicy {
+    #[serde(default)]
+    pub action: StaleTradeAction,
+    /// Time without broker updates after which a trade is stale. Working GTC orders, such as
+    /// bracket legs, also go without updates, so this should be longer than they are expected
+    /// to work
+    #[serde(default = "default_stale_after_seconds")]
+    pub after_seconds: usize,
+}
+
+impl Default for StaleTradePolicy {
+    fn default() -> Self {
+        Self {
+            action: StaleTradeAction::default(),
+            after_seconds: default_stale_after_seconds(),
+        }
+    }
+}
+
+fn default_stale_after_seconds() -> usize {
+    86400
+}
+
+/// Staleness policies for trades that the broker accepted but never reported as done.
+#[derive(Debug, Default, Deserialize)]
+pub struct StaleTradeSettings {
+    #[serde(default)]
+    pub accepted: StaleTradePolicy,
+    #[serde(default)]
+    pub partially_filled: StaleTradePolicy,
+    /// Base URL of a broker API that reports orders in the Alpaca format at
+    /// `/orders/{broker_id}`, asked for the status of a stale trade before it is marked dead
+    pub broker_url: Option<String>,
+}
+
+/// Handling of the shares the house is left holding when fills exceed the claims on a ticker.
+#[derive(Debug, Deserialize)]
+pub struct HouseSettings {
+    #[serde(default)]
+    pub policy: HousePolicy,
+    /// Error account that house positions are reassigned to by the `error_account` policy
+    #[serde(default = "default_error_account")]
+    pub error_account: String,
+    /// Minimum number of shares held by the house before the policy is applied
+    #[serde(default = "default_house_min_shares")]
+    pub min_shares: Decimal,
+    /// Minimum notional held by the house before the policy is applied, at the last price
+    pub min_notional: Option<Decimal>,
+    /// Whether actions are held until an operator approves them through the API
+    #[serde(default)]
+    pub require_approval: bool,
+}
+
+impl HouseSettings {
+    /// Whether a house position is large enough for the policy to apply to it. The notional
+    /// threshold can only be met if the last price is known.
+    pub fn is_actionable(&self, shares: Decimal, last_price: Option<Decimal>) -> bool {
+        if shares.abs() < self.min_shares {
+            return false;
+        }
+        match (self.min_notional, last_price) {
+            (Some(min_notional), Some(price)) => (shares * price).abs() >= min_notional,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+impl Default for HouseSettings {
+    fn default() -> Self {
+        Self {
+            policy: HousePolicy::default(),
+            error_account: default_error_account(),
+            min_shares: default_house_min_shares(),
+            min_notional: None,
+            require_approval: false,
+        }
+    }
+}
+
+fn default_error_account() -> String {
+    "errors".into()
+}
+
+fn default_house_min_shares() -> Decimal {
+    Decimal::ONE
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AppSettings {
     pub unreported_trade_expiry_seconds: usize,
+    #[serde(default)]
+    pub risk: RiskSettings,
+    #[serde(default)]
+    pub fees: FeeSettings,
+    #[serde(default)]
+    pub calendar: CalendarSettings,
+    #[serde(default)]
+    pub stale_trades: StaleTradeSettings,
+    #[serde(default)]
+    pub house: HouseSettings,
+    /// Comma-separated list of tickers that are traded in fractional shares, or `*` for all
+    #[serde(default)]
+    pub fractional_tickers: String,
+    /// Whether lots of tickers that are not traded in fractional shares are allocated in whole
+    /// shares. Off by default, in which case claims are allocated fractional shares and the house
+    /// keeps the rounding remainder.
+    #[serde(default)]
+    pub whole_share_allocation: bool,
+    /// Comma-separated list of broker accounts that accept notional orders, or `*` for all. Dollar
+    /// claims on fractional tickers in these accounts are sent as notional orders.
+    #[serde(default)]
+    pub notional_accounts: String,
+}
+
+impl AppSettings {
+    pub fn is_fractional(&self, ticker: &str) -> bool {
+        list_contains(&self.fractional_tickers, ticker)
+    }
+
+    pub fn allocates_whole_shares(&self, ticker: &str) -> bool {
+        self.whole_share_allocation && !self.is_fractional(ticker)
+    }
+
+    pub fn accepts_notional(&self, account: &str) -> bool {
+        list_contains(&self.notional_accounts, account)
+    }
+}
+
+fn list_contains(list: &str, value: &str) -> bool {
+    list.split(',')
+        .map(str::trim)
+        .any(|item| item == "*" || item.eq_ignore_ascii_case(value))
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,3 +336,28 @@ impl Settings {
         s.try_into()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_house_thresholds() {
+        let settings = HouseSettings {
+            min_shares: Decimal::new(5, 0),
+            min_notional: Some(Decimal::new(1000, 0)),
+            ..Default::default()
+        };
+        let price = Some(Decimal::new(300, 0));
+        assert!(!settings.is_actionable(Decimal::new(4, 0), price));
+        assert!(settings.is_actionable(Decimal::new(5, 0), price));
+        assert!(settings.is_actionable(Decimal::new(-5, 0), price));
+        // Enough shares, but not enough notional
+        assert!(!settings.is_actionable(Decimal::new(5, 0), Some(Decimal::new(100, 0))));
+        // The notional threshold can't be met without a price
+        assert!(!settings.is_actionable(Decimal::new(5, 0), None));
+        let settings = HouseSettings::default();
+        assert!(settings.is_actionable(Decimal::ONE, None));
+        assert!(!settings.is_actionable(Decimal::new(5, 1), None));
+    }
+}
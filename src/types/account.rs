@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+
+/// The broker account that trades are sent to unless their strategy is routed elsewhere. Its
+/// trades are sent to the `trade-intents` topic.
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+pub fn default_account() -> String {
+    DEFAULT_ACCOUNT.to_string()
+}
+
+/// The Kafka topic that the broker connection of an account consumes trade messages from.
+pub fn trade_topic(account: &str) -> String {
+    if account == DEFAULT_ACCOUNT {
+        "trade-intents".to_string()
+    } else {
+        format!("trade-intents-{}", account)
+    }
+}
+
+/// Per-strategy configuration of the broker account that claims are traded in.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AccountRoute {
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub account: String,
+}
+
+impl TryFrom<Row> for AccountRoute {
+    type Error = tokio_postgres::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            strategy: row.try_get("strategy")?,
+            sub_strategy: row.try_get("sub_strategy")?,
+            account: row.try_get("account")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_trade_topic() {
+        assert_eq!(trade_topic(DEFAULT_ACCOUNT), "trade-intents");
+        assert_eq!(trade_topic("paper"), "trade-intents-paper");
+    }
+}
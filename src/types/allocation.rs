@@ -1,5 +1,5 @@
-use super::{Claim, Lot, Owner};
-use rust_decimal::Decimal;
+use super::{default_account, Claim, Lot, Owner};
+use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use tokio_postgres::Row;
@@ -15,7 +15,13 @@ pub struct Allocation {
     pub lot_id: Uuid,
     pub ticker: String,
     pub shares: Decimal,
+    /// Cost of the shares including `fees`
     pub basis: Decimal,
+    #[serde(default)]
+    pub fees: Decimal,
+    /// The broker account the allocated shares are held in
+    #[serde(default = "default_account")]
+    pub account: String,
 }
 
 impl Allocation {
@@ -37,27 +43,42 @@ impl Allocation {
             ticker,
             shares,
             basis,
+            fees: Decimal::ZERO,
+            account: default_account(),
         }
     }
+
+    /// Adds the share of the fees of the lot to the allocation, including them in its basis.
+    pub fn with_fees(mut self, fees: Decimal) -> Self {
+        self.basis += fees;
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_account(mut self, account: String) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Cost of the shares excluding fees
+    pub fn gross_basis(&self) -> Decimal {
+        self.basis - self.fees
+    }
 }
 
 impl TryFrom<Row> for Allocation {
     type Error = tokio_postgres::Error;
     fn try_from(row: Row) -> Result<Self, Self::Error> {
-        let owner = row.try_get("owner")?;
-        let owner = if owner == "House" {
-            Owner::House
-        } else {
-            Owner::Strategy(owner, row.try_get("sub_owner")?)
-        };
         Ok(Allocation {
             id: row.try_get("id")?,
-            owner,
+            owner: row.try_get("owner")?,
             claim_id: row.try_get("claim_id")?,
             lot_id: row.try_get("lot_id")?,
             ticker: row.try_get("ticker")?,
             shares: row.try_get("shares")?,
             basis: row.try_get("basis")?,
+            fees: row.try_get("fees")?,
+            account: row.try_get("account")?,
         })
     }
 }
@@ -66,7 +87,7 @@ fn should_allocate(lot: &Lot, claim: &Claim) -> bool {
     if claim.amount.is_zero() {
         return false;
     }
-    if claim.ticker != lot.ticker {
+    if claim.ticker != lot.ticker || claim.account != lot.account {
         return false;
     }
     if claim.amount.is_sign_positive() && lot.shares.is_sign_negative() {
@@ -86,11 +107,30 @@ fn should_allocate(lot: &Lot, claim: &Claim) -> bool {
     true
 }
 
+/// Allocates a whole lot to a single owner, for fills that belong to one strategy regardless of
+/// the claims on the ticker.
+pub fn allocate_lot(owner: Owner, lot: &Lot) -> Allocation {
+    Allocation::new(
+        owner,
+        None,
+        lot.id,
+        lot.ticker.clone(),
+        lot.shares,
+        lot.shares * lot.price,
+    )
+    .with_fees(lot.fees)
+    .with_account(lot.account.clone())
+}
+
+/// Splits a lot between the claims on its ticker in its account, allocating any remainder to the
+/// house in that account. If the ticker is only traded in `whole_shares`, claims are allocated
+/// whole shares, since the trades sent for them were rounded to whole shares.
 #[tracing::instrument(skip(claims, lot))]
-pub fn split_lot(claims: &[Claim], lot: &Lot) -> Vec<Allocation> {
+pub fn split_lot(claims: &[Claim], lot: &Lot, whole_shares: bool) -> Vec<Allocation> {
     let mut remaining_shares = lot.shares;
     let mut remaining_basis = lot.shares * lot.price;
-    let mut out = Vec::new();
+    let mut remaining_fees = lot.fees;
+    let mut allocated = Vec::new();
     for claim in claims {
         if !should_allocate(lot, claim) {
             continue;
@@ -113,36 +153,110 @@ pub fn split_lot(claims: &[Claim], lot: &Lot) -> Vec<Allocation> {
             }
             Amount::Zero => (Decimal::ZERO, Decimal::ZERO),
         };
-        out.push(Allocation::new(
-            Owner::Strategy(claim.strategy.clone(), claim.sub_strategy.clone()),
-            Some(claim.id),
-            lot.id,
-            lot.ticker.clone(),
-            shares,
-            basis,
-        ));
+        allocated.push((claim, shares, basis));
         remaining_shares -= shares;
         remaining_basis -= basis;
     }
+    if whole_shares {
+        let shares: Vec<Decimal> = allocated.iter().map(|(_, shares, _)| *shares).collect();
+        let rounded = round_to_whole_shares(&shares, lot.shares);
+        remaining_shares = lot.shares - rounded.iter().copied().sum::<Decimal>();
+        remaining_basis = remaining_shares * lot.price;
+        for ((_, shares, basis), rounded) in allocated.iter_mut().zip(rounded) {
+            *shares = rounded;
+            *basis = rounded * lot.price;
+        }
+    }
+    let mut out = Vec::new();
+    for (claim, shares, basis) in allocated {
+        // Claims rounded down to nothing are left for the next lot
+        if whole_shares && shares.is_zero() {
+            continue;
+        }
+        let fees = if lot.shares.is_zero() {
+            Decimal::ZERO
+        } else {
+            (lot.fees * shares / lot.shares).round_dp(8)
+        };
+        out.push(
+            Allocation::new(
+                Owner::Strategy(claim.strategy.clone(), claim.sub_strategy.clone()),
+                Some(claim.id),
+                lot.id,
+                lot.ticker.clone(),
+                shares,
+                basis,
+            )
+            .with_fees(fees)
+            .with_account(lot.account.clone()),
+        );
+        remaining_fees -= fees;
+    }
     if remaining_shares.ne(&Decimal::ZERO) {
-        out.push(Allocation::new(
-            Owner::House,
-            None,
-            lot.id,
-            lot.ticker.clone(),
-            remaining_shares,
-            remaining_basis,
-        ));
+        out.push(
+            Allocation::new(
+                Owner::House,
+                None,
+                lot.id,
+                lot.ticker.clone(),
+                remaining_shares,
+                remaining_basis,
+            )
+            .with_fees(remaining_fees)
+            .with_account(lot.account.clone()),
+        );
     }
 
     out
 }
 
+/// Rounds the fractional allocations of a lot to whole shares without exceeding the whole shares
+/// of the lot. Every allocation is rounded towards zero, and the shares left over are handed out
+/// one at a time to the allocations with the largest fractional remainders, so that rounding one
+/// claim up never starves the claims after it.
+fn round_to_whole_shares(shares: &[Decimal], lot_shares: Decimal) -> Vec<Decimal> {
+    let mut rounded: Vec<Decimal> = shares.iter().map(|shares| shares.trunc()).collect();
+    let rounded_total: Decimal = rounded.iter().map(|shares| shares.abs()).sum();
+    let mut leftover = lot_shares.abs().trunc() - rounded_total;
+    let mut by_remainder: Vec<usize> = (0..shares.len()).filter(|i| !shares[*i].fract().is_zero()).collect();
+    // The sort is stable, so ties go to the claims that come first
+    by_remainder.sort_by(|a, b| shares[*b].fract().abs().cmp(&shares[*a].fract().abs()));
+    for i in by_remainder {
+        if leftover < Decimal::ONE {
+            break;
+        }
+        rounded[i] += shares[i].signum();
+        leftover -= Decimal::ONE;
+    }
+    rounded
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::DEFAULT_ACCOUNT;
     use chrono::Utc;
 
+    #[test]
+    fn test_allocate_lot() {
+        let lot = Lot::new(
+            Uuid::new_v4(),
+            "AAPL".into(),
+            Utc::now(),
+            Decimal::new(100, 0),
+            Decimal::new(-10, 0),
+        )
+        .with_fees(Decimal::ONE);
+        let owner = Owner::Strategy("A".into(), Some("A1".into()));
+        let allocation = allocate_lot(owner.clone(), &lot);
+        assert_eq!(allocation.owner, owner);
+        assert_eq!(allocation.claim_id, None);
+        assert_eq!(allocation.shares, Decimal::new(-10, 0));
+        assert_eq!(allocation.basis, Decimal::new(-999, 0));
+        assert_eq!(allocation.fees, Decimal::ONE);
+        assert_eq!(allocation.account, DEFAULT_ACCOUNT);
+    }
+
     #[test]
     fn test_should_allocate() {
         let lot = Lot::new(
@@ -198,6 +312,17 @@ mod test {
         let wrong_ticker_claim = Claim::new("A".into(), None, "AAP".into(), Amount::Shares(Decimal::ONE), None, None);
         assert!(!should_allocate(&lot, &wrong_ticker_claim));
 
+        let mut wrong_account_claim = Claim::new(
+            "A".into(),
+            None,
+            "AAPL".into(),
+            Amount::Shares(Decimal::ONE),
+            None,
+            None,
+        );
+        wrong_account_claim.set_account("paper".into());
+        assert!(!should_allocate(&lot, &wrong_account_claim));
+
         let okay_claim = Claim::new(
             "A".into(),
             None,
@@ -244,7 +369,7 @@ mod test {
                 None,
             ),
         ];
-        let allocations = split_lot(&claims, &lot);
+        let allocations = split_lot(&claims, &lot, false);
         assert_eq!(allocations.len(), 3);
         assert_eq!(
             allocations[0],
@@ -255,7 +380,9 @@ mod test {
                 lot_id: lot.id,
                 ticker: "AAPL".into(),
                 shares: Decimal::new(4, 0),
-                basis: Decimal::new(400, 0)
+                basis: Decimal::new(400, 0),
+                fees: Decimal::ZERO,
+                account: DEFAULT_ACCOUNT.into()
             }
         );
         assert_eq!(
@@ -267,7 +394,9 @@ mod test {
                 lot_id: lot.id,
                 ticker: "AAPL".into(),
                 shares: Decimal::new(25, 1),
-                basis: Decimal::new(250, 0)
+                basis: Decimal::new(250, 0),
+                fees: Decimal::ZERO,
+                account: DEFAULT_ACCOUNT.into()
             }
         );
         assert_eq!(
@@ -279,8 +408,121 @@ mod test {
                 lot_id: lot.id,
                 ticker: "AAPL".into(),
                 shares: Decimal::new(35, 1),
-                basis: Decimal::new(350, 0)
+                basis: Decimal::new(350, 0),
+                fees: Decimal::ZERO,
+                account: DEFAULT_ACCOUNT.into()
             }
         );
     }
+
+    #[test]
+    fn test_split_lot_fees() {
+        let lot = Lot::new(
+            Uuid::new_v4(),
+            "AAPL".into(),
+            Utc::now(),
+            Decimal::new(100, 0),
+            Decimal::new(10, 0),
+        )
+        .with_fees(Decimal::new(2, 0));
+        let claims = vec![Claim::new(
+            "A".into(),
+            None,
+            "AAPL".into(),
+            Amount::Shares(Decimal::new(4, 0)),
+            None,
+            None,
+        )];
+        let allocations = split_lot(&claims, &lot, false);
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[0].fees, Decimal::new(8, 1));
+        assert_eq!(allocations[0].basis, Decimal::new(4008, 1));
+        assert_eq!(allocations[0].gross_basis(), Decimal::new(400, 0));
+        assert_eq!(allocations[1].fees, Decimal::new(12, 1));
+        assert_eq!(allocations[1].basis, Decimal::new(6012, 1));
+        let total_basis: Decimal = allocations.iter().map(|allocation| allocation.basis).sum();
+        assert_eq!(total_basis, lot.basis());
+    }
+
+    #[test]
+    fn test_split_lot_whole_shares() {
+        let lot = Lot::new(
+            Uuid::new_v4(),
+            "AAPL".into(),
+            Utc::now(),
+            Decimal::new(30, 0),
+            Decimal::new(34, 0),
+        );
+        let claims = vec![Claim::new(
+            "A".into(),
+            None,
+            "AAPL".into(),
+            Amount::Dollars(Decimal::new(1000, 0)),
+            None,
+            None,
+        )];
+        // Fractional allocation leaves the house holding the rounding dust
+        let allocations = split_lot(&claims, &lot, false);
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[1].owner, Owner::House);
+        // Whole share allocation gives the claim the whole trade that was sent for it
+        let allocations = split_lot(&claims, &lot, true);
+        assert_eq!(allocations.len(), 1);
+        assert_eq!(allocations[0].shares, Decimal::new(34, 0));
+        assert_eq!(allocations[0].basis, Decimal::new(1020, 0));
+    }
+
+    #[test]
+    fn test_split_lot_whole_shares_does_not_starve_claims() {
+        let lot = Lot::new(
+            Uuid::new_v4(),
+            "AAPL".into(),
+            Utc::now(),
+            Decimal::new(10, 0),
+            Decimal::new(3, 0),
+        );
+        let claims = vec![
+            Claim::new(
+                "A".into(),
+                None,
+                "AAPL".into(),
+                Amount::Shares(Decimal::new(23, 1)),
+                None,
+                None,
+            ),
+            Claim::new(
+                "B".into(),
+                None,
+                "AAPL".into(),
+                Amount::Shares(Decimal::new(7, 1)),
+                None,
+                None,
+            ),
+        ];
+        let allocations = split_lot(&claims, &lot, true);
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[0].shares, Decimal::new(2, 0));
+        assert_eq!(allocations[1].owner, Owner::Strategy("B".into(), None));
+        assert_eq!(allocations[1].shares, Decimal::ONE);
+    }
+
+    #[test]
+    fn test_round_to_whole_shares() {
+        let shares = [Decimal::new(23, 1), Decimal::new(7, 1)];
+        assert_eq!(
+            round_to_whole_shares(&shares, Decimal::new(3, 0)),
+            vec![Decimal::new(2, 0), Decimal::ONE]
+        );
+        // Leftover shares that the claims can't take stay with the house
+        let shares = [Decimal::new(15, 1), Decimal::new(5, 1)];
+        assert_eq!(
+            round_to_whole_shares(&shares, Decimal::new(4, 0)),
+            vec![Decimal::new(2, 0), Decimal::ONE]
+        );
+        let shares = [Decimal::new(-15, 1), Decimal::new(-15, 1)];
+        assert_eq!(
+            round_to_whole_shares(&shares, Decimal::new(-3, 0)),
+            vec![Decimal::new(-2, 0), -Decimal::ONE]
+        );
+    }
 }
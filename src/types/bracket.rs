@@ -0,0 +1,198 @@
+use super::TradeOrder;
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use tracing::trace;
+use trading_base::{OrderType, TradeIntent};
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BracketLegKind {
+    TakeProfit,
+    StopLoss,
+}
+
+/// A protective child order of a parent trade. The legs of a parent are one-cancels-other: once
+/// one of them fills, the others are cancelled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BracketLeg {
+    pub id: Uuid,
+    pub parent_id: Uuid,
+    /// The claim the bracket protects. A claim has at most one bracket waiting for its parent.
+    #[serde(default)]
+    pub claim_id: Option<Uuid>,
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub ticker: String,
+    pub kind: BracketLegKind,
+    pub price: Decimal,
+    /// Quantity the leg is resent with once its cancellation has been confirmed
+    pub resize_to: Option<Decimal>,
+}
+
+impl BracketLeg {
+    #[tracing::instrument(skip(strategy, sub_strategy, ticker, kind, price))]
+    pub fn new(
+        parent_id: Uuid,
+        strategy: String,
+        sub_strategy: Option<String>,
+        ticker: String,
+        kind: BracketLegKind,
+        price: Decimal,
+    ) -> Self {
+        trace!(%strategy, ?sub_strategy, %ticker, ?kind, %price, "New BracketLeg");
+        Self {
+            id: Uuid::new_v4(),
+            parent_id,
+            claim_id: None,
+            strategy,
+            sub_strategy,
+            ticker,
+            kind,
+            price,
+            resize_to: None,
+        }
+    }
+
+    pub fn with_claim(mut self, claim_id: Uuid) -> Self {
+        self.claim_id = Some(claim_id);
+        self
+    }
+
+    /// A copy of this leg under a new id, used to resend it with a different quantity.
+    pub fn replacement(&self) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            resize_to: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn trade(&self, quantity: Decimal) -> Result<TradeOrder> {
+        let order_type = match self.kind {
+            BracketLegKind::TakeProfit => OrderType::Limit {
+                limit_price: self.price,
+            },
+            BracketLegKind::StopLoss => OrderType::Stop { stop_price: self.price },
+        };
+        let mut trade = TradeIntent::new(&self.ticker, 0).order_type(order_type);
+        trade.id = self.id;
+        TradeOrder::new(trade).with_quantity(quantity)
+    }
+}
+
+/// The quantity of the legs protecting a position of `shares`. Outside of tickers traded in
+/// fractions legs are only sent for whole shares, so `None` is returned if the position is less
+/// than a share.
+pub fn leg_quantity(shares: Decimal, fractional: bool) -> Option<Decimal> {
+    let shares = if fractional { shares } else { shares.trunc() };
+    Some(-shares).filter(|quantity| !quantity.is_zero())
+}
+
+/// Checks that the take-profit and stop-loss levels are on the correct sides of each other for a
+/// parent trade of the given quantity.
+pub fn validate_bracket(quantity: Decimal, take_profit: Option<Decimal>, stop_loss: Option<Decimal>) -> Result<()> {
+    if let (Some(take_profit), Some(stop_loss)) = (take_profit, stop_loss) {
+        let valid = if quantity.is_sign_positive() {
+            take_profit > stop_loss
+        } else {
+            take_profit < stop_loss
+        };
+        if !valid {
+            return Err(anyhow!(
+                "Take-profit {} and stop-loss {} are on the wrong sides for quantity {}",
+                take_profit,
+                stop_loss,
+                quantity
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl TryFrom<Row> for BracketLeg {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            parent_id: row.try_get("parent_id")?,
+            claim_id: row.try_get("claim_id")?,
+            strategy: row.try_get("strategy")?,
+            sub_strategy: row.try_get("sub_strategy")?,
+            ticker: row.try_get("ticker")?,
+            kind: serde_plain::from_str(row.try_get("kind")?)?,
+            price: row.try_get("price")?,
+            resize_to: row.try_get("resize_to")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_leg_trades() {
+        let parent_id = Uuid::new_v4();
+        let take_profit = BracketLeg::new(
+            parent_id,
+            "A".into(),
+            None,
+            "AAPL".into(),
+            BracketLegKind::TakeProfit,
+            Decimal::new(110, 0),
+        );
+        let trade = take_profit.trade(Decimal::new(-10, 0)).unwrap();
+        assert_eq!(trade.intent.id, take_profit.id);
+        assert_eq!(trade.intent.qty, -10);
+        assert_eq!(
+            trade.intent.order_type,
+            OrderType::Limit {
+                limit_price: Decimal::new(110, 0)
+            }
+        );
+        let stop_loss = BracketLeg::new(
+            parent_id,
+            "A".into(),
+            None,
+            "AAPL".into(),
+            BracketLegKind::StopLoss,
+            Decimal::new(90, 0),
+        );
+        assert_eq!(
+            stop_loss.trade(Decimal::new(-10, 0)).unwrap().intent.order_type,
+            OrderType::Stop {
+                stop_price: Decimal::new(90, 0)
+            }
+        );
+        let claim_id = Uuid::new_v4();
+        let replacement = stop_loss.with_claim(claim_id).replacement();
+        assert_ne!(replacement.id, stop_loss.id);
+        assert_eq!(replacement.parent_id, parent_id);
+        assert_eq!(replacement.claim_id, Some(claim_id));
+    }
+
+    #[test]
+    fn test_leg_quantity() {
+        assert_eq!(leg_quantity(Decimal::new(10, 0), false), Some(Decimal::new(-10, 0)));
+        assert_eq!(leg_quantity(Decimal::new(-105, 1), false), Some(Decimal::new(10, 0)));
+        assert_eq!(leg_quantity(Decimal::new(5, 1), false), None);
+        assert_eq!(leg_quantity(Decimal::new(5, 1), true), Some(Decimal::new(-5, 1)));
+        assert_eq!(leg_quantity(Decimal::ZERO, true), None);
+    }
+
+    #[test]
+    fn test_validate_bracket() {
+        let high = Some(Decimal::new(110, 0));
+        let low = Some(Decimal::new(90, 0));
+        let buy = Decimal::new(10, 0);
+        assert!(validate_bracket(buy, high, low).is_ok());
+        assert!(validate_bracket(buy, low, high).is_err());
+        assert!(validate_bracket(-buy, low, high).is_ok());
+        assert!(validate_bracket(-buy, high, low).is_err());
+        assert!(validate_bracket(-buy, high, None).is_ok());
+    }
+}
@@ -0,0 +1,345 @@
+use super::Position;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt::{Display, Error, Formatter};
+use tokio_postgres::Row;
+use tracing::trace;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Budget {
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub capital: Decimal,
+    pub max_gross_exposure: Option<Decimal>,
+    pub max_net_exposure: Option<Decimal>,
+    pub max_ticker_concentration: Option<Decimal>,
+    /// Whether purchases are limited to the cash of the strategy
+    #[serde(default)]
+    pub enforce_buying_power: bool,
+}
+
+impl Budget {
+    /// Returns the first limit breached by moving from `current` to `projected` exposure.
+    ///
+    /// Limits are only enforced on changes that increase the relevant exposure, so that a
+    /// strategy which is already over budget is always allowed to reduce its risk.
+    pub fn check(&self, current: &Exposure, projected: &Exposure) -> Option<BudgetViolation> {
+        let max_gross = self.max_gross_exposure.unwrap_or(self.capital);
+        if projected.gross > max_gross && projected.gross > current.gross {
+            return Some(BudgetViolation::GrossExposure {
+                limit: max_gross,
+                projected: projected.gross,
+            });
+        }
+        if let Some(max_net) = self.max_net_exposure {
+            if projected.net.abs() > max_net && projected.net.abs() > current.net.abs() {
+                return Some(BudgetViolation::NetExposure {
+                    limit: max_net,
+                    projected: projected.net,
+                });
+            }
+        }
+        if let Some(concentration) = self.max_ticker_concentration {
+            let limit = concentration * self.capital;
+            if projected.ticker.abs() > limit && projected.ticker.abs() > current.ticker.abs() {
+                return Some(BudgetViolation::TickerConcentration {
+                    limit,
+                    projected: projected.ticker,
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns a violation if a change in exposure of `diff_notional` costs more than the `cash`
+    /// left once the `outstanding` purchases have been paid for.
+    pub fn check_buying_power(
+        &self,
+        cash: Decimal,
+        outstanding: Decimal,
+        diff_notional: Decimal,
+    ) -> Option<BudgetViolation> {
+        let available = cash - outstanding;
+        if self.enforce_buying_power && diff_notional > available && diff_notional > Decimal::ZERO {
+            Some(BudgetViolation::BuyingPower {
+                available,
+                required: diff_notional,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl TryFrom<Row> for Budget {
+    type Error = tokio_postgres::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            strategy: row.try_get("strategy")?,
+            sub_strategy: row.try_get("sub_strategy")?,
+            capital: row.try_get("capital")?,
+            max_gross_exposure: row.try_get("max_gross_exposure")?,
+            max_net_exposure: row.try_get("max_net_exposure")?,
+            max_ticker_concentration: row.try_get("max_ticker_concentration")?,
+            enforce_buying_power: row.try_get("enforce_buying_power")?,
+        })
+    }
+}
+
+/// Notional exposure of an owner, both in aggregate and for a single ticker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exposure {
+    pub gross: Decimal,
+    pub net: Decimal,
+    pub ticker: Decimal,
+}
+
+impl Exposure {
+    /// Positions in `ticker` are valued at `price`, all other positions at their basis.
+    pub fn from_positions(positions: &[Position], ticker: &str, price: Decimal) -> Self {
+        let mut exposure = Self {
+            gross: Decimal::ZERO,
+            net: Decimal::ZERO,
+            ticker: Decimal::ZERO,
+        };
+        for position in positions {
+            let notional = if position.ticker == ticker {
+                exposure.ticker += position.shares * price;
+                position.shares * price
+            } else {
+                position.basis
+            };
+            exposure.gross += notional.abs();
+            exposure.net += notional;
+        }
+        exposure
+    }
+
+    /// The exposure after the position in the ticker has been changed to `ticker_notional`.
+    pub fn with_ticker_notional(&self, ticker_notional: Decimal) -> Self {
+        Self {
+            gross: self.gross - self.ticker.abs() + ticker_notional.abs(),
+            net: self.net - self.ticker + ticker_notional,
+            ticker: ticker_notional,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BudgetViolation {
+    GrossExposure { limit: Decimal, projected: Decimal },
+    NetExposure { limit: Decimal, projected: Decimal },
+    TickerConcentration { limit: Decimal, projected: Decimal },
+    BuyingPower { available: Decimal, required: Decimal },
+    MissingPrice,
+}
+
+impl Display for BudgetViolation {
+    fn fmt(&self, formatter: &mut Formatter) -> std::result::Result<(), Error> {
+        match self {
+            BudgetViolation::GrossExposure { limit, projected } => write!(
+                formatter,
+                "Gross exposure of {} would exceed limit of {}",
+                projected, limit
+            ),
+            BudgetViolation::NetExposure { limit, projected } => write!(
+                formatter,
+                "Net exposure of {} would exceed limit of {}",
+                projected, limit
+            ),
+            BudgetViolation::TickerConcentration { limit, projected } => write!(
+                formatter,
+                "Ticker exposure of {} would exceed concentration limit of {}",
+                projected, limit
+            ),
+            BudgetViolation::BuyingPower { available, required } => write!(
+                formatter,
+                "Purchase of {} would exceed available cash of {}",
+                required, available
+            ),
+            BudgetViolation::MissingPrice => formatter.write_str("Missing price for budget check"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BudgetDenial {
+    pub id: Uuid,
+    pub intent_id: Uuid,
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub ticker: String,
+    pub reason: String,
+    pub datetime: DateTime<Utc>,
+}
+
+impl BudgetDenial {
+    #[tracing::instrument(skip(intent_id, strategy, sub_strategy, ticker, reason))]
+    pub fn new(
+        intent_id: Uuid,
+        strategy: String,
+        sub_strategy: Option<String>,
+        ticker: String,
+        reason: String,
+    ) -> Self {
+        trace!(%intent_id, %strategy, ?sub_strategy, %ticker, %reason, "New BudgetDenial");
+        Self {
+            id: Uuid::new_v4(),
+            intent_id,
+            strategy,
+            sub_strategy,
+            ticker,
+            reason,
+            datetime: Utc::now(),
+        }
+    }
+}
+
+impl TryFrom<Row> for BudgetDenial {
+    type Error = tokio_postgres::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            intent_id: row.try_get("intent_id")?,
+            strategy: row.try_get("strategy")?,
+            sub_strategy: row.try_get("sub_strategy")?,
+            ticker: row.try_get("ticker")?,
+            reason: row.try_get("reason")?,
+            datetime: row.try_get("datetime")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Owner;
+
+    fn budget() -> Budget {
+        Budget {
+            strategy: "A".into(),
+            sub_strategy: None,
+            capital: Decimal::new(10000, 0),
+            max_gross_exposure: None,
+            max_net_exposure: Some(Decimal::new(5000, 0)),
+            max_ticker_concentration: Some(Decimal::new(25, 2)),
+            enforce_buying_power: false,
+        }
+    }
+
+    #[test]
+    fn test_exposure() {
+        let positions = vec![
+            Position::new(
+                Owner::Strategy("A".into(), None),
+                "AAPL".into(),
+                Decimal::TEN,
+                Decimal::new(900, 0),
+            ),
+            Position::new(
+                Owner::Strategy("A".into(), None),
+                "MSFT".into(),
+                -Decimal::TEN,
+                Decimal::new(-2000, 0),
+            ),
+        ];
+        let exposure = Exposure::from_positions(&positions, "AAPL", Decimal::ONE_HUNDRED);
+        assert_eq!(
+            exposure,
+            Exposure {
+                gross: Decimal::new(3000, 0),
+                net: Decimal::new(-1000, 0),
+                ticker: Decimal::new(1000, 0),
+            }
+        );
+        assert_eq!(
+            exposure.with_ticker_notional(Decimal::new(-500, 0)),
+            Exposure {
+                gross: Decimal::new(2500, 0),
+                net: Decimal::new(-2500, 0),
+                ticker: Decimal::new(-500, 0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_budget_check() {
+        let budget = budget();
+        let current = Exposure {
+            gross: Decimal::new(2000, 0),
+            net: Decimal::ZERO,
+            ticker: Decimal::ZERO,
+        };
+
+        let okay = current.with_ticker_notional(Decimal::new(2000, 0));
+        assert_eq!(budget.check(&current, &okay), None);
+
+        let concentrated = current.with_ticker_notional(Decimal::new(3000, 0));
+        assert!(matches!(
+            budget.check(&current, &concentrated),
+            Some(BudgetViolation::TickerConcentration { .. })
+        ));
+
+        let net = Exposure {
+            gross: Decimal::new(4000, 0),
+            net: Decimal::new(4000, 0),
+            ticker: Decimal::ZERO,
+        };
+        let projected = net.with_ticker_notional(Decimal::new(2000, 0));
+        assert!(matches!(
+            budget.check(&net, &projected),
+            Some(BudgetViolation::NetExposure { .. })
+        ));
+
+        let gross = Exposure {
+            gross: Decimal::new(12000, 0),
+            net: Decimal::ZERO,
+            ticker: Decimal::new(2000, 0),
+        };
+        let reduced = gross.with_ticker_notional(Decimal::new(1000, 0));
+        assert_eq!(budget.check(&gross, &reduced), None);
+        let increased = gross.with_ticker_notional(Decimal::new(2100, 0));
+        assert!(matches!(
+            budget.check(&gross, &increased),
+            Some(BudgetViolation::GrossExposure { .. })
+        ));
+    }
+
+    #[test]
+    fn test_buying_power() {
+        let mut budget = budget();
+        let cash = Decimal::new(1000, 0);
+        assert_eq!(
+            budget.check_buying_power(cash, Decimal::ZERO, Decimal::new(2000, 0)),
+            None
+        );
+        budget.enforce_buying_power = true;
+        assert_eq!(
+            budget.check_buying_power(cash, Decimal::ZERO, Decimal::new(500, 0)),
+            None
+        );
+        // Sales free up cash rather than use it
+        assert_eq!(
+            budget.check_buying_power(-cash, Decimal::ZERO, Decimal::new(-500, 0)),
+            None
+        );
+        assert_eq!(
+            budget.check_buying_power(cash, Decimal::ZERO, Decimal::new(2000, 0)),
+            Some(BudgetViolation::BuyingPower {
+                available: cash,
+                required: Decimal::new(2000, 0),
+            })
+        );
+        // Cash is set aside for purchases that are still outstanding
+        assert_eq!(
+            budget.check_buying_power(cash, Decimal::new(600, 0), Decimal::new(500, 0)),
+            Some(BudgetViolation::BuyingPower {
+                available: Decimal::new(400, 0),
+                required: Decimal::new(500, 0),
+            })
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use super::{default_account, Owner, Position};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CashEntryKind {
+    /// Cost or proceeds of the shares of an allocation, excluding fees
+    Trade,
+    /// Fees of an allocation
+    Fee,
+    /// Cash dividend on an allocation
+    Dividend,
+    Deposit,
+    Withdrawal,
+    /// One side of a transfer between owners
+    Transfer,
+}
+
+/// An external movement of cash, in or out of an owner's cash. Positive amounts are credited
+/// to the owner.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CashMovement {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub owner: Owner,
+    #[serde(default = "default_account")]
+    pub account: String,
+    pub amount: Decimal,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub datetime: DateTime<Utc>,
+}
+
+impl CashMovement {
+    pub fn kind(&self) -> CashEntryKind {
+        if self.amount.is_sign_negative() {
+            CashEntryKind::Withdrawal
+        } else {
+            CashEntryKind::Deposit
+        }
+    }
+}
+
+/// A transfer of cash from one owner to another in the same account.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CashTransfer {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub from: Owner,
+    pub to: Owner,
+    #[serde(default = "default_account")]
+    pub account: String,
+    pub amount: Decimal,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub datetime: DateTime<Utc>,
+}
+
+/// An entry of the cash ledger, which is derived from allocations, cash dividends and cash
+/// movements.
+#[derive(Clone, Debug, Serialize)]
+pub struct CashEntry {
+    pub owner: Owner,
+    pub account: String,
+    pub kind: CashEntryKind,
+    pub amount: Decimal,
+    pub datetime: Option<DateTime<Utc>>,
+    /// The allocation, corporate action or cash movement behind the entry
+    pub reference_id: Uuid,
+}
+
+impl TryFrom<Row> for CashEntry {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            owner: row.try_get("owner")?,
+            account: row.try_get("account")?,
+            kind: serde_plain::from_str(row.try_get("kind")?)?,
+            amount: row.try_get("amount")?,
+            datetime: row.try_get("datetime")?,
+            reference_id: row.try_get("reference_id")?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CashBalance {
+    pub owner: Owner,
+    pub account: String,
+    pub cash: Decimal,
+}
+
+impl TryFrom<Row> for CashBalance {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            owner: row.try_get("owner")?,
+            account: row.try_get("account")?,
+            cash: row.try_get("cash")?,
+        })
+    }
+}
+
+/// Net asset value of an owner in an account.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Nav {
+    pub owner: Owner,
+    pub account: String,
+    pub cash: Decimal,
+    pub market_value: Decimal,
+    pub nav: Decimal,
+}
+
+impl Nav {
+    /// Values positions at the price of their ticker, or at their basis if there is no price, and
+    /// adds the cash of their owner.
+    pub fn from_balances(
+        balances: &[CashBalance],
+        positions: &[Position],
+        prices: &HashMap<String, Decimal>,
+    ) -> Vec<Self> {
+        let mut navs: Vec<Self> = balances
+            .iter()
+            .map(|balance| Self {
+                owner: balance.owner.clone(),
+                account: balance.account.clone(),
+                cash: balance.cash,
+                market_value: Decimal::ZERO,
+                nav: balance.cash,
+            })
+            .collect();
+        for position in positions {
+            let value = prices
+                .get(&position.ticker)
+                .map(|price| position.shares * price)
+                .unwrap_or(position.basis);
+            let existing = navs
+                .iter_mut()
+                .find(|nav| nav.owner == position.owner && nav.account == position.account);
+            match existing {
+                Some(nav) => {
+                    nav.market_value += value;
+                    nav.nav += value;
+                }
+                None => navs.push(Self {
+                    owner: position.owner.clone(),
+                    account: position.account.clone(),
+                    cash: Decimal::ZERO,
+                    market_value: value,
+                    nav: value,
+                }),
+            }
+        }
+        navs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nav_from_balances() {
+        let owner = Owner::Strategy("A".into(), None);
+        let balances = vec![CashBalance {
+            owner: owner.clone(),
+            account: default_account(),
+            cash: Decimal::new(5000, 0),
+        }];
+        let positions = vec![
+            Position::new(owner.clone(), "AAPL".into(), Decimal::new(10, 0), Decimal::new(1000, 0)),
+            Position::new(owner.clone(), "MSFT".into(), Decimal::new(5, 0), Decimal::new(1500, 0)),
+            Position::new(Owner::House, "AAPL".into(), Decimal::ONE, Decimal::new(100, 0)),
+        ];
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), Decimal::new(120, 0));
+        let navs = Nav::from_balances(&balances, &positions, &prices);
+        assert_eq!(
+            navs,
+            vec![
+                Nav {
+                    owner,
+                    account: default_account(),
+                    cash: Decimal::new(5000, 0),
+                    // MSFT has no price, so is valued at its basis
+                    market_value: Decimal::new(2700, 0),
+                    nav: Decimal::new(7700, 0),
+                },
+                Nav {
+                    owner: Owner::House,
+                    account: default_account(),
+                    cash: Decimal::ZERO,
+                    market_value: Decimal::new(120, 0),
+                    nav: Decimal::new(120, 0),
+                },
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use chrono::{DateTime, Utc};
+use super::default_account;
+use chrono::{DateTime, Duration, Utc};
+use postgres_types::{FromSql, ToSql};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -7,6 +9,35 @@ use tracing::{trace, warn};
 use trading_base::Amount;
 use uuid::Uuid;
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSql, FromSql)]
+#[serde(rename_all = "snake_case")]
+#[postgres(name = "claim_state")]
+pub enum ClaimState {
+    /// No trade has been sent for the claim yet
+    #[postgres(name = "open")]
+    Open,
+    /// A trade has been sent for the claim, but nothing has filled
+    #[postgres(name = "working")]
+    Working,
+    #[postgres(name = "partially_satisfied")]
+    PartiallySatisfied,
+    #[postgres(name = "satisfied")]
+    Satisfied,
+    #[postgres(name = "expired")]
+    Expired,
+    #[postgres(name = "cancelled")]
+    Cancelled,
+    /// Replaced by a newer claim of the same strategy and ticker
+    #[postgres(name = "superseded")]
+    Superseded,
+}
+
+impl Default for ClaimState {
+    fn default() -> Self {
+        ClaimState::Open
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Claim {
     pub id: Uuid,
@@ -16,6 +47,19 @@ pub struct Claim {
     pub amount: Amount,
     pub limit_price: Option<Decimal>,
     pub before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub intent_id: Option<Uuid>,
+    #[serde(default)]
+    pub denial_count: i32,
+    #[serde(default)]
+    pub denied_reason: Option<String>,
+    #[serde(default)]
+    pub retry_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub state: ClaimState,
+    /// The broker account the claim is traded in
+    #[serde(default = "default_account")]
+    pub account: String,
 }
 
 impl Claim {
@@ -37,8 +81,35 @@ impl Claim {
             amount,
             limit_price,
             before,
+            intent_id: None,
+            denial_count: 0,
+            denied_reason: None,
+            retry_after: None,
+            state: ClaimState::Open,
+            account: default_account(),
         }
     }
+
+    pub fn set_intent_id(&mut self, intent_id: Uuid) {
+        self.intent_id = Some(intent_id);
+    }
+
+    pub fn set_account(&mut self, account: String) {
+        self.account = account;
+    }
+
+    /// Records a risk denial, backing off exponentially from `backoff` before the claim is
+    /// retried.
+    pub fn record_denial(&mut self, reason: String, backoff: Duration) {
+        self.denial_count += 1;
+        self.denied_reason = Some(reason);
+        let multiple = 2i32.pow((self.denial_count - 1).clamp(0, 10) as u32);
+        self.retry_after = Some(Utc::now() + backoff * multiple);
+    }
+
+    pub fn is_backing_off(&self) -> bool {
+        self.retry_after.map(|dt| dt > Utc::now()).unwrap_or(false)
+    }
 }
 
 impl TryFrom<Row> for Claim {
@@ -52,6 +123,31 @@ impl TryFrom<Row> for Claim {
             amount: unite_amount_spec(row.try_get("amount")?, row.try_get("unit")?),
             limit_price: row.try_get("limit_price")?,
             before: row.try_get("before")?,
+            intent_id: row.try_get("intent_id")?,
+            denial_count: row.try_get("denial_count")?,
+            denied_reason: row.try_get("denied_reason")?,
+            retry_after: row.try_get("retry_after")?,
+            state: row.try_get("state")?,
+            account: row.try_get("account")?,
+        })
+    }
+}
+
+/// A claim that is no longer traded, kept to explain how a strategy's position came about.
+#[derive(Clone, Debug, Serialize)]
+pub struct ArchivedClaim {
+    #[serde(flatten)]
+    pub claim: Claim,
+    pub archived_at: DateTime<Utc>,
+}
+
+impl TryFrom<Row> for ArchivedClaim {
+    type Error = tokio_postgres::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let archived_at = row.try_get("archived_at")?;
+        Ok(Self {
+            claim: Claim::try_from(row)?,
+            archived_at,
         })
     }
 }
@@ -100,4 +196,25 @@ mod test {
         assert_eq!(dollars_no_price, None);
         assert_eq!(zero, Some(Amount::Shares(-Decimal::ONE)));
     }
+
+    #[test]
+    fn test_record_denial() {
+        let mut claim = Claim::new(
+            "A".into(),
+            None,
+            "AAPL".into(),
+            Amount::Shares(Decimal::ONE),
+            None,
+            None,
+        );
+        assert!(!claim.is_backing_off());
+        claim.record_denial("Denied".into(), Duration::seconds(60));
+        assert_eq!(claim.denial_count, 1);
+        assert_eq!(claim.denied_reason, Some("Denied".into()));
+        let first_retry = claim.retry_after.unwrap();
+        assert!(claim.is_backing_off());
+        claim.record_denial("Denied".into(), Duration::seconds(60));
+        assert_eq!(claim.denial_count, 2);
+        assert!(claim.retry_after.unwrap() - first_retry >= Duration::seconds(59));
+    }
 }
@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CorporateActionKind {
+    /// `to` shares for every `from` shares held, so a 4-for-1 split is `from: 1, to: 4` and a
+    /// 1-for-10 reverse split is `from: 10, to: 1`
+    Split {
+        from: Decimal,
+        to: Decimal,
+    },
+    SymbolChange {
+        new_ticker: String,
+    },
+    /// Cash paid per share held before the ex-date
+    CashDividend {
+        amount: Decimal,
+        ex_date: NaiveDate,
+    },
+    /// New shares paid per share held
+    StockDividend {
+        rate: Decimal,
+    },
+}
+
+impl CorporateActionKind {
+    /// The number of shares held after the action for every share held before it.
+    pub fn share_factor(&self) -> Decimal {
+        match self {
+            CorporateActionKind::Split { from, to } => *to / *from,
+            CorporateActionKind::StockDividend { rate } => Decimal::ONE + *rate,
+            CorporateActionKind::SymbolChange { .. } | CorporateActionKind::CashDividend { .. } => Decimal::ONE,
+        }
+    }
+}
+
+/// A corporate action on a ticker, applied to the positions and orders in it as soon as it is
+/// received.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub id: Uuid,
+    pub ticker: String,
+    #[serde(flatten)]
+    pub kind: CorporateActionKind,
+    #[serde(default = "Utc::now")]
+    pub applied_at: DateTime<Utc>,
+}
+
+impl CorporateAction {
+    pub fn validate(&self) -> Result<()> {
+        match &self.kind {
+            CorporateActionKind::Split { from, to } if from <= &Decimal::ZERO || to <= &Decimal::ZERO => {
+                Err(anyhow!("Split ratio must be positive"))
+            }
+            CorporateActionKind::SymbolChange { new_ticker } if new_ticker.is_empty() || new_ticker == &self.ticker => {
+                Err(anyhow!("Symbol change must be to a new ticker"))
+            }
+            CorporateActionKind::StockDividend { rate } if rate <= &Decimal::ZERO => {
+                Err(anyhow!("Stock dividend rate must be positive"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The ticker after the action
+    pub fn new_ticker(&self) -> &str {
+        match &self.kind {
+            CorporateActionKind::SymbolChange { new_ticker } => new_ticker,
+            _ => &self.ticker,
+        }
+    }
+}
+
+impl TryFrom<Row> for CorporateAction {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            ticker: row.try_get("ticker")?,
+            kind: serde_json::from_str(row.try_get("action")?)?,
+            applied_at: row.try_get("applied_at")?,
+        })
+    }
+}
+
+/// The change a corporate action made to a single record, kept as an audit of the action.
+#[derive(Clone, Debug, Serialize)]
+pub struct CorporateActionAdjustment {
+    pub corporate_action_id: Uuid,
+    /// The kind of record adjusted, e.g. `lot` or `claim`
+    pub entity: String,
+    pub entity_id: Uuid,
+    /// The ticker of the record before the action
+    pub ticker: String,
+    pub quantity: Option<Decimal>,
+    pub adjusted_quantity: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub adjusted_price: Option<Decimal>,
+    /// Cash owed to the owner of an allocation by a cash dividend
+    pub cash: Option<Decimal>,
+}
+
+impl TryFrom<Row> for CorporateActionAdjustment {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            corporate_action_id: row.try_get("corporate_action_id")?,
+            entity: row.try_get("entity")?,
+            entity_id: row.try_get("entity_id")?,
+            ticker: row.try_get("ticker")?,
+            quantity: row.try_get("quantity")?,
+            adjusted_quantity: row.try_get("adjusted_quantity")?,
+            price: row.try_get("price")?,
+            adjusted_price: row.try_get("adjusted_price")?,
+            cash: row.try_get("cash")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_corporate_action() {
+        let action: CorporateAction = serde_json::from_str(
+            r#"{"id": "a4dc1b5b-1ecf-4d44-9a44-5f5c8c4d3f6b", "ticker": "AAPL", "type": "split", "from": 1, "to": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(action.ticker, "AAPL");
+        assert_eq!(
+            action.kind,
+            CorporateActionKind::Split {
+                from: Decimal::ONE,
+                to: Decimal::new(4, 0)
+            }
+        );
+        assert!(action.validate().is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_cash_dividend() {
+        let action: CorporateAction = serde_json::from_str(
+            r#"{"id": "a4dc1b5b-1ecf-4d44-9a44-5f5c8c4d3f6b", "ticker": "AAPL", "type": "cash_dividend", "amount": "0.22", "ex_date": "2021-08-06"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            action.kind,
+            CorporateActionKind::CashDividend {
+                amount: Decimal::new(22, 2),
+                ex_date: NaiveDate::from_ymd(2021, 8, 6)
+            }
+        );
+        // Dividends are only paid on shares held before the ex-date
+        assert!(serde_json::from_str::<CorporateAction>(
+            r#"{"id": "a4dc1b5b-1ecf-4d44-9a44-5f5c8c4d3f6b", "ticker": "AAPL", "type": "cash_dividend", "amount": "0.22"}"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_share_factor() {
+        let split = CorporateActionKind::Split {
+            from: Decimal::ONE,
+            to: Decimal::new(4, 0),
+        };
+        assert_eq!(split.share_factor(), Decimal::new(4, 0));
+        let reverse_split = CorporateActionKind::Split {
+            from: Decimal::new(10, 0),
+            to: Decimal::ONE,
+        };
+        assert_eq!(reverse_split.share_factor(), Decimal::new(1, 1));
+        let stock_dividend = CorporateActionKind::StockDividend {
+            rate: Decimal::new(5, 2),
+        };
+        assert_eq!(stock_dividend.share_factor(), Decimal::new(105, 2));
+        let dividend = CorporateActionKind::CashDividend {
+            amount: Decimal::new(23, 2),
+            ex_date: NaiveDate::from_ymd(2021, 8, 12),
+        };
+        assert_eq!(dividend.share_factor(), Decimal::ONE);
+    }
+
+    #[test]
+    fn test_validate() {
+        let action = |kind| CorporateAction {
+            id: Uuid::new_v4(),
+            ticker: "FB".into(),
+            kind,
+            applied_at: Utc::now(),
+        };
+        assert!(action(CorporateActionKind::SymbolChange {
+            new_ticker: "META".into()
+        })
+        .validate()
+        .is_ok());
+        assert!(action(CorporateActionKind::SymbolChange {
+            new_ticker: "FB".into()
+        })
+        .validate()
+        .is_err());
+        assert!(action(CorporateActionKind::Split {
+            from: Decimal::ZERO,
+            to: Decimal::ONE
+        })
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use super::{OrderInstructions, Status, TradeOrder};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use trading_base::{OrderType, TradeIntent};
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependentTrigger {
+    /// Sent once the parent has completely filled
+    OnFill,
+    /// Sent once the parent has filled at least `trigger_quantity` shares
+    OnPartialFill,
+    /// Sent once the parent has been cancelled, expired or rejected
+    OnCancel,
+}
+
+impl Default for DependentTrigger {
+    fn default() -> Self {
+        DependentTrigger::OnFill
+    }
+}
+
+/// A trade that is sent once its parent trade reaches the trigger condition.
+///
+/// If the parent ends before the condition is met, the dependent trade is resized to the part of
+/// the parent that filled, or cancelled together with its own dependent trades if nothing filled.
+#[derive(Clone, Debug, Serialize)]
+pub struct DependentTrade {
+    pub parent_id: Uuid,
+    pub trade: TradeOrder,
+    /// The time-in-force has already been applied to the trade, the order kind still needs to be
+    /// applied at the prices at which it is sent
+    pub instructions: OrderInstructions,
+    pub trigger: DependentTrigger,
+    /// Filled shares of the parent after which an `OnPartialFill` trade is sent
+    pub trigger_quantity: Option<Decimal>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl DependentTrade {
+    pub fn new(parent_id: Uuid, trade: TradeOrder, instructions: OrderInstructions) -> Self {
+        Self {
+            parent_id,
+            trade,
+            instructions,
+            trigger: DependentTrigger::OnFill,
+            trigger_quantity: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: DependentTrigger, trigger_quantity: Option<Decimal>) -> Self {
+        self.trigger = trigger;
+        self.trigger_quantity = trigger_quantity;
+        self
+    }
+
+    pub fn expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|dt| dt <= Utc::now()).unwrap_or(false)
+    }
+
+    /// The trade to send once the parent has ended with `filled` of its `parent_quantity` shares
+    /// filled, or `None` if nothing is left to trade.
+    ///
+    /// Bracket legs only protect the part of the parent that filled, so they are scaled down.
+    /// Other trades continue from where the parent would have left the position, such as the leg
+    /// opening the new position of a flip, so they also take over the part that did not fill.
+    /// Partial shares are only traded in `fractional` tickers.
+    pub fn on_parent_ended(
+        &self,
+        filled: Decimal,
+        parent_quantity: Decimal,
+        is_bracket_leg: bool,
+        fractional: bool,
+    ) -> Option<TradeOrder> {
+        match self.trigger {
+            DependentTrigger::OnCancel => Some(self.trade.clone()),
+            DependentTrigger::OnFill | DependentTrigger::OnPartialFill if is_bracket_leg => {
+                self.scaled(filled, parent_quantity, fractional)
+            }
+            DependentTrigger::OnFill | DependentTrigger::OnPartialFill => {
+                self.continued(parent_quantity - filled, fractional)
+            }
+        }
+    }
+
+    /// The trade resized in proportion to the part of its parent that filled. Returns `None` if
+    /// nothing is left to trade.
+    pub fn scaled(&self, filled: Decimal, parent_quantity: Decimal, fractional: bool) -> Option<TradeOrder> {
+        if parent_quantity.is_zero() {
+            return None;
+        }
+        self.resized(self.trade.quantity() * filled / parent_quantity, fractional)
+    }
+
+    /// The trade together with the `unfilled` shares of its parent. Returns `None` if nothing is
+    /// left to trade.
+    pub fn continued(&self, unfilled: Decimal, fractional: bool) -> Option<TradeOrder> {
+        self.resized(self.trade.quantity() + unfilled, fractional)
+    }
+
+    /// The trade resized to `quantity`, rounded towards zero to whole shares unless `fractional`
+    fn resized(&self, quantity: Decimal, fractional: bool) -> Option<TradeOrder> {
+        let quantity = if fractional {
+            quantity.round_dp_with_strategy(8, RoundingStrategy::ToZero)
+        } else {
+            quantity.trunc()
+        };
+        if quantity.is_zero() {
+            return None;
+        }
+        self.trade.clone().with_quantity(quantity).ok()
+    }
+}
+
+impl TryFrom<Row> for DependentTrade {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let order_type = match (
+            row.try_get("order_type")?,
+            row.try_get("limit_price")?,
+            row.try_get("stop_price")?,
+        ) {
+            ("market", _, _) => OrderType::Market,
+            ("limit", Some(limit_price), _) => OrderType::Limit { limit_price },
+            ("stop", _, Some(stop_price)) => OrderType::Stop { stop_price },
+            ("stoplimit", Some(limit_price), Some(stop_price)) => OrderType::StopLimit {
+                limit_price,
+                stop_price,
+            },
+            (order_type, limit_price, stop_price) => {
+                return Err(anyhow!(
+                    "Invalid dependent trade: order type {} with limit price {:?} and stop price {:?}",
+                    order_type,
+                    limit_price,
+                    stop_price
+                ))
+            }
+        };
+        let time_in_force = serde_plain::from_str(row.try_get("time_in_force")?)?;
+        let intent = TradeIntent {
+            id: row.try_get("id")?,
+            ticker: row.try_get("ticker")?,
+            qty: 0,
+            order_type,
+            time_in_force,
+        };
+        let mut trade = TradeOrder::new(intent).with_quantity(row.try_get("qty")?)?;
+        trade.extensions.notional = row.try_get("notional")?;
+        trade.account = row.try_get("account")?;
+        let instructions = OrderInstructions {
+            order_kind: serde_plain::from_str(row.try_get("order_kind")?)?,
+            trail_price: row.try_get("trail_price")?,
+            trail_percent: row.try_get("trail_percent")?,
+            time_in_force: None,
+            extended_hours: row.try_get("extended_hours")?,
+            take_profit: None,
+            stop_loss: None,
+            trigger: DependentTrigger::OnFill,
+            trigger_quantity: None,
+        };
+        Ok(Self {
+            parent_id: row.try_get("dependent_id")?,
+            trade,
+            instructions,
+            trigger: serde_plain::from_str(row.try_get("trigger_condition")?)?,
+            trigger_quantity: row.try_get("trigger_quantity")?,
+            expires_at: row.try_get("expires_at")?,
+        })
+    }
+}
+
+/// A dependent trade that is waiting for its trigger, with the status of its parent. The parent
+/// status is missing while the parent has not been sent, or when the parent is an execution.
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingDependentTrade {
+    #[serde(flatten)]
+    pub dependent_trade: DependentTrade,
+    pub parent_status: Option<Status>,
+}
+
+impl TryFrom<Row> for PendingDependentTrade {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let parent_status = row.try_get("parent_status")?;
+        Ok(Self {
+            dependent_trade: DependentTrade::try_from(row)?,
+            parent_status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dependent(qty: isize) -> DependentTrade {
+        DependentTrade::new(
+            Uuid::new_v4(),
+            TradeOrder::new(TradeIntent::new("AAPL", qty)),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn test_scaled() {
+        let dependent = dependent(-100);
+        let trade = dependent
+            .scaled(Decimal::new(37, 0), Decimal::new(100, 0), false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -37);
+        assert_eq!(trade.intent.id, dependent.trade.intent.id);
+        // Partial shares are not traded
+        let trade = dependent
+            .scaled(Decimal::new(-75, 1), Decimal::new(-10, 0), false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -75);
+        assert!(dependent.scaled(Decimal::ZERO, Decimal::new(100, 0), false).is_none());
+        assert!(dependent
+            .scaled(Decimal::new(5, 1), Decimal::new(100, 0), false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_scaled_fractional() {
+        let dependent = dependent(-100);
+        let trade = dependent
+            .scaled(Decimal::new(5, 1), Decimal::new(100, 0), true)
+            .unwrap();
+        assert_eq!(trade.quantity(), Decimal::new(-5, 1));
+        assert_eq!(trade.intent.qty, -1);
+        let trade = dependent
+            .on_parent_ended(Decimal::new(-255, 1), Decimal::new(-50, 0), false, true)
+            .unwrap();
+        assert_eq!(trade.quantity(), Decimal::new(-1245, 1));
+    }
+
+    #[test]
+    fn test_flip_continues_after_partial_close() {
+        // Closing 100 shares and opening a short of 50 after them
+        let dependent = dependent(-50);
+        let trade = dependent
+            .on_parent_ended(Decimal::new(-60, 0), Decimal::new(-100, 0), false, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -90);
+        let trade = dependent
+            .on_parent_ended(Decimal::ZERO, Decimal::new(-100, 0), false, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -150);
+        // A follow-up trade that only undid the part of the parent that did not fill
+        assert!(dependent
+            .on_parent_ended(Decimal::new(50, 0), Decimal::new(100, 0), false, false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_triggers_on_parent_ended() {
+        let bracket_leg = dependent(-100);
+        let trade = bracket_leg
+            .on_parent_ended(Decimal::new(40, 0), Decimal::new(100, 0), true, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -40);
+        assert!(bracket_leg
+            .on_parent_ended(Decimal::ZERO, Decimal::new(100, 0), true, false)
+            .is_none());
+
+        let partial = bracket_leg
+            .clone()
+            .with_trigger(DependentTrigger::OnPartialFill, Some(Decimal::new(50, 0)));
+        let trade = partial
+            .on_parent_ended(Decimal::new(40, 0), Decimal::new(100, 0), true, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, -40);
+
+        let on_cancel = dependent(10).with_trigger(DependentTrigger::OnCancel, None);
+        let trade = on_cancel
+            .on_parent_ended(Decimal::new(40, 0), Decimal::new(100, 0), false, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, 10);
+        let trade = on_cancel
+            .on_parent_ended(Decimal::ZERO, Decimal::new(100, 0), true, false)
+            .unwrap();
+        assert_eq!(trade.intent.qty, 10);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let dependent = dependent(10);
+        assert!(!dependent.is_expired());
+        let dependent = dependent.expires_at(Some(Utc::now() - chrono::Duration::seconds(1)));
+        assert!(dependent.is_expired());
+    }
+}
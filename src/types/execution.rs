@@ -0,0 +1,340 @@
+use super::TradeOrder;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use tracing::trace;
+use trading_base::{OrderType, TradeIntent};
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionAlgorithm {
+    /// Equal slices released at regular intervals
+    Twap,
+    /// Slices sized as a fraction of the volume traded since the previous slice
+    Pov,
+    /// Slices of a fixed display size, each released once the previous one has filled
+    Iceberg,
+}
+
+/// Per-strategy configuration of how large claims should be worked.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExecutionPolicy {
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub algorithm: ExecutionAlgorithm,
+    /// Trades below this notional are sent as a single order
+    pub min_notional: Decimal,
+    pub duration_seconds: i32,
+    pub slices: i32,
+    pub participation_rate: Option<Decimal>,
+    pub display_size: Option<i32>,
+}
+
+impl ExecutionPolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.slices < 1 {
+            return Err(anyhow!("Execution must have at least one slice"));
+        }
+        if self.algorithm != ExecutionAlgorithm::Iceberg && self.duration_seconds < self.slices {
+            return Err(anyhow!("Slices must be at least one second apart"));
+        }
+        if self.display_size.map(|size| size <= 0).unwrap_or(false) {
+            return Err(anyhow!("Display size must be positive"));
+        }
+        if self.algorithm == ExecutionAlgorithm::Pov
+            && !self
+                .participation_rate
+                .map(|rate| rate > Decimal::ZERO)
+                .unwrap_or(false)
+        {
+            return Err(anyhow!("Participation rate must be positive"));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Row> for ExecutionPolicy {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            strategy: row.try_get("strategy")?,
+            sub_strategy: row.try_get("sub_strategy")?,
+            algorithm: serde_plain::from_str(row.try_get("algorithm")?)?,
+            min_notional: row.try_get("min_notional")?,
+            duration_seconds: row.try_get("duration_seconds")?,
+            slices: row.try_get("slices")?,
+            participation_rate: row.try_get("participation_rate")?,
+            display_size: row.try_get("display_size")?,
+        })
+    }
+}
+
+/// A parent trade that is being worked as a series of child trades.
+///
+/// The `id` is the id of the parent `TradeIntent`, so that dependent trades saved against the
+/// parent are released once the execution has completed.
+#[derive(Clone, Debug, Serialize)]
+pub struct Execution {
+    pub id: Uuid,
+    pub claim_id: Option<Uuid>,
+    pub ticker: String,
+    /// The broker account that the child trades are sent to
+    pub account: String,
+    pub algorithm: ExecutionAlgorithm,
+    pub total_quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    pub slices_remaining: i32,
+    pub interval_seconds: i32,
+    pub participation_rate: Option<Decimal>,
+    pub display_size: Option<i32>,
+    pub limit_price: Option<Decimal>,
+    pub stop_price: Option<Decimal>,
+    pub last_volume: Option<Decimal>,
+    pub working_id: Option<Uuid>,
+    pub next_release_at: Option<DateTime<Utc>>,
+    /// Set when a child trade ended without filling. The execution is kept as a record of what
+    /// was worked, and the remainder of the claim is left to reconciliation.
+    pub abandoned_at: Option<DateTime<Utc>>,
+}
+
+impl Execution {
+    #[tracing::instrument(skip(order, claim_id, policy), fields(id = %order.intent.id))]
+    pub fn new(order: &TradeOrder, claim_id: Option<Uuid>, policy: &ExecutionPolicy) -> Self {
+        trace!(?claim_id, algorithm = ?policy.algorithm, "New Execution");
+        let parent = &order.intent;
+        let (limit_price, stop_price) = match parent.order_type {
+            OrderType::Market => (None, None),
+            OrderType::Limit { limit_price } => (Some(limit_price), None),
+            OrderType::Stop { stop_price } => (None, Some(stop_price)),
+            OrderType::StopLimit {
+                limit_price,
+                stop_price,
+            } => (Some(limit_price), Some(stop_price)),
+        };
+        // Policies are validated when they are saved, but a zero interval or display size would
+        // release slices without pausing or without making progress
+        let slices = policy.slices.max(1);
+        Self {
+            id: parent.id,
+            claim_id,
+            ticker: parent.ticker.clone(),
+            account: order.account.clone(),
+            algorithm: policy.algorithm,
+            total_quantity: order.quantity(),
+            remaining_quantity: order.quantity(),
+            slices_remaining: slices,
+            interval_seconds: (policy.duration_seconds / slices).max(1),
+            participation_rate: policy.participation_rate,
+            display_size: policy.display_size.filter(|size| *size > 0),
+            limit_price,
+            stop_price,
+            last_volume: None,
+            working_id: None,
+            next_release_at: Some(Utc::now()),
+            abandoned_at: None,
+        }
+    }
+
+    /// Size of the next child trade, given the cumulative traded volume of the ticker. Slices are
+    /// whole shares, and a fractional remainder is traded with the last slice.
+    pub fn next_slice_quantity(&self, volume: Option<Decimal>) -> Decimal {
+        let remaining = self.remaining_quantity.abs();
+        let quantity = match self.algorithm {
+            ExecutionAlgorithm::Twap => {
+                if self.slices_remaining <= 1 {
+                    remaining
+                } else {
+                    (remaining / Decimal::from(self.slices_remaining))
+                        .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero)
+                }
+            }
+            ExecutionAlgorithm::Pov => match (volume, self.last_volume, self.participation_rate) {
+                _ if self.slices_remaining <= 1 => remaining,
+                (Some(volume), Some(last_volume), Some(rate)) => ((volume - last_volume).max(Decimal::ZERO) * rate)
+                    .round_dp_with_strategy(0, RoundingStrategy::ToZero),
+                _ => Decimal::ZERO,
+            },
+            ExecutionAlgorithm::Iceberg => self.display_size.map(Decimal::from).unwrap_or(remaining),
+        };
+        let quantity = quantity.min(remaining);
+        if self.remaining_quantity.is_sign_negative() {
+            -quantity
+        } else {
+            quantity
+        }
+    }
+
+    pub fn child_trade(&self, quantity: Decimal) -> Result<TradeOrder> {
+        let order_type = match (self.limit_price, self.stop_price) {
+            (Some(limit_price), Some(stop_price)) => OrderType::StopLimit {
+                limit_price,
+                stop_price,
+            },
+            (Some(limit_price), None) => OrderType::Limit { limit_price },
+            (None, Some(stop_price)) => OrderType::Stop { stop_price },
+            (None, None) => OrderType::Market,
+        };
+        TradeOrder::new(TradeIntent::new(&self.ticker, 0).order_type(order_type))
+            .with_account(self.account.clone())
+            .with_quantity(quantity)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining_quantity.is_zero()
+    }
+
+    pub fn is_abandoned(&self) -> bool {
+        self.abandoned_at.is_some()
+    }
+}
+
+impl TryFrom<Row> for Execution {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            claim_id: row.try_get("claim_id")?,
+            ticker: row.try_get("ticker")?,
+            account: row.try_get("account")?,
+            algorithm: serde_plain::from_str(row.try_get("algorithm")?)?,
+            total_quantity: row.try_get("total_quantity")?,
+            remaining_quantity: row.try_get("remaining_quantity")?,
+            slices_remaining: row.try_get("slices_remaining")?,
+            interval_seconds: row.try_get("interval_seconds")?,
+            participation_rate: row.try_get("participation_rate")?,
+            display_size: row.try_get("display_size")?,
+            limit_price: row.try_get("limit_price")?,
+            stop_price: row.try_get("stop_price")?,
+            last_volume: row.try_get("last_volume")?,
+            working_id: row.try_get("working_id")?,
+            next_release_at: row.try_get("next_release_at")?,
+            abandoned_at: row.try_get("abandoned_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy(algorithm: ExecutionAlgorithm) -> ExecutionPolicy {
+        ExecutionPolicy {
+            strategy: "A".into(),
+            sub_strategy: None,
+            algorithm,
+            min_notional: Decimal::ZERO,
+            duration_seconds: 600,
+            slices: 3,
+            participation_rate: Some(Decimal::new(1, 1)),
+            display_size: Some(40),
+        }
+    }
+
+    #[test]
+    fn test_twap_slices() {
+        let parent = TradeOrder::new(TradeIntent::new("AAPL", -100));
+        let mut execution = Execution::new(&parent, None, &policy(ExecutionAlgorithm::Twap));
+        assert_eq!(execution.interval_seconds, 200);
+        let mut slices = Vec::new();
+        while !execution.is_complete() {
+            let quantity = execution.next_slice_quantity(None);
+            execution.remaining_quantity -= quantity;
+            execution.slices_remaining -= 1;
+            slices.push(quantity);
+        }
+        assert_eq!(slices, vec![Decimal::from(-34), Decimal::from(-33), Decimal::from(-33)]);
+    }
+
+    #[test]
+    fn test_fractional_twap_slices() {
+        let parent = TradeOrder::new(TradeIntent::new("AAPL", 0))
+            .with_quantity(Decimal::new(25, 1))
+            .unwrap();
+        let mut execution = Execution::new(&parent, None, &policy(ExecutionAlgorithm::Twap));
+        let mut slices = Vec::new();
+        while !execution.is_complete() {
+            let quantity = execution.next_slice_quantity(None);
+            execution.remaining_quantity -= quantity;
+            execution.slices_remaining -= 1;
+            slices.push(quantity);
+        }
+        assert_eq!(slices, vec![Decimal::ONE, Decimal::ONE, Decimal::new(5, 1)]);
+        let child = execution.child_trade(Decimal::new(5, 1)).unwrap();
+        assert_eq!(child.intent.qty, 1);
+        assert_eq!(child.quantity(), Decimal::new(5, 1));
+    }
+
+    #[test]
+    fn test_pov_slices() {
+        let parent = TradeOrder::new(TradeIntent::new("AAPL", 100));
+        let mut execution = Execution::new(&parent, None, &policy(ExecutionAlgorithm::Pov));
+        assert_eq!(
+            execution.next_slice_quantity(Some(Decimal::new(1000, 0))),
+            Decimal::ZERO
+        );
+        execution.last_volume = Some(Decimal::new(1000, 0));
+        assert_eq!(
+            execution.next_slice_quantity(Some(Decimal::new(1255, 0))),
+            Decimal::from(25)
+        );
+        assert_eq!(
+            execution.next_slice_quantity(Some(Decimal::new(5000, 0))),
+            Decimal::ONE_HUNDRED
+        );
+        execution.slices_remaining = 1;
+        assert_eq!(execution.next_slice_quantity(None), Decimal::ONE_HUNDRED);
+    }
+
+    #[test]
+    fn test_validate_policy() {
+        assert!(policy(ExecutionAlgorithm::Twap).validate().is_ok());
+        let mut too_many_slices = policy(ExecutionAlgorithm::Twap);
+        too_many_slices.slices = 1000;
+        assert!(too_many_slices.validate().is_err());
+        let mut no_slices = policy(ExecutionAlgorithm::Pov);
+        no_slices.slices = 0;
+        assert!(no_slices.validate().is_err());
+        let mut no_rate = policy(ExecutionAlgorithm::Pov);
+        no_rate.participation_rate = None;
+        assert!(no_rate.validate().is_err());
+        let mut empty_display = policy(ExecutionAlgorithm::Iceberg);
+        empty_display.display_size = Some(0);
+        assert!(empty_display.validate().is_err());
+    }
+
+    #[test]
+    fn test_clamped_execution() {
+        let mut policy = policy(ExecutionAlgorithm::Iceberg);
+        policy.slices = 1000;
+        policy.display_size = Some(0);
+        let execution = Execution::new(&TradeOrder::new(TradeIntent::new("AAPL", 100)), None, &policy);
+        assert_eq!(execution.interval_seconds, 1);
+        // Without a display size the remainder is released in one slice
+        assert_eq!(execution.next_slice_quantity(None), Decimal::ONE_HUNDRED);
+    }
+
+    #[test]
+    fn test_iceberg_slices() {
+        let parent = TradeIntent::new("AAPL", 100).order_type(OrderType::Limit {
+            limit_price: Decimal::ONE,
+        });
+        let parent = TradeOrder::new(parent).with_account("paper".into());
+        let mut execution = Execution::new(&parent, None, &policy(ExecutionAlgorithm::Iceberg));
+        assert_eq!(execution.next_slice_quantity(None), Decimal::from(40));
+        execution.remaining_quantity = Decimal::from(20);
+        assert_eq!(execution.next_slice_quantity(None), Decimal::from(20));
+        let child = execution.child_trade(Decimal::from(20)).unwrap();
+        assert_eq!(child.intent.qty, 20);
+        assert_eq!(child.account, "paper");
+        assert_eq!(
+            child.intent.order_type,
+            OrderType::Limit {
+                limit_price: Decimal::ONE
+            }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use super::{Allocation, Owner, Position};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// What reconciliation does with the shares the house is left holding when fills exceed the
+/// claims on a ticker.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HousePolicy {
+    /// The position is reduced to less than a share at market
+    Liquidate,
+    /// The position is left with the house
+    Hold,
+    /// The position is reassigned to the error account, a strategy that can be flattened with a
+    /// regular position intent
+    ErrorAccount,
+    /// The shares are reallocated to the strategy whose claim the overfilled lot was allocated to
+    Reallocate,
+}
+
+impl Default for HousePolicy {
+    fn default() -> Self {
+        HousePolicy::Liquidate
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HouseActionState {
+    /// Waiting for an operator to approve or reject the action
+    Pending,
+    /// Approved, and applied at the next reconciliation that can apply it
+    Approved,
+    Rejected,
+    Executed,
+    /// The house position changed or closed before the approved action was applied
+    Superseded,
+    /// The action could only partly be applied, such as a reallocation with shares that no
+    /// strategy can take
+    Failed,
+}
+
+/// A house policy action held for operator approval.
+#[derive(Clone, Debug, Serialize)]
+pub struct HouseAction {
+    pub id: Uuid,
+    pub account: String,
+    pub ticker: String,
+    pub policy: HousePolicy,
+    /// The shares held by the house when the action was proposed
+    pub shares: Decimal,
+    pub state: HouseActionState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl HouseAction {
+    pub fn new(position: &Position, policy: HousePolicy) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            account: position.account.clone(),
+            ticker: position.ticker.clone(),
+            policy,
+            shares: position.shares,
+            state: HouseActionState::Pending,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Whether the action can be applied to a house position of `shares`. An approved action is
+    /// only applied to the position the operator approved.
+    pub fn applies_to(&self, shares: Decimal) -> bool {
+        self.shares == shares
+    }
+}
+
+/// Whether a house position of `shares` needs a new action, given the last action proposed for
+/// it. Positions with an open action are not proposed again, nor are positions whose last action
+/// was rejected or failed while the position is unchanged.
+pub fn needs_house_action(last_action: Option<&HouseAction>, shares: Decimal) -> bool {
+    match last_action {
+        None => true,
+        Some(action) => match action.state {
+            HouseActionState::Pending | HouseActionState::Approved => false,
+            HouseActionState::Rejected | HouseActionState::Failed => !action.applies_to(shares),
+            HouseActionState::Executed | HouseActionState::Superseded => true,
+        },
+    }
+}
+
+impl TryFrom<Row> for HouseAction {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            account: row.try_get("account")?,
+            ticker: row.try_get("ticker")?,
+            policy: serde_plain::from_str(row.try_get("policy")?)?,
+            shares: row.try_get("shares")?,
+            state: serde_plain::from_str(row.try_get("state")?)?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+/// The strategy that caused a lot to be overfilled, taken to be the one with the largest claim
+/// allocation of the lot.
+pub fn reallocation_owner(lot_allocations: &[Allocation]) -> Option<Owner> {
+    lot_allocations
+        .iter()
+        .filter(|allocation| allocation.claim_id.is_some() && allocation.owner != Owner::House)
+        .max_by_key(|allocation| allocation.shares.abs())
+        .map(|allocation| allocation.owner.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn action(shares: Decimal, state: HouseActionState) -> HouseAction {
+        let position = Position::new(Owner::House, "AAPL".into(), shares, shares * Decimal::ONE_HUNDRED);
+        let mut action = HouseAction::new(&position, HousePolicy::Liquidate);
+        action.state = state;
+        action
+    }
+
+    #[test]
+    fn test_approved_action_applies_to_approved_shares() {
+        let approved = action(Decimal::new(5, 0), HouseActionState::Approved);
+        assert!(approved.applies_to(Decimal::new(5, 0)));
+        assert!(!approved.applies_to(Decimal::new(7, 0)));
+        assert!(!approved.applies_to(Decimal::new(-5, 0)));
+    }
+
+    #[test]
+    fn test_needs_house_action() {
+        let shares = Decimal::new(5, 0);
+        let changed = Decimal::new(7, 0);
+        assert!(needs_house_action(None, shares));
+        for state in [HouseActionState::Pending, HouseActionState::Approved].iter() {
+            assert!(!needs_house_action(Some(&action(shares, *state)), changed));
+        }
+        // Rejected and failed actions are only proposed again once the position changes
+        for state in [HouseActionState::Rejected, HouseActionState::Failed].iter() {
+            assert!(!needs_house_action(Some(&action(shares, *state)), shares));
+            assert!(needs_house_action(Some(&action(shares, *state)), changed));
+        }
+        for state in [HouseActionState::Executed, HouseActionState::Superseded].iter() {
+            assert!(needs_house_action(Some(&action(shares, *state)), shares));
+        }
+    }
+
+    #[test]
+    fn test_reallocation_owner() {
+        let lot_id = Uuid::new_v4();
+        let allocation = |owner: Owner, claim_id: Option<Uuid>, shares: Decimal| {
+            Allocation::new(
+                owner,
+                claim_id,
+                lot_id,
+                "AAPL".into(),
+                shares,
+                shares * Decimal::ONE_HUNDRED,
+            )
+        };
+        let house = allocation(Owner::House, None, Decimal::new(5, 0));
+        let small = allocation(
+            Owner::Strategy("A".into(), None),
+            Some(Uuid::new_v4()),
+            Decimal::new(2, 0),
+        );
+        let large = allocation(
+            Owner::Strategy("B".into(), Some("B1".into())),
+            Some(Uuid::new_v4()),
+            Decimal::new(8, 0),
+        );
+        assert_eq!(
+            reallocation_owner(&[house.clone(), small.clone(), large]),
+            Some(Owner::Strategy("B".into(), Some("B1".into())))
+        );
+        assert_eq!(
+            reallocation_owner(&[small, house.clone()]),
+            Some(Owner::Strategy("A".into(), None))
+        );
+        // Lots filled without any claim open have no strategy to reallocate to
+        assert_eq!(reallocation_owner(&[house]), None);
+    }
+}
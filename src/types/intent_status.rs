@@ -0,0 +1,159 @@
+use super::Claim;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+use trading_base::{PositionIntent, UpdatePolicy};
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentState {
+    Accepted,
+    Scheduled,
+    /// A scheduled intent reached its trigger time and is being evaluated
+    Triggered,
+    Ignored,
+    Expired,
+    RiskDenied,
+    PartiallyFilled,
+    Completed,
+    Cancelled,
+}
+
+/// Lifecycle update on a `PositionIntent`, reported back to the strategy that sent it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntentStatus {
+    pub intent_id: Uuid,
+    pub strategy: String,
+    pub sub_strategy: Option<String>,
+    pub state: IntentState,
+    pub reason: Option<String>,
+    pub datetime: DateTime<Utc>,
+}
+
+impl IntentStatus {
+    #[tracing::instrument(skip(intent_id, strategy, sub_strategy, state, reason))]
+    pub fn new(
+        intent_id: Uuid,
+        strategy: String,
+        sub_strategy: Option<String>,
+        state: IntentState,
+        reason: Option<String>,
+    ) -> Self {
+        trace!(%intent_id, %strategy, ?sub_strategy, ?state, ?reason, "New IntentStatus");
+        Self {
+            intent_id,
+            strategy,
+            sub_strategy,
+            state,
+            reason,
+            datetime: Utc::now(),
+        }
+    }
+
+    pub fn for_intent(intent: &PositionIntent, state: IntentState, reason: Option<String>) -> Self {
+        Self::new(
+            intent.id,
+            intent.strategy.clone(),
+            intent.sub_strategy.clone(),
+            state,
+            reason,
+        )
+    }
+
+    /// The status of a multi-ticker `Zero` intent that closes `closing` positions. It is only
+    /// ignored if nothing is closed because the update policy retains every position.
+    pub fn for_multi_ticker_intent(intent: &PositionIntent, closing: usize) -> Self {
+        match intent.update_policy {
+            _ if closing > 0 => Self::for_intent(intent, IntentState::Accepted, None),
+            UpdatePolicy::Retain => Self::for_intent(
+                intent,
+                IntentState::Ignored,
+                Some("Position retained by update policy".into()),
+            ),
+            _ => Self::for_intent(intent, IntentState::Completed, None),
+        }
+    }
+
+    /// Returns `None` for claims that were not created from a `PositionIntent`.
+    pub fn for_claim(claim: &Claim, state: IntentState, reason: Option<String>) -> Option<Self> {
+        claim.intent_id.map(|intent_id| {
+            Self::new(
+                intent_id,
+                claim.strategy.clone(),
+                claim.sub_strategy.clone(),
+                state,
+                reason,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use trading_base::{Amount, Identifier};
+
+    fn intent(update_policy: UpdatePolicy) -> PositionIntent {
+        let mut intent = PositionIntent::builder("A", "AAPL", Amount::Zero)
+            .update_policy(update_policy)
+            .build()
+            .unwrap();
+        intent.identifier = Identifier::All;
+        intent.sub_strategy = Some("A1".into());
+        intent
+    }
+
+    #[test]
+    fn test_for_intent() {
+        let intent = intent(UpdatePolicy::Update);
+        let status = IntentStatus::for_intent(&intent, IntentState::RiskDenied, Some("Too big".into()));
+        assert_eq!(status.intent_id, intent.id);
+        assert_eq!(status.strategy, "A");
+        assert_eq!(status.sub_strategy, Some("A1".into()));
+        assert_eq!(status.state, IntentState::RiskDenied);
+        assert_eq!(status.reason, Some("Too big".into()));
+    }
+
+    #[test]
+    fn test_for_multi_ticker_intent() {
+        let retain = intent(UpdatePolicy::Retain);
+        assert_eq!(
+            IntentStatus::for_multi_ticker_intent(&retain, 0).state,
+            IntentState::Ignored
+        );
+        assert_eq!(
+            IntentStatus::for_multi_ticker_intent(&retain, 2).state,
+            IntentState::Accepted
+        );
+        let retain_long = intent(UpdatePolicy::RetainLong);
+        assert_eq!(
+            IntentStatus::for_multi_ticker_intent(&retain_long, 1).state,
+            IntentState::Accepted
+        );
+        // Nothing left to close, so the intent is already satisfied
+        let update = intent(UpdatePolicy::Update);
+        let status = IntentStatus::for_multi_ticker_intent(&update, 0);
+        assert_eq!(status.state, IntentState::Completed);
+        assert_eq!(status.reason, None);
+    }
+
+    #[test]
+    fn test_for_claim() {
+        let mut claim = Claim::new(
+            "A".into(),
+            None,
+            "AAPL".into(),
+            Amount::Shares(Decimal::ONE),
+            None,
+            None,
+        );
+        assert!(IntentStatus::for_claim(&claim, IntentState::Completed, None).is_none());
+        let intent_id = Uuid::new_v4();
+        claim.set_intent_id(intent_id);
+        let status = IntentStatus::for_claim(&claim, IntentState::PartiallyFilled, None).unwrap();
+        assert_eq!(status.intent_id, intent_id);
+        assert_eq!(status.state, IntentState::PartiallyFilled);
+    }
+}
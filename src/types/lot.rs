@@ -1,3 +1,4 @@
+use super::default_account;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -14,6 +15,12 @@ pub struct Lot {
     pub fill_time: DateTime<Utc>,
     pub price: Decimal,
     pub shares: Decimal,
+    /// Commissions and regulatory charges paid on the fill
+    #[serde(default)]
+    pub fees: Decimal,
+    /// The broker account the fill happened in
+    #[serde(default = "default_account")]
+    pub account: String,
 }
 
 impl Lot {
@@ -27,8 +34,26 @@ impl Lot {
             fill_time,
             price,
             shares,
+            fees: Decimal::ZERO,
+            account: default_account(),
         }
     }
+
+    pub fn with_fees(mut self, fees: Decimal) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_account(mut self, account: String) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Cost of the lot including fees. Fees increase the cost of purchases and reduce the
+    /// proceeds of sales.
+    pub fn basis(&self) -> Decimal {
+        self.shares * self.price + self.fees
+    }
 }
 
 impl TryFrom<Row> for Lot {
@@ -41,6 +66,8 @@ impl TryFrom<Row> for Lot {
             fill_time: row.try_get("fill_time")?,
             price: row.try_get("price")?,
             shares: row.try_get("shares")?,
+            fees: row.try_get("fees")?,
+            account: row.try_get("account")?,
         })
     }
 }
@@ -1,12 +1,44 @@
+mod account;
 mod allocation;
+mod bracket;
+mod budget;
+mod cash;
 mod claim;
+mod corporate_action;
+mod dependent_trade;
+mod execution;
+mod house_action;
+mod intent_status;
 mod lot;
+mod order_instructions;
 mod owner;
 mod position;
+mod queued_trade;
+mod risk_check_request;
+mod schedule;
+mod stale_trade_resolution;
+mod trade_order;
 mod trades;
+mod trading_calendar;
+pub use account::*;
 pub use allocation::*;
+pub use bracket::*;
+pub use budget::*;
+pub use cash::*;
 pub use claim::*;
+pub use corporate_action::*;
+pub use dependent_trade::*;
+pub use execution::*;
+pub use house_action::*;
+pub use intent_status::*;
 pub use lot::*;
+pub use order_instructions::*;
 pub use owner::*;
 pub use position::*;
+pub use queued_trade::*;
+pub use risk_check_request::*;
+pub use schedule::*;
+pub use stale_trade_resolution::*;
+pub use trade_order::*;
 pub use trades::*;
+pub use trading_calendar::*;
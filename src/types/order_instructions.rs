@@ -0,0 +1,279 @@
+use super::{DependentTrigger, TradeOrder};
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use trading_base::{OrderType, TradeIntent};
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderKind {
+    /// Market, limit, stop or stop-limit, depending on the prices of the intent
+    Standard,
+    TrailingStop,
+    MarketOnOpen,
+    MarketOnClose,
+}
+
+impl Default for OrderKind {
+    fn default() -> Self {
+        OrderKind::Standard
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeInForce {
+    Day,
+    Gtc,
+    Opg,
+    Cls,
+    Ioc,
+    Fok,
+}
+
+impl TimeInForce {
+    /// The time in force of a trade
+    pub fn of(trade: &TradeIntent) -> Result<Self> {
+        let time_in_force = serde_plain::to_string(&trade.time_in_force)?;
+        serde_plain::from_str(&time_in_force).map_err(|_| anyhow!("Unsupported time in force: {}", time_in_force))
+    }
+}
+
+/// Order options that a `PositionIntent` can carry in addition to its limit and stop prices.
+///
+/// These are read from the same message as the intent and stored against the intent id, so that
+/// they are applied to every trade generated for the intent's claim.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct OrderInstructions {
+    #[serde(default)]
+    pub order_kind: OrderKind,
+    #[serde(default)]
+    pub trail_price: Option<Decimal>,
+    /// Trail as a percentage of the last price, e.g. 1.5 for 1.5%
+    #[serde(default)]
+    pub trail_percent: Option<Decimal>,
+    #[serde(default)]
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(default)]
+    pub extended_hours: bool,
+    /// Limit price of a take-profit order placed once the trade has filled
+    #[serde(default)]
+    pub take_profit: Option<Decimal>,
+    /// Stop price of a stop-loss order placed once the trade has filled
+    #[serde(default)]
+    pub stop_loss: Option<Decimal>,
+    /// When a trade that waits for an earlier trade of the same ticker is sent, such as the leg
+    /// opening the new position of a flip
+    #[serde(default)]
+    pub trigger: DependentTrigger,
+    /// Filled shares of the earlier trade after which an `on_partial_fill` trade is sent
+    #[serde(default)]
+    pub trigger_quantity: Option<Decimal>,
+}
+
+impl OrderInstructions {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// A trailing stop requires a trail, and a trigger quantity is required for, and only accepted
+    /// with, the `on_partial_fill` trigger.
+    pub fn validate(&self) -> Result<()> {
+        if self.order_kind == OrderKind::TrailingStop && self.trail_price.is_none() && self.trail_percent.is_none() {
+            return Err(anyhow!("A trailing stop requires a trail price or percent"));
+        }
+        match (self.trigger, self.trigger_quantity) {
+            (DependentTrigger::OnPartialFill, Some(quantity)) if quantity > Decimal::ZERO => Ok(()),
+            (DependentTrigger::OnPartialFill, _) => Err(anyhow!(
+                "The on_partial_fill trigger requires a positive trigger quantity"
+            )),
+            (_, Some(_)) => Err(anyhow!("A trigger quantity requires the on_partial_fill trigger")),
+            (_, None) => Ok(()),
+        }
+    }
+
+    pub fn has_bracket(&self) -> bool {
+        self.take_profit.is_some() || self.stop_loss.is_some()
+    }
+
+    /// Applies the time-in-force, which does not depend on market prices.
+    pub fn apply_time_in_force(&self, mut trade: TradeIntent) -> Result<TradeIntent> {
+        let time_in_force = match self.order_kind {
+            OrderKind::MarketOnOpen => Some(TimeInForce::Opg),
+            OrderKind::MarketOnClose => Some(TimeInForce::Cls),
+            _ => self.time_in_force,
+        };
+        if let Some(time_in_force) = time_in_force {
+            let time_in_force = serde_plain::to_string(&time_in_force)?;
+            trade.time_in_force = serde_plain::from_str(&time_in_force)
+                .map_err(|_| anyhow!("Unsupported time in force: {}", time_in_force))?;
+        }
+        Ok(trade)
+    }
+
+    /// Applies the order kind and the extended hours flag. Auction orders are sent at market.
+    /// Trailing stops are sent with their trail as an extension, and as a stop at the last price
+    /// plus the trail for brokers that can't trail.
+    pub fn apply_order_kind(&self, mut order: TradeOrder, last_price: Option<Decimal>) -> Result<TradeOrder> {
+        let trade = &mut order.intent;
+        let extensions = &mut order.extensions;
+        match self.order_kind {
+            OrderKind::Standard => {}
+            OrderKind::MarketOnOpen | OrderKind::MarketOnClose => trade.order_type = OrderType::Market,
+            OrderKind::TrailingStop => {
+                let last_price = last_price.ok_or_else(|| anyhow!("Trailing stop requires a price"))?;
+                let trail = match (self.trail_price, self.trail_percent) {
+                    (Some(trail_price), _) => {
+                        extensions.trail_price = Some(trail_price);
+                        trail_price
+                    }
+                    (None, Some(trail_percent)) => {
+                        extensions.trail_percent = Some(trail_percent);
+                        last_price * trail_percent / Decimal::ONE_HUNDRED
+                    }
+                    (None, None) => return Err(anyhow!("Trailing stop requires a trail price or percent")),
+                };
+                let stop_price = if trade.qty > 0 {
+                    last_price + trail
+                } else {
+                    last_price - trail
+                };
+                trade.order_type = OrderType::Stop {
+                    stop_price: stop_price.round_dp(2),
+                };
+            }
+        }
+        if self.extended_hours && !matches!(trade.order_type, OrderType::Limit { .. }) {
+            return Err(anyhow!("Extended hours trading requires a limit order"));
+        }
+        extensions.extended_hours = self.extended_hours;
+        Ok(order)
+    }
+
+    pub fn apply(&self, mut order: TradeOrder, last_price: Option<Decimal>) -> Result<TradeOrder> {
+        order.intent = self.apply_time_in_force(order.intent)?;
+        self.apply_order_kind(order, last_price)
+    }
+}
+
+impl TryFrom<Row> for OrderInstructions {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let time_in_force: Option<&str> = row.try_get("time_in_force")?;
+        Ok(Self {
+            order_kind: serde_plain::from_str(row.try_get("order_kind")?)?,
+            trail_price: row.try_get("trail_price")?,
+            trail_percent: row.try_get("trail_percent")?,
+            time_in_force: time_in_force.map(serde_plain::from_str).transpose()?,
+            extended_hours: row.try_get("extended_hours")?,
+            take_profit: row.try_get("take_profit")?,
+            stop_loss: row.try_get("stop_loss")?,
+            trigger: serde_plain::from_str(row.try_get("trigger_condition")?)?,
+            trigger_quantity: row.try_get("trigger_quantity")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_from_intent_message() {
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","order_kind":"market_on_close","take_profit":110}"#).unwrap();
+        assert_eq!(instructions.order_kind, OrderKind::MarketOnClose);
+        assert_eq!(instructions.take_profit, Some(Decimal::new(110, 0)));
+        let instructions: OrderInstructions = serde_json::from_str(r#"{"strategy":"A"}"#).unwrap();
+        assert!(instructions.is_default());
+    }
+
+    #[test]
+    fn test_trigger() {
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","trigger":"on_partial_fill","trigger_quantity":50}"#).unwrap();
+        assert_eq!(instructions.trigger, DependentTrigger::OnPartialFill);
+        assert!(instructions.validate().is_ok());
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","trigger":"on_partial_fill"}"#).unwrap();
+        assert!(instructions.validate().is_err());
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","trigger":"on_cancel","trigger_quantity":50}"#).unwrap();
+        assert!(instructions.validate().is_err());
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","trigger":"on_cancel"}"#).unwrap();
+        assert!(instructions.validate().is_ok());
+        assert!(!instructions.is_default());
+    }
+
+    #[test]
+    fn test_trailing_stop() {
+        let instructions: OrderInstructions =
+            serde_json::from_str(r#"{"strategy":"A","order_kind":"trailing_stop","trail_percent":1}"#).unwrap();
+        assert_eq!(instructions.order_kind, OrderKind::TrailingStop);
+        let price = Some(Decimal::ONE_HUNDRED);
+        let sell = instructions
+            .apply(TradeOrder::new(TradeIntent::new("AAPL", -10)), price)
+            .unwrap();
+        assert_eq!(
+            sell.intent.order_type,
+            OrderType::Stop {
+                stop_price: Decimal::new(99, 0)
+            }
+        );
+        assert_eq!(sell.extensions.trail_percent, Some(Decimal::ONE));
+        assert_eq!(sell.extensions.trail_price, None);
+        let buy = instructions
+            .apply(TradeOrder::new(TradeIntent::new("AAPL", 10)), price)
+            .unwrap();
+        assert_eq!(
+            buy.intent.order_type,
+            OrderType::Stop {
+                stop_price: Decimal::new(101, 0)
+            }
+        );
+        assert!(instructions
+            .apply(TradeOrder::new(TradeIntent::new("AAPL", 10)), None)
+            .is_err());
+        let no_trail = OrderInstructions {
+            order_kind: OrderKind::TrailingStop,
+            ..Default::default()
+        };
+        assert!(no_trail.validate().is_err());
+        assert!(no_trail
+            .apply(TradeOrder::new(TradeIntent::new("AAPL", 10)), price)
+            .is_err());
+    }
+
+    #[test]
+    fn test_market_on_close() {
+        let instructions = OrderInstructions {
+            order_kind: OrderKind::MarketOnClose,
+            ..Default::default()
+        };
+        let trade = TradeIntent::new("AAPL", 10).order_type(OrderType::Limit {
+            limit_price: Decimal::ONE,
+        });
+        let trade = instructions.apply_order_kind(TradeOrder::new(trade), None).unwrap();
+        assert_eq!(trade.intent.order_type, OrderType::Market);
+        assert!(trade.extensions.is_default());
+    }
+
+    #[test]
+    fn test_extended_hours_requires_limit() {
+        let instructions = OrderInstructions {
+            extended_hours: true,
+            ..Default::default()
+        };
+        assert!(instructions
+            .apply_order_kind(TradeOrder::new(TradeIntent::new("AAPL", 10)), None)
+            .is_err());
+        let limit = TradeIntent::new("AAPL", 10).order_type(OrderType::Limit {
+            limit_price: Decimal::ONE,
+        });
+        let trade = instructions.apply_order_kind(TradeOrder::new(limit), None).unwrap();
+        assert!(trade.extensions.extended_hours);
+    }
+}
@@ -1,10 +1,16 @@
+use bytes::BytesMut;
+use postgres_types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Error, Formatter};
 
+type SqlError = Box<dyn std::error::Error + Sync + Send>;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Owner {
     House,
     Strategy(String, Option<String>),
+    /// An account that house positions are moved to for manual review, by name
+    ErrorAccount(String),
 }
 
 impl Display for Owner {
@@ -20,6 +26,83 @@ impl Display for Owner {
                     formatter.write_str(strategy)
                 }
             }
+            Owner::ErrorAccount(name) => {
+                formatter.write_str("ErrorAccount:")?;
+                formatter.write_str(name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, ToSql, FromSql)]
+#[postgres(name = "owner_kind")]
+enum OwnerKind {
+    #[postgres(name = "house")]
+    House,
+    #[postgres(name = "strategy")]
+    Strategy,
+    #[postgres(name = "error_account")]
+    ErrorAccount,
+}
+
+/// The `owner` composite type in Postgres. Owners are stored by kind so that a strategy can have
+/// any name, including the ones of other owner kinds. The name of an error account is stored as
+/// its strategy.
+#[derive(Debug, ToSql, FromSql)]
+#[postgres(name = "owner")]
+struct OwnerRecord {
+    kind: OwnerKind,
+    strategy: Option<String>,
+    sub_strategy: Option<String>,
+}
+
+impl From<&Owner> for OwnerRecord {
+    fn from(owner: &Owner) -> Self {
+        match owner {
+            Owner::House => OwnerRecord {
+                kind: OwnerKind::House,
+                strategy: None,
+                sub_strategy: None,
+            },
+            Owner::Strategy(strategy, sub_strategy) => OwnerRecord {
+                kind: OwnerKind::Strategy,
+                strategy: Some(strategy.clone()),
+                sub_strategy: sub_strategy.clone(),
+            },
+            Owner::ErrorAccount(name) => OwnerRecord {
+                kind: OwnerKind::ErrorAccount,
+                strategy: Some(name.clone()),
+                sub_strategy: None,
+            },
         }
     }
 }
+
+impl ToSql for Owner {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, SqlError> {
+        OwnerRecord::from(self).to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <OwnerRecord as ToSql>::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for Owner {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, SqlError> {
+        let record = OwnerRecord::from_sql(ty, raw)?;
+        match (record.kind, record.strategy) {
+            (OwnerKind::House, _) => Ok(Owner::House),
+            (OwnerKind::Strategy, Some(strategy)) => Ok(Owner::Strategy(strategy, record.sub_strategy)),
+            (OwnerKind::Strategy, None) => Err("Strategy owner without a strategy".into()),
+            (OwnerKind::ErrorAccount, Some(name)) => Ok(Owner::ErrorAccount(name)),
+            (OwnerKind::ErrorAccount, None) => Err("Error account owner without a name".into()),
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <OwnerRecord as FromSql>::accepts(ty)
+    }
+}
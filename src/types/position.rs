@@ -1,4 +1,4 @@
-use super::Owner;
+use super::{default_account, Owner};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -8,6 +8,8 @@ use tracing::trace;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Position {
     pub owner: Owner,
+    #[serde(default = "default_account")]
+    pub account: String,
     pub ticker: String,
     pub shares: Decimal,
     pub basis: Decimal,
@@ -19,6 +21,7 @@ impl Position {
         trace!(%owner, %ticker, %shares, %basis, "New Position");
         Self {
             owner,
+            account: default_account(),
             ticker,
             shares,
             basis,
@@ -37,14 +40,9 @@ impl Position {
 impl TryFrom<Row> for Position {
     type Error = tokio_postgres::Error;
     fn try_from(row: Row) -> Result<Self, Self::Error> {
-        let owner = row.try_get("owner")?;
-        let owner = if owner == "House" {
-            Owner::House
-        } else {
-            Owner::Strategy(owner, row.try_get("sub_owner")?)
-        };
         Ok(Self {
-            owner,
+            owner: row.try_get("owner")?,
+            account: row.try_get("account")?,
             ticker: row.try_get("ticker")?,
             shares: row.try_get("shares")?,
             basis: row.try_get("basis")?,
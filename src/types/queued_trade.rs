@@ -0,0 +1,53 @@
+use super::TradeOrder;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// A trade generated while the market was closed, held until the next open.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueuedTrade {
+    pub id: Uuid,
+    pub order: TradeOrder,
+    pub claim_id: Option<Uuid>,
+    pub queued_at: DateTime<Utc>,
+    /// Whether the trade already passed the risk check, so that it is sent without another one
+    pub risk_checked: bool,
+}
+
+impl QueuedTrade {
+    #[tracing::instrument(skip(order, claim_id), fields(id = %order.intent.id))]
+    pub fn new(order: TradeOrder, claim_id: Option<Uuid>) -> Self {
+        tracing::trace!(?claim_id, "New QueuedTrade");
+        Self {
+            id: order.intent.id,
+            order,
+            claim_id,
+            queued_at: Utc::now(),
+            risk_checked: false,
+        }
+    }
+
+    pub fn risk_checked(mut self) -> Self {
+        self.risk_checked = true;
+        self
+    }
+}
+
+impl TryFrom<Row> for QueuedTrade {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            order: TradeOrder {
+                intent: serde_json::from_str(row.try_get("intent")?)?,
+                extensions: serde_json::from_str(row.try_get("extensions")?)?,
+                account: row.try_get("account")?,
+            },
+            claim_id: row.try_get("claim_id")?,
+            queued_at: row.try_get("queued_at")?,
+            risk_checked: row.try_get("risk_checked")?,
+        })
+    }
+}
@@ -0,0 +1,47 @@
+use super::TradeOrder;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// A trade that has been sent to the external risk-manager and not yet been answered.
+#[derive(Clone, Debug, Serialize)]
+pub struct RiskCheckRequest {
+    pub id: Uuid,
+    pub order: TradeOrder,
+    pub claim_id: Option<Uuid>,
+    pub sent_at: DateTime<Utc>,
+    pub attempts: i32,
+}
+
+impl RiskCheckRequest {
+    #[tracing::instrument(skip(order, claim_id), fields(id = %order.intent.id))]
+    pub fn new(order: TradeOrder, claim_id: Option<Uuid>) -> Self {
+        tracing::trace!(?claim_id, "New RiskCheckRequest");
+        Self {
+            id: order.intent.id,
+            order,
+            claim_id,
+            sent_at: Utc::now(),
+            attempts: 1,
+        }
+    }
+}
+
+impl TryFrom<Row> for RiskCheckRequest {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            order: TradeOrder {
+                intent: serde_json::from_str(row.try_get("intent")?)?,
+                extensions: serde_json::from_str(row.try_get("extensions")?)?,
+                account: row.try_get("account")?,
+            },
+            claim_id: row.try_get("claim_id")?,
+            sent_at: row.try_get("sent_at")?,
+            attempts: row.try_get("attempts")?,
+        })
+    }
+}
@@ -0,0 +1,291 @@
+use super::{OrderInstructions, TradingCalendar};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use trading_base::PositionIntent;
+use uuid::Uuid;
+
+/// How far ahead to look for the next run of a schedule before giving up on it.
+const MAX_LOOKAHEAD_DAYS: i64 = 400;
+
+/// When a recurring intent runs. Times are New York local times, and schedules only run on
+/// trading days of the exchange calendar.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Schedule {
+    /// A five field cron expression: minute, hour, day of month, month and day of week
+    Cron { expression: String },
+    /// Relative to the market open, e.g. 5 minutes after the open
+    MarketOpen {
+        #[serde(default)]
+        offset_minutes: i64,
+    },
+    /// Relative to the market close, e.g. -10 for 10 minutes before the close
+    MarketClose {
+        #[serde(default)]
+        offset_minutes: i64,
+    },
+    /// At a fixed time of each trading day
+    Daily { time: NaiveTime },
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<()> {
+        if let Schedule::Cron { expression } = self {
+            CronExpression::parse(expression)?;
+        }
+        Ok(())
+    }
+
+    /// The first time strictly after `after` that the schedule runs, if any.
+    pub fn next_after(&self, calendar: &TradingCalendar, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let cron = match self {
+            Schedule::Cron { expression } => Some(CronExpression::parse(expression)?),
+            _ => None,
+        };
+        // Start the day before, since offsets from the open or close can cross midnight
+        let start = calendar.to_local(after).date().pred();
+        for days in 0..MAX_LOOKAHEAD_DAYS {
+            let date = start + Duration::days(days);
+            if !calendar.is_trading_day(date) {
+                continue;
+            }
+            let next = match (self, &cron) {
+                (Schedule::MarketOpen { offset_minutes }, _) => {
+                    Some(calendar.market_open(date) + Duration::minutes(*offset_minutes))
+                }
+                (Schedule::MarketClose { offset_minutes }, _) => {
+                    Some(calendar.market_close(date) + Duration::minutes(*offset_minutes))
+                }
+                (Schedule::Daily { time }, _) => Some(calendar.to_utc(date.and_time(*time))),
+                (Schedule::Cron { .. }, Some(cron)) => cron.next_on(calendar, date, after),
+                (Schedule::Cron { .. }, None) => unreachable!(),
+            };
+            if let Some(next) = next.filter(|next| *next > after) {
+                return Ok(Some(next));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct CronExpression {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronExpression {
+    fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow!("Cron expression must have five fields: {}", expression));
+        }
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if days_of_week.remove(&7) {
+            days_of_week.insert(0);
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day_of_month = self.days_of_month.contains(&date.day());
+        let day_of_week = self.days_of_week.contains(&date.weekday().num_days_from_sunday());
+        // As in cron, a day matches either restriction when both fields are restricted
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    fn next_on(&self, calendar: &TradingCalendar, date: NaiveDate, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.matches_date(date) {
+            return None;
+        }
+        self.hours
+            .iter()
+            .flat_map(|hour| {
+                self.minutes
+                    .iter()
+                    .map(move |minute| NaiveTime::from_hms(*hour, *minute, 0))
+            })
+            .map(|time| calendar.to_utc(date.and_time(time)))
+            .find(|datetime| *datetime > after)
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>> {
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("Invalid step in cron field: {}", field));
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None => {
+                    let value = range.parse()?;
+                    // A single value with a step runs from the value to the end of the range
+                    if part.contains('/') {
+                        (value, max)
+                    } else {
+                        (value, value)
+                    }
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(anyhow!("Cron field out of range: {}", field));
+        }
+        values.extend((start..=end).step_by(step));
+    }
+    Ok(values)
+}
+
+/// A rule that generates a new `PositionIntent` from a template each time its schedule runs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecurringIntent {
+    pub id: Uuid,
+    pub schedule: Schedule,
+    /// Template of the generated intents. Its id, timestamp, `before` and `after` are replaced
+    pub intent: PositionIntent,
+    #[serde(default)]
+    pub instructions: OrderInstructions,
+    #[serde(default)]
+    pub next_run_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+impl RecurringIntent {
+    /// A new intent generated from the template.
+    pub fn instance(&self) -> PositionIntent {
+        let mut intent = self.intent.clone();
+        intent.id = Uuid::new_v4();
+        intent.timestamp = Utc::now();
+        intent.before = None;
+        intent.after = None;
+        intent
+    }
+}
+
+impl TryFrom<Row> for RecurringIntent {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            schedule: serde_json::from_str(row.try_get("schedule")?)?,
+            intent: serde_json::from_str(row.try_get("intent")?)?,
+            instructions: serde_json::from_str(row.try_get("instructions")?)?,
+            next_run_at: row.try_get("next_run_at")?,
+            last_run_at: row.try_get("last_run_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_cron() {
+        let cron = CronExpression::parse("*/15 9-16 * * 1-5").unwrap();
+        assert_eq!(cron.minutes, vec![0, 15, 30, 45].into_iter().collect());
+        assert_eq!(cron.hours.len(), 8);
+        assert_eq!(cron.days_of_week, (1..=5).collect());
+        let cron = CronExpression::parse("0 12 1,15 * 7").unwrap();
+        assert_eq!(cron.days_of_week, vec![0].into_iter().collect());
+        assert!(CronExpression::parse("0 12 * *").is_err());
+        assert!(CronExpression::parse("60 12 * * *").is_err());
+        assert!(CronExpression::parse("*/0 12 * * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let calendar = TradingCalendar::default();
+        let schedule = Schedule::Cron {
+            expression: "50 15 * * *".into(),
+        };
+        // Friday July 2nd 2021, after 15:50 in New York
+        let after = Utc.ymd(2021, 7, 2).and_hms(20, 0, 0);
+        // The weekend is skipped
+        assert_eq!(
+            schedule.next_after(&calendar, after).unwrap(),
+            Some(Utc.ymd(2021, 7, 5).and_hms(19, 50, 0))
+        );
+        let schedule = Schedule::Cron {
+            expression: "0 10 30 2 *".into(),
+        };
+        assert_eq!(schedule.next_after(&calendar, after).unwrap(), None);
+    }
+
+    #[test]
+    fn test_market_open_schedule() {
+        let holiday = NaiveDate::from_ymd(2021, 7, 5);
+        let calendar = TradingCalendar::new(vec![holiday].into_iter().collect(), Default::default());
+        let schedule = Schedule::MarketOpen { offset_minutes: 5 };
+        let after = Utc.ymd(2021, 7, 2).and_hms(13, 0, 0);
+        assert_eq!(
+            schedule.next_after(&calendar, after).unwrap(),
+            Some(Utc.ymd(2021, 7, 2).and_hms(13, 35, 0))
+        );
+        // The holiday is skipped
+        let after = Utc.ymd(2021, 7, 2).and_hms(13, 35, 0);
+        assert_eq!(
+            schedule.next_after(&calendar, after).unwrap(),
+            Some(Utc.ymd(2021, 7, 6).and_hms(13, 35, 0))
+        );
+    }
+
+    #[test]
+    fn test_daily_and_market_close_schedules() {
+        let calendar = TradingCalendar::default();
+        let after = Utc.ymd(2021, 12, 1).and_hms(12, 0, 0);
+        let daily = Schedule::Daily {
+            time: NaiveTime::from_hms(15, 50, 0),
+        };
+        let close = Schedule::MarketClose { offset_minutes: -10 };
+        let expected = Some(Utc.ymd(2021, 12, 1).and_hms(20, 50, 0));
+        assert_eq!(daily.next_after(&calendar, after).unwrap(), expected);
+        assert_eq!(close.next_after(&calendar, after).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_deserialize_schedule() {
+        let schedule: Schedule = serde_json::from_str(r#"{"type":"market_open","offset_minutes":5}"#).unwrap();
+        assert_eq!(schedule, Schedule::MarketOpen { offset_minutes: 5 });
+        let schedule: Schedule = serde_json::from_str(r#"{"type":"daily","time":"15:50:00"}"#).unwrap();
+        assert_eq!(
+            schedule,
+            Schedule::Daily {
+                time: NaiveTime::from_hms(15, 50, 0)
+            }
+        );
+    }
+}
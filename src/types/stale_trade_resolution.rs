@@ -0,0 +1,124 @@
+use super::{Status, Trade};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// A cancel was sent to the broker for the stale trade
+    CancelSent,
+    /// The trade was marked dead without hearing back from the broker
+    MarkedDead,
+    /// The broker reported the trade as ended, and its status was applied
+    ReportedByBroker,
+    /// The broker reported the trade as still working, so it was left in place
+    StillWorking,
+    /// The broker reported fills that were never received, so the trade was left in place for
+    /// an operator to book them
+    MissingFills,
+}
+
+impl Resolution {
+    /// The resolution of a stale trade given the order the broker reports for it, if the broker
+    /// could be asked.
+    pub fn for_broker_order(trade: &Trade, order: Option<&Trade>) -> Self {
+        match order {
+            None => Resolution::MarkedDead,
+            Some(order) if order.quantity - order.pending_quantity != trade.filled_quantity => Resolution::MissingFills,
+            Some(order) if order.status.is_terminal() => Resolution::ReportedByBroker,
+            Some(_) => Resolution::StillWorking,
+        }
+    }
+}
+
+/// A record of reconciliation stepping in for a trade that stopped receiving broker updates,
+/// kept for operators to follow up on with the broker.
+#[derive(Clone, Debug, Serialize)]
+pub struct StaleTradeResolution {
+    pub trade_id: Uuid,
+    pub ticker: String,
+    /// The status of the trade when it was found to be stale
+    pub status: Status,
+    pub resolution: Resolution,
+    pub datetime: DateTime<Utc>,
+}
+
+impl StaleTradeResolution {
+    pub fn new(trade: &Trade, resolution: Resolution) -> Self {
+        Self {
+            trade_id: trade.id,
+            ticker: trade.ticker.clone(),
+            status: trade.status,
+            resolution,
+            datetime: Utc::now(),
+        }
+    }
+}
+
+impl TryFrom<Row> for StaleTradeResolution {
+    type Error = anyhow::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            trade_id: row.try_get("trade_id")?,
+            ticker: row.try_get("ticker")?,
+            status: row.try_get("status")?,
+            resolution: serde_plain::from_str(row.try_get("resolution")?)?,
+            datetime: row.try_get("datetime")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn stale_trade() -> Trade {
+        let mut trade = Trade::new(Uuid::new_v4(), "AAPL".into(), Decimal::new(100, 0));
+        trade.status = Status::PartiallyFilled;
+        trade.pending_quantity = Decimal::new(60, 0);
+        trade.filled_quantity = Decimal::new(40, 0);
+        trade
+    }
+
+    fn broker_order(trade: &Trade, status: Status, pending_quantity: Decimal) -> Trade {
+        let mut order = trade.clone();
+        order.status = status;
+        order.pending_quantity = pending_quantity;
+        order
+    }
+
+    #[test]
+    fn test_resolution_for_broker_order() {
+        let trade = stale_trade();
+        assert_eq!(Resolution::for_broker_order(&trade, None), Resolution::MarkedDead);
+        let working = broker_order(&trade, Status::PartiallyFilled, Decimal::new(60, 0));
+        assert_eq!(
+            Resolution::for_broker_order(&trade, Some(&working)),
+            Resolution::StillWorking
+        );
+        let cancelled = broker_order(&trade, Status::Cancelled, Decimal::new(60, 0));
+        assert_eq!(
+            Resolution::for_broker_order(&trade, Some(&cancelled)),
+            Resolution::ReportedByBroker
+        );
+        let dead = broker_order(&trade, Status::Dead, Decimal::new(60, 0));
+        assert_eq!(
+            Resolution::for_broker_order(&trade, Some(&dead)),
+            Resolution::ReportedByBroker
+        );
+        let filled = broker_order(&trade, Status::Filled, Decimal::ZERO);
+        assert_eq!(
+            Resolution::for_broker_order(&trade, Some(&filled)),
+            Resolution::MissingFills
+        );
+        let cancelled_after_fill = broker_order(&trade, Status::Cancelled, Decimal::new(50, 0));
+        assert_eq!(
+            Resolution::for_broker_order(&trade, Some(&cancelled_after_fill)),
+            Resolution::MissingFills
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use super::default_account;
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use trading_base::TradeIntent;
+
+/// Kafka header of trade messages that carries their `OrderExtensions` as JSON
+pub const ORDER_EXTENSIONS_HEADER: &str = "order-extensions";
+/// Kafka header with the version of the `OrderExtensions` schema
+pub const ORDER_EXTENSIONS_VERSION_HEADER: &str = "order-extensions-version";
+pub const ORDER_EXTENSIONS_VERSION: &str = "1";
+
+/// Order options that a `TradeIntent` can't express.
+///
+/// Trade messages stay plain `TradeMessage`s, and the extensions of an order are sent alongside
+/// them in the `order-extensions` header. The intent is always a valid order on its own, so that
+/// consumers that don't read the header still trade it: a fractional quantity is sent as the
+/// quantity rounded away from zero, a notional order as the number of shares it was estimated at
+/// and a trailing stop as a stop at its initial stop price.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct OrderExtensions {
+    /// Quantity of a fractional order, negative for sells. Replaces the quantity of the intent.
+    #[serde(default)]
+    pub quantity: Option<Decimal>,
+    /// Dollar amount of a notional market order, negative for sells. Replaces the quantity of the
+    /// intent.
+    #[serde(default)]
+    pub notional: Option<Decimal>,
+    /// Trail of a trailing stop in dollars
+    #[serde(default)]
+    pub trail_price: Option<Decimal>,
+    /// Trail of a trailing stop as a percentage of the price, e.g. 1.5 for 1.5%
+    #[serde(default)]
+    pub trail_percent: Option<Decimal>,
+    /// Whether the order may also execute in the pre- and post-market sessions
+    #[serde(default)]
+    pub extended_hours: bool,
+}
+
+impl OrderExtensions {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether the order can be worked in slices. Slices are sent with their own quantity and no
+    /// other extensions.
+    pub fn can_be_sliced(&self) -> bool {
+        self.notional.is_none() && self.trail_price.is_none() && self.trail_percent.is_none() && !self.extended_hours
+    }
+}
+
+/// A trade intent together with the extensions it is sent with and the broker account it is
+/// sent to.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TradeOrder {
+    pub intent: TradeIntent,
+    #[serde(default)]
+    pub extensions: OrderExtensions,
+    #[serde(default = "default_account")]
+    pub account: String,
+}
+
+impl TradeOrder {
+    pub fn new(intent: TradeIntent) -> Self {
+        Self {
+            intent,
+            extensions: OrderExtensions::default(),
+            account: default_account(),
+        }
+    }
+
+    pub fn with_account(mut self, account: String) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// The number of shares to trade, which is an estimate for notional orders
+    pub fn quantity(&self) -> Decimal {
+        self.extensions
+            .quantity
+            .unwrap_or_else(|| Decimal::from(self.intent.qty))
+    }
+
+    /// Resizes the order to `quantity` shares. Fractional quantities are sent as an extension,
+    /// and a notional order becomes an order for shares.
+    pub fn with_quantity(mut self, quantity: Decimal) -> Result<Self> {
+        self.intent.qty = quantity
+            .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero)
+            .to_isize()
+            .ok_or_else(|| anyhow!("Failed to convert decimal"))?;
+        self.extensions.quantity = Some(quantity).filter(|quantity| !quantity.fract().is_zero());
+        self.extensions.notional = None;
+        Ok(self)
+    }
+
+    pub fn with_notional(mut self, notional: Decimal) -> Self {
+        self.extensions.notional = Some(notional);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quantity() {
+        let order = TradeOrder::new(TradeIntent::new("AAPL", 0));
+        let fractional = order.clone().with_quantity(Decimal::new(-25, 1)).unwrap();
+        assert_eq!(fractional.intent.qty, -3);
+        assert_eq!(fractional.quantity(), Decimal::new(-25, 1));
+        let whole = fractional.with_quantity(Decimal::new(20, 1)).unwrap();
+        assert_eq!(whole.intent.qty, 2);
+        assert_eq!(whole.quantity(), Decimal::TWO);
+        assert!(whole.extensions.is_default());
+        let notional = order.with_notional(Decimal::ONE_HUNDRED);
+        assert!(!notional.extensions.can_be_sliced());
+        let resized = notional.with_quantity(Decimal::ONE).unwrap();
+        assert_eq!(resized.extensions.notional, None);
+    }
+
+    #[test]
+    fn test_extensions_header() {
+        let extensions = OrderExtensions {
+            trail_percent: Some(Decimal::new(15, 1)),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&extensions).unwrap();
+        assert_eq!(serde_json::from_str::<OrderExtensions>(&json).unwrap(), extensions);
+        // Fields added in later versions are optional
+        let extensions: OrderExtensions = serde_json::from_str(r#"{"extended_hours":true}"#).unwrap();
+        assert!(extensions.extended_hours);
+        assert!(!extensions.is_default());
+    }
+}
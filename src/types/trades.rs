@@ -1,8 +1,11 @@
+use super::default_account;
 use alpaca::{Order, OrderStatus, Side};
 use chrono::{DateTime, Utc};
 use postgres_types::{FromSql, ToSql};
+use rust_decimal::Decimal;
 use serde::Serialize;
 use std::convert::TryFrom;
+use std::fmt::{Display, Error, Formatter};
 use tokio_postgres::Row;
 use uuid::Uuid;
 
@@ -24,20 +27,63 @@ pub enum Status {
     Dead,
 }
 
+impl Status {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Status::Filled | Status::Cancelled | Status::Dead)
+    }
+
+    /// Whether a trade may move from this status to `next`. Trades never leave a terminal status
+    /// or move backwards, but repeated acceptances and partial fills are allowed.
+    pub fn can_transition_to(&self, next: Status) -> bool {
+        match (self, next) {
+            (from, _) if from.is_terminal() => false,
+            (_, Status::Unreported) => false,
+            (Status::PartiallyFilled, Status::Accepted) => false,
+            _ => true,
+        }
+    }
+}
+
+/// A status update that the trade state machine does not allow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub id: Uuid,
+    pub from: Status,
+    pub to: Status,
+}
+
+impl Display for InvalidTransition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "Invalid transition of trade {} from {:?} to {:?}",
+            self.id, self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Trade {
     pub id: Uuid,
     pub broker_id: Option<Uuid>,
     pub ticker: String,
-    pub quantity: i32,
-    pub pending_quantity: i32,
+    pub quantity: Decimal,
+    pub pending_quantity: Decimal,
     pub datetime: DateTime<Utc>,
     pub status: Status,
+    pub cancel_requested: bool,
+    pub filled_quantity: Decimal,
+    pub average_fill_price: Option<Decimal>,
+    pub fees: Decimal,
+    /// The broker account the trade was sent to
+    pub account: String,
 }
 
 impl Trade {
     #[tracing::instrument(skip(id, ticker, quantity))]
-    pub fn new(id: Uuid, ticker: String, quantity: i32) -> Self {
+    pub fn new(id: Uuid, ticker: String, quantity: Decimal) -> Self {
         tracing::trace!(%id, %ticker, %quantity, "New Trade");
         Self {
             id,
@@ -47,27 +93,66 @@ impl Trade {
             pending_quantity: quantity,
             datetime: Utc::now(),
             status: Status::Unreported,
+            cancel_requested: false,
+            filled_quantity: Decimal::ZERO,
+            average_fill_price: None,
+            fees: Decimal::ZERO,
+            account: default_account(),
         }
     }
 
-    pub fn accepted(&mut self) {
-        self.status = Status::Accepted;
+    pub fn transition(&mut self, next: Status) -> Result<(), InvalidTransition> {
+        if !self.status.can_transition_to(next) {
+            return Err(InvalidTransition {
+                id: self.id,
+                from: self.status,
+                to: next,
+            });
+        }
+        self.status = next;
+        Ok(())
+    }
+
+    pub fn accepted(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(Status::Accepted)
     }
 
-    pub fn partially_filled(&mut self) {
-        self.status = Status::PartiallyFilled;
+    pub fn partially_filled(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(Status::PartiallyFilled)
     }
 
-    pub fn filled(&mut self) {
-        self.status = Status::Filled;
+    pub fn filled(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(Status::Filled)
     }
 
-    pub fn cancelled(&mut self) {
-        self.status = Status::Cancelled;
+    pub fn cancelled(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(Status::Cancelled)
     }
 
-    pub fn dead(&mut self) {
-        self.status = Status::Dead;
+    pub fn dead(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(Status::Dead)
+    }
+
+    /// Records a fill of `quantity` shares (negative for sales) at `price`, updating the
+    /// volume-weighted average fill price.
+    pub fn record_fill(&mut self, quantity: Decimal, price: Decimal, fees: Decimal) {
+        let filled_quantity = self.filled_quantity + quantity;
+        if !filled_quantity.is_zero() {
+            let previous_cost = self.average_fill_price.unwrap_or_default() * self.filled_quantity;
+            let cost = previous_cost + price * quantity;
+            self.average_fill_price = Some(cost / filled_quantity);
+        }
+        self.filled_quantity = filled_quantity;
+        self.fees += fees;
+    }
+
+    /// Applies an update reported by the broker, rejecting it if the status transition is not
+    /// allowed.
+    pub fn apply_update(&mut self, update: &Trade) -> Result<(), InvalidTransition> {
+        self.transition(update.status)?;
+        self.broker_id = update.broker_id;
+        self.pending_quantity = update.pending_quantity;
+        Ok(())
     }
 
     pub fn set_broker_id(&mut self, broker_id: Uuid) {
@@ -84,9 +169,11 @@ impl Trade {
 
 impl From<Order> for Trade {
     fn from(order: Order) -> Trade {
+        let quantity = order.qty;
+        let pending_quantity = quantity - order.filled_qty;
         let (quantity, pending_quantity) = match order.side {
-            Side::Buy => (order.qty as i32, (order.qty - order.filled_qty) as i32),
-            Side::Sell => (-(order.qty as i32), -((order.qty - order.filled_qty) as i32)),
+            Side::Buy => (quantity, pending_quantity),
+            Side::Sell => (-quantity, -pending_quantity),
         };
         let status = match order.status {
             OrderStatus::Canceled => Status::Cancelled,
@@ -106,6 +193,11 @@ impl From<Order> for Trade {
             pending_quantity,
             datetime: order.created_at,
             status,
+            cancel_requested: false,
+            filled_quantity: Decimal::ZERO,
+            average_fill_price: None,
+            fees: Decimal::ZERO,
+            account: default_account(),
         }
     }
 }
@@ -121,6 +213,182 @@ impl TryFrom<Row> for Trade {
             pending_quantity: row.try_get("pending_quantity")?,
             datetime: row.try_get("datetime")?,
             status: row.try_get("status")?,
+            cancel_requested: row.try_get("cancel_requested")?,
+            filled_quantity: row.try_get("filled_quantity")?,
+            average_fill_price: row.try_get("average_fill_price")?,
+            fees: row.try_get("fees")?,
+            account: row.try_get("account")?,
+        })
+    }
+}
+
+/// A status change of a trade, or a rejected attempt at one.
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeEvent {
+    pub trade_id: Uuid,
+    pub from_status: Option<Status>,
+    pub to_status: Status,
+    pub rejected: bool,
+    pub datetime: DateTime<Utc>,
+}
+
+impl TradeEvent {
+    pub fn new(trade_id: Uuid, from_status: Option<Status>, to_status: Status) -> Self {
+        Self {
+            trade_id,
+            from_status,
+            to_status,
+            rejected: false,
+            datetime: Utc::now(),
+        }
+    }
+}
+
+impl From<InvalidTransition> for TradeEvent {
+    fn from(error: InvalidTransition) -> Self {
+        Self {
+            trade_id: error.id,
+            from_status: Some(error.from),
+            to_status: error.to,
+            rejected: true,
+            datetime: Utc::now(),
+        }
+    }
+}
+
+impl TryFrom<Row> for TradeEvent {
+    type Error = tokio_postgres::Error;
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            trade_id: row.try_get("trade_id")?,
+            from_status: row.try_get("from_status")?,
+            to_status: row.try_get("to_status")?,
+            rejected: row.try_get("rejected")?,
+            datetime: row.try_get("datetime")?,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATUSES: [Status; 6] = [
+        Status::Unreported,
+        Status::Accepted,
+        Status::PartiallyFilled,
+        Status::Filled,
+        Status::Cancelled,
+        Status::Dead,
+    ];
+
+    fn rank(status: Status) -> usize {
+        match status {
+            Status::Unreported => 0,
+            Status::Accepted => 1,
+            Status::PartiallyFilled => 2,
+            Status::Filled | Status::Cancelled | Status::Dead => 3,
+        }
+    }
+
+    /// All sequences of statuses of the given length
+    fn sequences(length: usize) -> Vec<Vec<Status>> {
+        (0..length).fold(vec![vec![]], |sequences, _| {
+            sequences
+                .into_iter()
+                .flat_map(|sequence| {
+                    STATUSES.iter().map(move |status| {
+                        let mut sequence = sequence.clone();
+                        sequence.push(*status);
+                        sequence
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn permutations(events: &[Status]) -> Vec<Vec<Status>> {
+        if events.len() <= 1 {
+            return vec![events.to_vec()];
+        }
+        (0..events.len())
+            .flat_map(|i| {
+                let mut rest = events.to_vec();
+                let first = rest.remove(i);
+                permutations(&rest).into_iter().map(move |mut permutation| {
+                    permutation.insert(0, first);
+                    permutation
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transitions_never_regress() {
+        for sequence in sequences(5) {
+            let mut trade = Trade::new(Uuid::new_v4(), "AAPL".into(), Decimal::ONE_HUNDRED);
+            for status in sequence {
+                let before = trade.status;
+                match trade.transition(status) {
+                    Ok(()) => {
+                        assert!(!before.is_terminal(), "{:?} -> {:?}", before, status);
+                        assert!(rank(status) >= rank(before), "{:?} -> {:?}", before, status);
+                        assert_eq!(trade.status, status);
+                    }
+                    Err(error) => {
+                        assert_eq!(trade.status, before);
+                        assert_eq!(error.from, before);
+                        assert_eq!(error.to, status);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_event_orderings_reach_terminal_status() {
+        let events = [
+            Status::Accepted,
+            Status::PartiallyFilled,
+            Status::PartiallyFilled,
+            Status::Filled,
+            Status::Cancelled,
+        ];
+        for ordering in permutations(&events) {
+            let mut trade = Trade::new(Uuid::new_v4(), "AAPL".into(), Decimal::ONE_HUNDRED);
+            let first_terminal = *ordering.iter().find(|status| status.is_terminal()).unwrap();
+            for status in ordering.iter() {
+                let _ = trade.transition(*status);
+            }
+            assert_eq!(trade.status, first_terminal, "{:?}", ordering);
+        }
+    }
+
+    #[test]
+    fn test_record_fill() {
+        let mut trade = Trade::new(Uuid::new_v4(), "AAPL".into(), Decimal::new(-300, 0));
+        trade.record_fill(Decimal::new(-100, 0), Decimal::new(10, 0), Decimal::new(5, 2));
+        trade.record_fill(Decimal::new(-1995, 1), Decimal::new(13, 0), Decimal::new(7, 2));
+        trade.record_fill(Decimal::new(-5, 1), Decimal::new(13, 0), Decimal::ZERO);
+        assert_eq!(trade.filled_quantity, Decimal::new(-300, 0));
+        assert_eq!(trade.average_fill_price, Some(Decimal::new(12, 0)));
+        assert_eq!(trade.fees, Decimal::new(12, 2));
+    }
+
+    #[test]
+    fn test_apply_update() {
+        let id = Uuid::new_v4();
+        let mut trade = Trade::new(id, "AAPL".into(), Decimal::ONE_HUNDRED);
+        let mut update = Trade::new(id, "AAPL".into(), Decimal::ONE_HUNDRED);
+        update.status = Status::PartiallyFilled;
+        update.pending_quantity = Decimal::new(40, 0);
+        update.broker_id = Some(Uuid::new_v4());
+        trade.apply_update(&update).unwrap();
+        assert_eq!(trade.pending_quantity, Decimal::new(40, 0));
+        assert_eq!(trade.broker_id, update.broker_id);
+        update.status = Status::Accepted;
+        update.pending_quantity = Decimal::ONE_HUNDRED;
+        assert!(trade.apply_update(&update).is_err());
+        assert_eq!(trade.pending_quantity, Decimal::new(40, 0));
+    }
+}
@@ -0,0 +1,434 @@
+use super::TimeInForce;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use trading_base::{OrderType, TradeIntent};
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Session {
+    PreMarket,
+    Regular,
+    PostMarket,
+    Closed,
+}
+
+/// Trading days and session hours of the exchange, in New York time.
+///
+/// The pre-market session opens at 4:00 and the post-market session runs until four hours after
+/// the close, which is 16:00 unless the day has an early close.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TradingCalendar {
+    #[serde(default)]
+    holidays: HashSet<NaiveDate>,
+    #[serde(default)]
+    early_closes: HashMap<NaiveDate, NaiveTime>,
+    /// Whether the regular NYSE holidays and early closes apply in addition to the listed ones
+    #[serde(default = "default_nyse_rules")]
+    nyse_rules: bool,
+}
+
+fn default_nyse_rules() -> bool {
+    true
+}
+
+impl TradingCalendar {
+    pub fn new(holidays: HashSet<NaiveDate>, early_closes: HashMap<NaiveDate, NaiveTime>) -> Self {
+        Self {
+            holidays,
+            early_closes,
+            nyse_rules: false,
+        }
+    }
+
+    /// The NYSE calendar, with its regular holidays and early closes.
+    pub fn nyse() -> Self {
+        Self {
+            nyse_rules: true,
+            ..Default::default()
+        }
+    }
+
+    /// Loads holidays and early closes from a JSON file, for example
+    /// `{"holidays": ["2021-12-24"], "early_closes": {"2021-11-26": "13:00:00"}}`. These are
+    /// added to the NYSE holidays and early closes unless the file sets `"nyse_rules": false`.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        let calendar = serde_json::from_str(&contents).context("Failed to parse trading calendar")?;
+        Ok(calendar)
+    }
+
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && !self.holidays.contains(&date)
+            && !(self.nyse_rules && is_nyse_holiday(date))
+    }
+
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut date = date.succ();
+        while !self.is_trading_day(date) {
+            date = date.succ();
+        }
+        date
+    }
+
+    pub fn market_open(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.to_utc(date.and_time(NaiveTime::from_hms(9, 30, 0)))
+    }
+
+    pub fn market_close(&self, date: NaiveDate) -> DateTime<Utc> {
+        let close = self
+            .early_closes
+            .get(&date)
+            .copied()
+            .or_else(|| Some(NaiveTime::from_hms(13, 0, 0)).filter(|_| self.nyse_rules && is_nyse_early_close(date)))
+            .unwrap_or_else(|| NaiveTime::from_hms(16, 0, 0));
+        self.to_utc(date.and_time(close))
+    }
+
+    pub fn session(&self, at: DateTime<Utc>) -> Session {
+        let date = self.to_local(at).date();
+        if !self.is_trading_day(date) {
+            return Session::Closed;
+        }
+        let pre_market_open = self.to_utc(date.and_time(NaiveTime::from_hms(4, 0, 0)));
+        let close = self.market_close(date);
+        if at < pre_market_open {
+            Session::Closed
+        } else if at < self.market_open(date) {
+            Session::PreMarket
+        } else if at < close {
+            Session::Regular
+        } else if at < close + Duration::hours(4) {
+            Session::PostMarket
+        } else {
+            Session::Closed
+        }
+    }
+
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        self.session(at) == Session::Regular
+    }
+
+    /// The start of the regular session that is open at `at`, or of the next one.
+    pub fn next_open(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let date = self.to_local(at).date();
+        if self.is_trading_day(date) && at < self.market_close(date) {
+            self.market_open(date)
+        } else {
+            self.market_open(self.next_trading_day(date))
+        }
+    }
+
+    /// Orders for the opening auction are accepted from 19:00 until 9:28, for the next open.
+    pub fn accepts_opening_auction_orders(&self, at: DateTime<Utc>) -> bool {
+        let time = self.to_local(at).time();
+        time >= NaiveTime::from_hms(19, 0, 0) || time < NaiveTime::from_hms(9, 28, 0)
+    }
+
+    /// Converts a New York local time to UTC.
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let offset = new_york_offset(local.date());
+        offset
+            .from_local_datetime(&local)
+            .single()
+            .expect("Fixed offsets are unambiguous")
+            .with_timezone(&Utc)
+    }
+
+    /// Converts a UTC time to New York local time.
+    pub fn to_local(&self, datetime: DateTime<Utc>) -> NaiveDateTime {
+        // The UTC date only differs from the local date in the evening, so converting with its
+        // offset gives the right local date to look up the actual offset with
+        let local = datetime.with_timezone(&new_york_offset(datetime.naive_utc().date()));
+        datetime
+            .with_timezone(&new_york_offset(local.naive_local().date()))
+            .naive_local()
+    }
+}
+
+/// Whether a trade can be sent while the regular session is closed, which is the case for all
+/// but market orders, and for market orders in the opening or closing auction.
+pub fn can_send_outside_market_hours(trade: &TradeIntent) -> Result<bool> {
+    if trade.order_type != OrderType::Market {
+        return Ok(true);
+    }
+    Ok(matches!(TimeInForce::of(trade)?, TimeInForce::Opg | TimeInForce::Cls))
+}
+
+/// Regular NYSE holidays. Fixed-date holidays falling on a Saturday are observed on the Friday
+/// before, and those falling on a Sunday on the Monday after, except that New Year's Day is not
+/// observed on the last trading day of the previous year.
+fn is_nyse_holiday(date: NaiveDate) -> bool {
+    let year = date.year();
+    let new_years_day = NaiveDate::from_ymd(year, 1, 1);
+    let observed_new_years_day = match new_years_day.weekday() {
+        Weekday::Sun => Some(new_years_day.succ()),
+        Weekday::Sat => None,
+        _ => Some(new_years_day),
+    };
+    let mut holidays = vec![
+        nth_weekday(year, 1, Weekday::Mon, 3),
+        nth_weekday(year, 2, Weekday::Mon, 3),
+        easter_sunday(year) - Duration::days(2),
+        last_weekday(year, 5, Weekday::Mon),
+        observed(NaiveDate::from_ymd(year, 7, 4)),
+        nth_weekday(year, 9, Weekday::Mon, 1),
+        nth_weekday(year, 11, Weekday::Thu, 4),
+        observed(NaiveDate::from_ymd(year, 12, 25)),
+    ];
+    holidays.extend(observed_new_years_day);
+    if year >= 2022 {
+        holidays.push(observed(NaiveDate::from_ymd(year, 6, 19)));
+    }
+    holidays.contains(&date)
+}
+
+/// The NYSE closes at 13:00 on the day before Independence Day, the day after Thanksgiving and
+/// Christmas Eve, when these are trading days.
+fn is_nyse_early_close(date: NaiveDate) -> bool {
+    let year = date.year();
+    let early_closes = [
+        NaiveDate::from_ymd(year, 7, 3),
+        nth_weekday(year, 11, Weekday::Thu, 4).succ(),
+        NaiveDate::from_ymd(year, 12, 24),
+    ];
+    early_closes.contains(&date) && !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !is_nyse_holiday(date)
+}
+
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date.pred(),
+        Weekday::Sun => date.succ(),
+        _ => date,
+    }
+}
+
+/// Easter Sunday in the Gregorian calendar, using the anonymous Gregorian algorithm.
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd(year, month as u32, day as u32)
+}
+
+/// UTC offset of New York on the given date. Daylight saving time runs from the second Sunday in
+/// March to the first Sunday in November.
+fn new_york_offset(date: NaiveDate) -> FixedOffset {
+    let dst_start = nth_weekday(date.year(), 3, Weekday::Sun, 2);
+    let dst_end = nth_weekday(date.year(), 11, Weekday::Sun, 1);
+    if date >= dst_start && date < dst_end {
+        FixedOffset::west(4 * 3600)
+    } else {
+        FixedOffset::west(5 * 3600)
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> NaiveDate {
+    let first = NaiveDate::from_ymd(year, month, 1);
+    let days_to_weekday = (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+    first + Duration::days((days_to_weekday + 7 * (n - 1)) as i64)
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd(year, month + 1, 1)
+    };
+    let last = next_month.pred();
+    let days_since_weekday = (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    last - Duration::days(days_since_weekday as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::OrderInstructions;
+
+    #[test]
+    fn test_market_hours_follow_daylight_saving() {
+        let calendar = TradingCalendar::default();
+        // Standard time
+        assert_eq!(
+            calendar.market_open(NaiveDate::from_ymd(2021, 3, 12)),
+            Utc.ymd(2021, 3, 12).and_hms(14, 30, 0)
+        );
+        // Daylight saving time starts on Sunday March 14th 2021
+        assert_eq!(
+            calendar.market_open(NaiveDate::from_ymd(2021, 3, 15)),
+            Utc.ymd(2021, 3, 15).and_hms(13, 30, 0)
+        );
+        // And ends on Sunday November 7th 2021
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2021, 11, 5)),
+            Utc.ymd(2021, 11, 5).and_hms(20, 0, 0)
+        );
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2021, 11, 8)),
+            Utc.ymd(2021, 11, 8).and_hms(21, 0, 0)
+        );
+        assert_eq!(
+            calendar.to_local(Utc.ymd(2021, 11, 9).and_hms(3, 0, 0)),
+            NaiveDate::from_ymd(2021, 11, 8).and_hms(22, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_trading_days() {
+        let holiday = NaiveDate::from_ymd(2021, 7, 5);
+        let calendar = TradingCalendar::new(vec![holiday].into_iter().collect(), HashMap::new());
+        assert!(!calendar.is_trading_day(holiday));
+        assert!(!calendar.is_trading_day(NaiveDate::from_ymd(2021, 7, 3)));
+        assert_eq!(
+            calendar.next_trading_day(NaiveDate::from_ymd(2021, 7, 2)),
+            NaiveDate::from_ymd(2021, 7, 6)
+        );
+    }
+
+    #[test]
+    fn test_sessions() {
+        let date = NaiveDate::from_ymd(2021, 11, 26);
+        let calendar = TradingCalendar::new(
+            HashSet::new(),
+            vec![(date, NaiveTime::from_hms(13, 0, 0))].into_iter().collect(),
+        );
+        assert_eq!(
+            calendar.session(Utc.ymd(2021, 11, 26).and_hms(8, 0, 0)),
+            Session::Closed
+        );
+        assert_eq!(
+            calendar.session(Utc.ymd(2021, 11, 26).and_hms(10, 0, 0)),
+            Session::PreMarket
+        );
+        assert_eq!(
+            calendar.session(Utc.ymd(2021, 11, 26).and_hms(15, 0, 0)),
+            Session::Regular
+        );
+        // Early close at 13:00 New York time
+        assert_eq!(
+            calendar.session(Utc.ymd(2021, 11, 26).and_hms(18, 30, 0)),
+            Session::PostMarket
+        );
+        assert_eq!(
+            calendar.session(Utc.ymd(2021, 11, 26).and_hms(22, 30, 0)),
+            Session::Closed
+        );
+        assert_eq!(
+            calendar.next_open(Utc.ymd(2021, 11, 26).and_hms(18, 30, 0)),
+            Utc.ymd(2021, 11, 29).and_hms(14, 30, 0)
+        );
+        assert_eq!(
+            calendar.next_open(Utc.ymd(2021, 11, 26).and_hms(15, 0, 0)),
+            Utc.ymd(2021, 11, 26).and_hms(14, 30, 0)
+        );
+    }
+
+    #[test]
+    fn test_nyse_holidays() {
+        let calendar = TradingCalendar::nyse();
+        let holidays = [
+            NaiveDate::from_ymd(2021, 1, 1),
+            NaiveDate::from_ymd(2021, 1, 18),
+            NaiveDate::from_ymd(2021, 2, 15),
+            NaiveDate::from_ymd(2021, 4, 2),
+            NaiveDate::from_ymd(2021, 5, 31),
+            NaiveDate::from_ymd(2021, 7, 5),
+            NaiveDate::from_ymd(2021, 9, 6),
+            NaiveDate::from_ymd(2021, 11, 25),
+            NaiveDate::from_ymd(2021, 12, 24),
+            NaiveDate::from_ymd(2022, 4, 15),
+            NaiveDate::from_ymd(2022, 6, 20),
+            NaiveDate::from_ymd(2023, 1, 2),
+        ];
+        for holiday in holidays.iter() {
+            assert!(!calendar.is_trading_day(*holiday), "{} is a holiday", holiday);
+        }
+        // New Year's Day on a Saturday is not observed on the Friday before
+        assert!(calendar.is_trading_day(NaiveDate::from_ymd(2021, 12, 31)));
+        // Juneteenth is only a holiday from 2022
+        assert!(calendar.is_trading_day(NaiveDate::from_ymd(2021, 6, 18)));
+        assert!(calendar.is_trading_day(NaiveDate::from_ymd(2021, 11, 26)));
+        assert!(TradingCalendar::default().is_trading_day(NaiveDate::from_ymd(2021, 11, 25)));
+    }
+
+    #[test]
+    fn test_nyse_early_closes() {
+        let calendar = TradingCalendar::nyse();
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2021, 11, 26)),
+            Utc.ymd(2021, 11, 26).and_hms(18, 0, 0)
+        );
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2023, 7, 3)),
+            Utc.ymd(2023, 7, 3).and_hms(17, 0, 0)
+        );
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2022, 12, 23)),
+            Utc.ymd(2022, 12, 23).and_hms(21, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_calendar() {
+        let calendar: TradingCalendar =
+            serde_json::from_str(r#"{"holidays":["2021-12-24"],"early_closes":{"2021-11-26":"13:00:00"}}"#).unwrap();
+        assert!(!calendar.is_trading_day(NaiveDate::from_ymd(2021, 12, 24)));
+        assert_eq!(
+            calendar.market_close(NaiveDate::from_ymd(2021, 11, 26)),
+            Utc.ymd(2021, 11, 26).and_hms(18, 0, 0)
+        );
+        // Holidays in the file are added to the NYSE holidays
+        assert!(!calendar.is_trading_day(NaiveDate::from_ymd(2021, 11, 25)));
+        let calendar: TradingCalendar = serde_json::from_str(r#"{"nyse_rules":false}"#).unwrap();
+        assert!(calendar.is_trading_day(NaiveDate::from_ymd(2021, 11, 25)));
+    }
+
+    #[test]
+    fn test_opening_auction_window() {
+        let calendar = TradingCalendar::default();
+        // 9:27 and 9:28 New York time
+        assert!(calendar.accepts_opening_auction_orders(Utc.ymd(2021, 11, 26).and_hms(14, 27, 59)));
+        assert!(!calendar.accepts_opening_auction_orders(Utc.ymd(2021, 11, 26).and_hms(14, 28, 0)));
+        // 18:59 and 19:00 New York time
+        assert!(!calendar.accepts_opening_auction_orders(Utc.ymd(2021, 11, 26).and_hms(23, 59, 0)));
+        assert!(calendar.accepts_opening_auction_orders(Utc.ymd(2021, 11, 27).and_hms(0, 0, 0)));
+        // 19:00 New York time during daylight saving time
+        assert!(calendar.accepts_opening_auction_orders(Utc.ymd(2021, 7, 6).and_hms(23, 0, 0)));
+    }
+
+    #[test]
+    fn test_market_orders_outside_hours() {
+        assert!(!can_send_outside_market_hours(&TradeIntent::new("AAPL", 10)).unwrap());
+        let limit = TradeIntent::new("AAPL", 10).order_type(OrderType::Limit {
+            limit_price: rust_decimal::Decimal::ONE,
+        });
+        assert!(can_send_outside_market_hours(&limit).unwrap());
+        for time_in_force in [TimeInForce::Opg, TimeInForce::Cls].iter() {
+            let instructions = OrderInstructions {
+                time_in_force: Some(*time_in_force),
+                ..Default::default()
+            };
+            let auction = instructions.apply_time_in_force(TradeIntent::new("AAPL", 10)).unwrap();
+            assert!(can_send_outside_market_hours(&auction).unwrap());
+        }
+        let instructions = OrderInstructions {
+            time_in_force: Some(TimeInForce::Gtc),
+            ..Default::default()
+        };
+        let gtc = instructions.apply_time_in_force(TradeIntent::new("AAPL", 10)).unwrap();
+        assert!(!can_send_outside_market_hours(&gtc).unwrap());
+    }
+}
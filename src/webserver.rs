@@ -1,19 +1,49 @@
 use crate::db;
-use crate::types::Owner;
+use crate::intent_scheduler::ScheduleCommand;
+use crate::order_manager::{get_last_price, Command};
+use crate::types::{
+    AccountRoute, Budget, CashMovement, CashTransfer, CorporateAction, ExecutionPolicy, HouseActionState, Nav, Owner,
+    RecurringIntent,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio_postgres::Client;
 use uuid::Uuid;
 use warp::reply::{json, Reply};
-use warp::{any, body, get, path, put, reject, serve, Filter, Rejection};
+use warp::{any, body, delete, get, path, post, put, reject, serve, Filter, Rejection};
 
 type Db = Arc<Client>;
+type Scheduler = UnboundedSender<ScheduleCommand>;
+type Commands = UnboundedSender<Command>;
+type DatastoreUrl = Arc<String>;
 
 fn with_db(db: Db) -> impl Filter<Extract = (Db,), Error = Infallible> + Clone {
     any().map(move || db.clone())
 }
 
+fn with_scheduler(scheduler: Scheduler) -> impl Filter<Extract = (Scheduler,), Error = Infallible> + Clone {
+    any().map(move || scheduler.clone())
+}
+
+fn with_commands(commands: Commands) -> impl Filter<Extract = (Commands,), Error = Infallible> + Clone {
+    any().map(move || commands.clone())
+}
+
+fn with_datastore_url(url: DatastoreUrl) -> impl Filter<Extract = (DatastoreUrl,), Error = Infallible> + Clone {
+    any().map(move || url.clone())
+}
+
+#[derive(Debug, Deserialize)]
+struct Reschedule {
+    after: DateTime<Utc>,
+}
+
 #[tracing::instrument(skip(db))]
 async fn get_allocations(db: Db) -> Result<impl Reply, Rejection> {
     let allocations = db::get_allocations(db.as_ref()).await.map_err(|_| reject())?;
@@ -40,6 +70,18 @@ async fn get_claims(db: Db) -> Result<impl Reply, Rejection> {
     Ok(json(&claims))
 }
 
+#[tracing::instrument(skip(db))]
+async fn get_claim_history(db: Db) -> Result<impl Reply, Rejection> {
+    let claims = db::get_claim_history(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&claims))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_claim_trades(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let trades = db::get_claim_trades(db.as_ref(), id).await.map_err(|_| reject())?;
+    Ok(json(&trades))
+}
+
 #[tracing::instrument(skip(db))]
 async fn get_trades(db: Db) -> Result<impl Reply, Rejection> {
     let trades = db::get_trades(db.as_ref()).await.map_err(|_| reject())?;
@@ -47,7 +89,310 @@ async fn get_trades(db: Db) -> Result<impl Reply, Rejection> {
 }
 
 #[tracing::instrument(skip(db))]
-pub async fn run(port: u16, db: Db) {
+async fn get_trade_events(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let events = db::get_trade_events(db.as_ref(), id).await.map_err(|_| reject())?;
+    Ok(json(&events))
+}
+
+/// Counters in the Prometheus text format
+#[tracing::instrument(skip(db))]
+async fn get_metrics(db: Db) -> Result<impl Reply, Rejection> {
+    let rejected = db::count_rejected_trade_events(db.as_ref())
+        .await
+        .map_err(|_| reject())?;
+    Ok(format!(
+        "# TYPE order_manager_rejected_trade_transitions_total counter\norder_manager_rejected_trade_transitions_total {}\n",
+        rejected
+    ))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_budgets(db: Db) -> Result<impl Reply, Rejection> {
+    let budgets = db::get_budgets(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&budgets))
+}
+
+#[tracing::instrument(skip(db))]
+async fn set_budget(budget: Budget, db: Db) -> Result<impl Reply, Rejection> {
+    db::upsert_budget(db.as_ref(), &budget).await.map_err(|_| reject())?;
+    Ok(json(&budget))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_budget_denials(db: Db) -> Result<impl Reply, Rejection> {
+    let denials = db::get_budget_denials(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&denials))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_cash_balances(db: Db) -> Result<impl Reply, Rejection> {
+    let balances = db::get_cash_balances(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&balances))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_cash_ledger(db: Db) -> Result<impl Reply, Rejection> {
+    let ledger = db::get_cash_ledger(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&ledger))
+}
+
+#[tracing::instrument(skip(db))]
+async fn save_cash_movement(movement: CashMovement, db: Db) -> Result<impl Reply, Rejection> {
+    if movement.amount.is_zero() {
+        return Err(reject());
+    }
+    db::save_cash_movement(db.as_ref(), &movement)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&movement))
+}
+
+#[tracing::instrument(skip(db))]
+async fn save_cash_transfer(transfer: CashTransfer, db: Db) -> Result<impl Reply, Rejection> {
+    if transfer.amount <= Decimal::ZERO || transfer.from == transfer.to {
+        return Err(reject());
+    }
+    db::save_cash_transfer(db.as_ref(), &transfer)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&transfer))
+}
+
+#[tracing::instrument(skip(db, datastore_url))]
+async fn get_nav(db: Db, datastore_url: DatastoreUrl) -> Result<impl Reply, Rejection> {
+    let balances = db::get_cash_balances(db.as_ref()).await.map_err(|_| reject())?;
+    let positions = db::get_positions(db.as_ref()).await.map_err(|_| reject())?;
+    let mut prices = HashMap::new();
+    for position in positions.iter() {
+        if prices.contains_key(&position.ticker) {
+            continue;
+        }
+        // Positions without a price are valued at their basis
+        if let Ok(price) = get_last_price(&datastore_url, &position.ticker).await {
+            prices.insert(position.ticker.clone(), price);
+        }
+    }
+    Ok(json(&Nav::from_balances(&balances, &positions, &prices)))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_execution_policies(db: Db) -> Result<impl Reply, Rejection> {
+    let policies = db::get_execution_policies(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&policies))
+}
+
+#[tracing::instrument(skip(db))]
+async fn set_execution_policy(policy: ExecutionPolicy, db: Db) -> Result<impl Reply, Rejection> {
+    policy.validate().map_err(|_| reject())?;
+    db::upsert_execution_policy(db.as_ref(), &policy)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&policy))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_account_routes(db: Db) -> Result<impl Reply, Rejection> {
+    let routes = db::get_account_routes(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&routes))
+}
+
+#[tracing::instrument(skip(db))]
+async fn set_account_route(route: AccountRoute, db: Db) -> Result<impl Reply, Rejection> {
+    db::upsert_account_route(db.as_ref(), &route)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&route))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_executions(db: Db) -> Result<impl Reply, Rejection> {
+    let executions = db::get_executions(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&executions))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_queued_trades(db: Db) -> Result<impl Reply, Rejection> {
+    let queued_trades = db::get_queued_trades(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&queued_trades))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_stale_trade_resolutions(db: Db) -> Result<impl Reply, Rejection> {
+    let resolutions = db::get_stale_trade_resolutions(db.as_ref())
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&resolutions))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_corporate_actions(db: Db) -> Result<impl Reply, Rejection> {
+    let actions = db::get_corporate_actions(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&actions))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_corporate_action_adjustments(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let adjustments = db::get_corporate_action_adjustments(db.as_ref(), id)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&adjustments))
+}
+
+#[tracing::instrument(skip(db, commands))]
+async fn apply_corporate_action(action: CorporateAction, db: Db, commands: Commands) -> Result<impl Reply, Rejection> {
+    action.validate().map_err(|_| reject())?;
+    let adjusted = db::apply_corporate_action(db.as_ref(), &action)
+        .await
+        .map_err(|_| reject())?;
+    // The action marks the working trades in the ticker for cancellation, the order manager owns
+    // the connections needed to send the cancels
+    let cancelled = db::get_corporate_action_cancelled_trades(db.as_ref(), action.id)
+        .await
+        .map_err(|_| reject())?;
+    for id in cancelled {
+        commands.send(Command::CancelTrade { id }).map_err(|_| reject())?;
+    }
+    Ok(json(&adjusted))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_house_actions(db: Db) -> Result<impl Reply, Rejection> {
+    let actions = db::get_house_actions(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&actions))
+}
+
+#[tracing::instrument(skip(db))]
+async fn approve_house_action(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    // The order manager applies approved actions when it next reconciles
+    let approved = db::transition_house_action(db.as_ref(), id, HouseActionState::Pending, HouseActionState::Approved)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&approved))
+}
+
+#[tracing::instrument(skip(db))]
+async fn reject_house_action(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let rejected = db::transition_house_action(db.as_ref(), id, HouseActionState::Pending, HouseActionState::Rejected)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&rejected))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_dependent_trades(db: Db) -> Result<impl Reply, Rejection> {
+    let dependent_trades = db::get_dependent_trades(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&dependent_trades))
+}
+
+#[tracing::instrument(skip(db))]
+async fn cancel_dependent_trade(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let cancelled = db::cancel_dependent_trade(db.as_ref(), id)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&cancelled))
+}
+
+#[tracing::instrument(skip(commands))]
+async fn release_dependent_trade(id: Uuid, commands: Commands) -> Result<impl Reply, Rejection> {
+    // The order manager owns the connections needed to send the trade
+    commands
+        .send(Command::ReleaseDependentTrade { id })
+        .map_err(|_| reject())?;
+    Ok(json(&id))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_scheduled_intents(db: Db) -> Result<impl Reply, Rejection> {
+    let intents = db::get_scheduled_intents(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&intents))
+}
+
+#[tracing::instrument(skip(db, scheduler))]
+async fn cancel_scheduled_intent(id: Uuid, db: Db, scheduler: Scheduler) -> Result<impl Reply, Rejection> {
+    let cancelled = db::cancel_scheduled_intent(db.as_ref(), id)
+        .await
+        .map_err(|_| reject())?;
+    if cancelled {
+        scheduler.send(ScheduleCommand::Cancel { id }).map_err(|_| reject())?;
+    }
+    Ok(json(&cancelled))
+}
+
+#[tracing::instrument(skip(db, scheduler))]
+async fn cancel_strategy_scheduled_intents(
+    strategy: String,
+    db: Db,
+    scheduler: Scheduler,
+) -> Result<impl Reply, Rejection> {
+    let ids = db::cancel_scheduled_intents_by_strategy(db.as_ref(), &strategy)
+        .await
+        .map_err(|_| reject())?;
+    for id in ids.iter() {
+        scheduler
+            .send(ScheduleCommand::Cancel { id: *id })
+            .map_err(|_| reject())?;
+    }
+    Ok(json(&ids))
+}
+
+#[tracing::instrument(skip(db, scheduler))]
+async fn reschedule_scheduled_intent(
+    id: Uuid,
+    reschedule: Reschedule,
+    db: Db,
+    scheduler: Scheduler,
+) -> Result<impl Reply, Rejection> {
+    let rescheduled = db::reschedule_scheduled_intent(db.as_ref(), id, reschedule.after)
+        .await
+        .map_err(|_| reject())?;
+    if rescheduled {
+        scheduler
+            .send(ScheduleCommand::Reschedule {
+                id,
+                at: reschedule.after,
+            })
+            .map_err(|_| reject())?;
+    }
+    Ok(json(&rescheduled))
+}
+
+#[tracing::instrument(skip(db))]
+async fn get_recurring_intents(db: Db) -> Result<impl Reply, Rejection> {
+    let recurring_intents = db::get_recurring_intents(db.as_ref()).await.map_err(|_| reject())?;
+    Ok(json(&recurring_intents))
+}
+
+#[tracing::instrument(skip(db, scheduler))]
+async fn set_recurring_intent(
+    recurring_intent: RecurringIntent,
+    db: Db,
+    scheduler: Scheduler,
+) -> Result<impl Reply, Rejection> {
+    recurring_intent.schedule.validate().map_err(|_| reject())?;
+    db::upsert_recurring_intent(db.as_ref(), &recurring_intent)
+        .await
+        .map_err(|_| reject())?;
+    // The order manager calculates the first run once the scheduler releases it
+    scheduler
+        .send(ScheduleCommand::RecurringIntent {
+            id: recurring_intent.id,
+            at: Utc::now(),
+        })
+        .map_err(|_| reject())?;
+    Ok(json(&recurring_intent))
+}
+
+#[tracing::instrument(skip(db))]
+async fn delete_recurring_intent(id: Uuid, db: Db) -> Result<impl Reply, Rejection> {
+    let deleted = db::delete_recurring_intent(db.as_ref(), id)
+        .await
+        .map_err(|_| reject())?;
+    Ok(json(&deleted))
+}
+
+#[tracing::instrument(skip(db, scheduler, commands))]
+pub async fn run(port: u16, db: Db, scheduler: Scheduler, commands: Commands, datastore_url: String) {
+    let datastore_url = Arc::new(datastore_url);
     let health = path!("health").map(|| "");
     let get_allocations = path("allocations")
         .and(get())
@@ -60,17 +405,207 @@ pub async fn run(port: u16, db: Db) {
         .and_then(set_allocation_owner);
     let lots = path("lots").and(get()).and(with_db(db.clone())).and_then(get_lots);
     let claims = path("claims").and(get()).and(with_db(db.clone())).and_then(get_claims);
+    let claim_history = path!("claims" / "history")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_claim_history);
+    let claim_trades = path!("claims" / Uuid / "trades")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_claim_trades);
     let pending_trades = path("pending_trades")
         .and(get())
         .and(with_db(db.clone()))
         .and_then(get_trades);
+    let trade_events = path!("trades" / Uuid / "events")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_trade_events);
+    let metrics = path!("metrics")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_metrics);
+    let get_budgets = path!("budgets")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_budgets);
+    let set_budget = path!("budgets")
+        .and(put())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and_then(set_budget);
+    let budget_denials = path!("budget_denials")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_budget_denials);
+    let cash_balances = path!("cash")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_cash_balances);
+    let cash_ledger = path!("cash" / "ledger")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_cash_ledger);
+    let cash_movement = path!("cash" / "movements")
+        .and(post())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and_then(save_cash_movement);
+    let cash_transfer = path!("cash" / "transfers")
+        .and(post())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and_then(save_cash_transfer);
+    let nav = path!("nav")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and(with_datastore_url(datastore_url))
+        .and_then(get_nav);
+    let get_execution_policies = path!("execution_policies")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_execution_policies);
+    let set_execution_policy = path!("execution_policies")
+        .and(put())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and_then(set_execution_policy);
+    let get_account_routes = path!("account_routes")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_account_routes);
+    let set_account_route = path!("account_routes")
+        .and(put())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and_then(set_account_route);
+    let executions = path!("executions")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_executions);
+    let queued_trades = path!("queued_trades")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_queued_trades);
+    let stale_trade_resolutions = path!("stale_trade_resolutions")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_stale_trade_resolutions);
+    let get_corporate_actions = path!("corporate_actions")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_corporate_actions);
+    let get_corporate_action_adjustments = path!("corporate_actions" / Uuid / "adjustments")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_corporate_action_adjustments);
+    let apply_corporate_action = path!("corporate_actions")
+        .and(post())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and(with_commands(commands.clone()))
+        .and_then(apply_corporate_action);
+    let get_house_actions = path!("house_actions")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_house_actions);
+    let approve_house_action = path!("house_actions" / Uuid / "approve")
+        .and(post())
+        .and(with_db(db.clone()))
+        .and_then(approve_house_action);
+    let reject_house_action = path!("house_actions" / Uuid / "reject")
+        .and(post())
+        .and(with_db(db.clone()))
+        .and_then(reject_house_action);
+    let get_dependent_trades = path!("dependent_trades")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_dependent_trades);
+    let cancel_dependent_trade = path!("dependent_trades" / Uuid)
+        .and(delete())
+        .and(with_db(db.clone()))
+        .and_then(cancel_dependent_trade);
+    let release_dependent_trade = path!("dependent_trades" / Uuid / "release")
+        .and(post())
+        .and(with_commands(commands))
+        .and_then(release_dependent_trade);
+    let get_scheduled_intents = path!("scheduled_intents")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_scheduled_intents);
+    let cancel_scheduled_intent = path!("scheduled_intents" / Uuid)
+        .and(delete())
+        .and(with_db(db.clone()))
+        .and(with_scheduler(scheduler.clone()))
+        .and_then(cancel_scheduled_intent);
+    let cancel_strategy_scheduled_intents = path!("scheduled_intents" / "strategy" / String)
+        .and(delete())
+        .and(with_db(db.clone()))
+        .and(with_scheduler(scheduler.clone()))
+        .and_then(cancel_strategy_scheduled_intents);
+    let reschedule_scheduled_intent = path!("scheduled_intents" / Uuid)
+        .and(put())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and(with_scheduler(scheduler.clone()))
+        .and_then(reschedule_scheduled_intent);
+    let get_recurring_intents = path!("recurring_intents")
+        .and(get())
+        .and(with_db(db.clone()))
+        .and_then(get_recurring_intents);
+    let set_recurring_intent = path!("recurring_intents")
+        .and(put())
+        .and(body::json())
+        .and(with_db(db.clone()))
+        .and(with_scheduler(scheduler))
+        .and_then(set_recurring_intent);
+    let delete_recurring_intent = path!("recurring_intents" / Uuid)
+        .and(delete())
+        .and(with_db(db.clone()))
+        .and_then(delete_recurring_intent);
     let routes = get()
         .and(health)
         .or(get_allocations)
         .or(set_allocation_owner)
         .or(lots)
+        // Before `claims`, which also matches the longer paths
+        .or(claim_history)
+        .or(claim_trades)
         .or(claims)
-        .or(pending_trades);
+        .or(pending_trades)
+        .or(trade_events)
+        .or(metrics)
+        .or(get_budgets)
+        .or(set_budget)
+        .or(budget_denials)
+        .or(cash_balances)
+        .or(cash_ledger)
+        .or(cash_movement)
+        .or(cash_transfer)
+        .or(nav)
+        .or(get_execution_policies)
+        .or(set_execution_policy)
+        .or(get_account_routes)
+        .or(set_account_route)
+        .or(executions)
+        .or(queued_trades)
+        .or(stale_trade_resolutions)
+        .or(get_corporate_actions)
+        .or(get_corporate_action_adjustments)
+        .or(apply_corporate_action)
+        .or(get_house_actions)
+        .or(approve_house_action)
+        .or(reject_house_action)
+        .or(get_dependent_trades)
+        .or(cancel_dependent_trade)
+        .or(release_dependent_trade)
+        .or(get_scheduled_intents)
+        .or(cancel_scheduled_intent)
+        .or(cancel_strategy_scheduled_intents)
+        .or(reschedule_scheduled_intent)
+        .or(get_recurring_intents)
+        .or(set_recurring_intent)
+        .or(delete_recurring_intent);
     let address = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
     serve(routes).run(address).await
 }
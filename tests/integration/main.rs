@@ -2,14 +2,16 @@ use anyhow::{anyhow, Result};
 use chrono::{Duration, Utc};
 use futures::FutureExt;
 use rdkafka::consumer::StreamConsumer;
+use rdkafka::message::Headers;
 use rdkafka::producer::{FutureProducer, FutureRecord};
 use rdkafka::Message;
 use risk_manager::RiskCheckResponse;
 use rust_decimal::Decimal;
 use tracing::info;
 use trading_base::{Amount, Identifier, OrderType, PositionIntent, TradeIntent, TradeMessage, UpdatePolicy};
+use uuid::Uuid;
 
-use order_manager::types::{Allocation, Claim, Lot, Owner};
+use order_manager::types::{AccountRoute, Allocation, Claim, CorporateAction, CorporateActionKind, Lot, Owner};
 use order_manager::Event;
 use order_message::*;
 use setup::setup;
@@ -55,7 +57,7 @@ async fn receive_event(consumer: &StreamConsumer) -> Result<Event> {
             let intent: TradeIntent = serde_json::from_slice(payload)?;
             Event::RiskCheckRequest(intent)
         }
-        "trade-intents" => {
+        "trade-intents" | "trade-intents-paper" => {
             let message: TradeMessage = serde_json::from_slice(payload)?;
             Event::TradeMessage(message)
         }
@@ -176,10 +178,10 @@ async fn test_1(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 100,
+        qty: 100.0,
+        position_qty: 100.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: None,
@@ -212,10 +214,10 @@ async fn test_2(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 50,
-        position_qty: 150,
+        qty: 50.0,
+        position_qty: 150.0,
         price: 100.0,
-        filled_qty: 50,
+        filled_qty: 50.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: None,
@@ -246,10 +248,10 @@ async fn test_3(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 150,
-        position_qty: 0,
+        qty: 150.0,
+        position_qty: 0.0,
         price: 100.0,
-        filled_qty: 150,
+        filled_qty: 150.0,
         filled_avg_price: 100.0,
         side: Side::Sell,
         limit_price: None,
@@ -272,10 +274,10 @@ async fn test_3(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 100,
+        qty: 100.0,
+        position_qty: 100.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Sell,
         limit_price: None,
@@ -307,10 +309,10 @@ async fn test_4(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 1,
-        position_qty: -101,
+        qty: 1.0,
+        position_qty: -101.0,
         price: 100.0,
-        filled_qty: 1,
+        filled_qty: 1.0,
         filled_avg_price: 100.0,
         side: Side::Sell,
         limit_price: None,
@@ -340,10 +342,10 @@ async fn test_5(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 101,
-        position_qty: 0,
+        qty: 101.0,
+        position_qty: 0.0,
         price: 100.0,
-        filled_qty: 101,
+        filled_qty: 101.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: None,
@@ -385,10 +387,10 @@ async fn test_6(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 100,
+        qty: 100.0,
+        position_qty: 100.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: Some(100.0),
@@ -439,10 +441,10 @@ async fn test_7(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 0,
+        qty: 100.0,
+        position_qty: 0.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Sell,
         limit_price: None,
@@ -490,10 +492,10 @@ async fn test_9(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 100,
+        qty: 100.0,
+        position_qty: 100.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: None,
@@ -522,10 +524,10 @@ async fn test_10(producer: &FutureProducer, consumer: &StreamConsumer) -> Result
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 0,
+        qty: 100.0,
+        position_qty: 0.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Sell,
         limit_price: None,
@@ -568,10 +570,10 @@ async fn test_11(producer: &FutureProducer, consumer: &StreamConsumer) -> Result
         client_order_id,
         event_type: EventType::Fill,
         ticker: "AAPL",
-        qty: 100,
-        position_qty: 200,
+        qty: 100.0,
+        position_qty: 200.0,
         price: 100.0,
-        filled_qty: 100,
+        filled_qty: 100.0,
         filled_avg_price: 100.0,
         side: Side::Buy,
         limit_price: None,
@@ -585,6 +587,529 @@ async fn test_11(producer: &FutureProducer, consumer: &StreamConsumer) -> Result
     Ok(())
 }
 
+/// A fill reported after the trade was cancelled is still booked, while the trade stays cancelled
+/// and the rejected transition is counted.
+async fn test_12(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("S7", "SNAP", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (_claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    let client_order_id = trade_intent.id;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    let _trade_intent = receive_event(&consumer).await?;
+    let cancel_message = OrderMessage {
+        client_order_id,
+        event_type: EventType::Cancel,
+        ticker: "SNAP",
+        qty: 10.0,
+        position_qty: 0.0,
+        price: 10.0,
+        filled_qty: 0.0,
+        filled_avg_price: 0.0,
+        side: Side::Buy,
+        limit_price: None,
+    };
+    send_order_message(&producer, &cancel_message).await?;
+    let fill_message = OrderMessage {
+        event_type: EventType::Fill,
+        position_qty: 10.0,
+        filled_qty: 10.0,
+        filled_avg_price: 10.0,
+        ..cancel_message
+    };
+    send_order_message(&producer, &fill_message).await?;
+    let (lot, allocation) = receive_lot_and_allocation(&consumer).await?;
+    assert_eq!(lot.shares, Decimal::TEN);
+    assert_eq!(allocation.shares, Decimal::TEN);
+    assert_eq!(allocation.owner, Owner::Strategy("S7".into(), None));
+
+    let events: Vec<serde_json::Value> =
+        reqwest::get(format!("http://localhost:8127/trades/{}/events", client_order_id))
+            .await?
+            .json()
+            .await?;
+    let last = events.last().ok_or_else(|| anyhow!("Missing trade events"))?;
+    assert_eq!(last["from_status"], "cancelled");
+    assert_eq!(last["to_status"], "filled");
+    assert_eq!(last["rejected"], true);
+    let metrics = reqwest::get("http://localhost:8127/metrics").await?.text().await?;
+    assert!(metrics.contains("order_manager_rejected_trade_transitions_total"));
+    assert!(!metrics.contains("order_manager_rejected_trade_transitions_total 0\n"));
+    Ok(())
+}
+
+/// Tickers traded in fractions are sent, filled and allocated in fractional shares
+async fn test_13(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("S13", "TSLA", Amount::Shares(Decimal::new(25, 1))).build()?,
+    )
+    .await?;
+    let (claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    assert_eq!(claim.amount, Amount::Shares(Decimal::new(25, 1)));
+    // Consumers that don't read the extensions see the quantity rounded away from zero
+    assert_eq!(trade_intent.qty, 3);
+    let client_order_id = trade_intent.id;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    let msg = consumer.recv().await?;
+    let headers = msg.headers().ok_or_else(|| anyhow!("Missing order extensions"))?;
+    let extensions = (0..headers.count())
+        .filter_map(|i| headers.get(i))
+        .find(|(name, _)| *name == "order-extensions")
+        .map(|(_, value)| serde_json::from_slice::<serde_json::Value>(value))
+        .transpose()?
+        .ok_or_else(|| anyhow!("Missing order extensions"))?;
+    assert_eq!(extensions["quantity"], "2.5");
+    let fill_message = OrderMessage {
+        client_order_id,
+        event_type: EventType::Fill,
+        ticker: "TSLA",
+        qty: 2.5,
+        position_qty: 2.5,
+        price: 100.0,
+        filled_qty: 2.5,
+        filled_avg_price: 100.0,
+        side: Side::Buy,
+        limit_price: None,
+    };
+    send_order_message(&producer, &fill_message).await?;
+    let (lot, allocation) = receive_lot_and_allocation(&consumer).await?;
+    assert_eq!(lot.shares, Decimal::new(25, 1));
+    assert_eq!(allocation.shares, Decimal::new(25, 1));
+    assert_eq!(allocation.owner, Owner::Strategy("S13".into(), None));
+    Ok(())
+}
+
+async fn scheduled_intent_ids() -> Result<Vec<String>> {
+    let intents: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/scheduled_intents")
+        .await?
+        .json()
+        .await?;
+    Ok(intents
+        .into_iter()
+        .filter_map(|intent| intent["id"].as_str().map(ToString::to_string))
+        .collect())
+}
+
+/// Scheduled intents can be listed, rescheduled and cancelled through the API, one at a time or
+/// for a whole strategy.
+async fn test_14(producer: &FutureProducer) -> Result<()> {
+    let intent = PositionIntent::builder("S8", "UBER", Amount::Shares(Decimal::new(10, 0)))
+        .after(Utc::now() + Duration::hours(1))
+        .build()?;
+    let id = intent.id;
+    send_position(&producer, &intent).await?;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    assert!(scheduled_intent_ids().await?.contains(&id.to_string()));
+
+    let client = reqwest::Client::new();
+    let rescheduled: bool = client
+        .put(format!("http://localhost:8127/scheduled_intents/{}", id))
+        .json(&serde_json::json!({ "after": Utc::now() + Duration::hours(2) }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(rescheduled);
+    let cancelled: bool = client
+        .delete(format!("http://localhost:8127/scheduled_intents/{}", id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(cancelled);
+    assert!(!scheduled_intent_ids().await?.contains(&id.to_string()));
+    let cancelled: bool = client
+        .delete(format!("http://localhost:8127/scheduled_intents/{}", id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(!cancelled);
+
+    let mut ids = Vec::new();
+    for ticker in ["UBER", "LYFT"].iter() {
+        let intent = PositionIntent::builder("S9", *ticker, Amount::Shares(Decimal::new(10, 0)))
+            .after(Utc::now() + Duration::hours(1))
+            .build()?;
+        ids.push(intent.id);
+        send_position(&producer, &intent).await?;
+    }
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let mut cancelled: Vec<Uuid> = client
+        .delete("http://localhost:8127/scheduled_intents/strategy/S9")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    cancelled.sort();
+    ids.sort();
+    assert_eq!(cancelled, ids);
+    let remaining = scheduled_intent_ids().await?;
+    assert!(ids.iter().all(|id| !remaining.contains(&id.to_string())));
+    Ok(())
+}
+
+async fn send_market_state(producer: &FutureProducer, state: &str) -> Result<()> {
+    let payload = match state {
+        "open" => r#"{"state":"open","next_close":710}"#,
+        _ => r#"{"state":"closed","next_open":710}"#,
+    };
+    let record = FutureRecord::to("time").key("").payload(payload);
+    producer
+        .send_result(record)
+        .map_err(|(e, m)| anyhow!("{:?}\n{:?}", e, m))?
+        .await?
+        .map_err(|(e, m)| anyhow!("{:?}\n{:?}", e, m))?;
+    // The clock is consumed from its own topic, so give it time to be applied
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    Ok(())
+}
+
+/// A market order granted after the market closed is queued instead of sent, and sent without
+/// another risk check once the market opens.
+async fn test_15(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("S10", "PINS", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (_claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    let id = trade_intent.id;
+    send_market_state(producer, "closed").await?;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let queued: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/queued_trades")
+        .await?
+        .json()
+        .await?;
+    let queued = queued
+        .into_iter()
+        .find(|queued| queued["id"] == id.to_string())
+        .ok_or_else(|| anyhow!("Missing queued trade {}", id))?;
+    assert_eq!(queued["risk_checked"], true);
+
+    send_market_state(producer, "open").await?;
+    // Reconciliation may send trades for other tickers when the market opens
+    for _ in 0..10 {
+        if let Event::TradeMessage(TradeMessage::New { intent }) = receive_event(&consumer).await? {
+            if intent.id == id {
+                return Ok(());
+            }
+        }
+    }
+    Err(anyhow!("Queued trade {} was not sent", id))
+}
+
+async fn receive_claim(consumer: &StreamConsumer) -> Result<Claim> {
+    match receive_event(&consumer).await? {
+        Event::Claim(claim) => Ok(claim),
+        x => Err(anyhow!("Unexpected event: was expecting a Claim. Event {:?}", x)),
+    }
+}
+
+/// Trades generated while the market is closed are queued before their risk check, and trades of
+/// replaced claims are dropped when the queue is released at the open.
+async fn test_16(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_market_state(producer, "closed").await?;
+    send_position(
+        &producer,
+        &PositionIntent::builder("S11", "ZM", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let claim = receive_claim(&consumer).await?;
+    assert_eq!(claim.ticker, "ZM");
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let queued: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/queued_trades")
+        .await?
+        .json()
+        .await?;
+    let replaced = queued
+        .into_iter()
+        .find(|queued| queued["claim_id"] == claim.id.to_string())
+        .ok_or_else(|| anyhow!("Missing queued trade for claim {}", claim.id))?;
+    assert_eq!(replaced["risk_checked"], false);
+
+    send_position(
+        &producer,
+        &PositionIntent::builder("S11", "ZM", Amount::Shares(Decimal::new(15, 0))).build()?,
+    )
+    .await?;
+    receive_claim(&consumer).await?;
+
+    send_market_state(producer, "open").await?;
+    // Reconciliation may request risk checks for other tickers when the market opens
+    for _ in 0..10 {
+        if let Event::RiskCheckRequest(intent) = receive_event(&consumer).await? {
+            if intent.ticker == "ZM" {
+                assert_ne!(intent.id.to_string(), replaced["id"]);
+                assert_eq!(intent.qty, 15);
+                return Ok(());
+            }
+        }
+    }
+    Err(anyhow!("Queued trade for ZM was not released"))
+}
+
+async fn cancel_requested(id: Uuid) -> Result<bool> {
+    let trades: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/pending_trades")
+        .await?
+        .json()
+        .await?;
+    trades
+        .into_iter()
+        .find(|trade| trade["id"] == id.to_string())
+        .and_then(|trade| trade["cancel_requested"].as_bool())
+        .ok_or_else(|| anyhow!("Missing trade {}", id))
+}
+
+/// Replacing the claim of one strategy only cancels the working trades sent for that claim, and
+/// leaves the trades of other strategies in the ticker working.
+async fn test_17(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("S5", "NFLX", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (_claim, _s5_request) = receive_claim_and_risk_check_request(&consumer).await?;
+    send_position(
+        &producer,
+        &PositionIntent::builder("S6", "NFLX", Amount::Shares(Decimal::new(20, 0))).build()?,
+    )
+    .await?;
+    let (_claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    let s6_trade_id = trade_intent.id;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    let _trade_intent = receive_event(&consumer).await?;
+
+    send_position(
+        &producer,
+        &PositionIntent::builder("S5", "NFLX", Amount::Shares(Decimal::new(15, 0))).build()?,
+    )
+    .await?;
+    match receive_event(&consumer).await? {
+        Event::Claim(claim) => assert_eq!(claim.strategy, "S5"),
+        event => return Err(anyhow!("Unexpected event {:?}", event)),
+    }
+    assert!(!cancel_requested(s6_trade_id).await?);
+
+    send_position(
+        &producer,
+        &PositionIntent::builder("S6", "NFLX", Amount::Shares(Decimal::new(25, 0))).build()?,
+    )
+    .await?;
+    match receive_event(&consumer).await? {
+        Event::Claim(claim) => assert_eq!(claim.strategy, "S6"),
+        event => return Err(anyhow!("Unexpected event {:?}", event)),
+    }
+    assert!(cancel_requested(s6_trade_id).await?);
+    Ok(())
+}
+
+/// A claim replaced by a new intent is moved to the claim history as superseded, while the new
+/// claim takes its place.
+async fn test_18(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("S12", "SHOP", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (superseded, _) = receive_claim_and_risk_check_request(&consumer).await?;
+    send_position(
+        &producer,
+        &PositionIntent::builder("S12", "SHOP", Amount::Shares(Decimal::new(20, 0))).build()?,
+    )
+    .await?;
+    let mut replacement = None;
+    for _ in 0..10 {
+        if let Event::Claim(claim) = receive_event(&consumer).await? {
+            if claim.ticker == "SHOP" {
+                replacement = Some(claim);
+                break;
+            }
+        }
+    }
+    let replacement = replacement.ok_or_else(|| anyhow!("Missing replacement claim"))?;
+    assert_ne!(replacement.id, superseded.id);
+
+    let history: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/claims/history")
+        .await?
+        .json()
+        .await?;
+    let archived: Vec<_> = history
+        .iter()
+        .filter(|claim| claim["id"] == superseded.id.to_string())
+        .collect();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0]["state"], "superseded");
+    let claims: Vec<serde_json::Value> = reqwest::get("http://localhost:8127/claims").await?.json().await?;
+    let live: Vec<_> = claims
+        .iter()
+        .filter(|claim| claim["strategy"] == "S12" && claim["ticker"] == "SHOP")
+        .collect();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0]["id"], replacement.id.to_string());
+    Ok(())
+}
+
+/// Claims with and without a sub-strategy are replaced by later intents for the same strategy,
+/// sub-strategy and ticker, but not by each other
+async fn test_19(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    for sub_strategy in vec![None, Some("A".to_string())] {
+        let mut claim_ids = Vec::new();
+        for shares in vec![100, 50] {
+            let mut intent = PositionIntent::builder("S3", "MSFT", Amount::Shares(Decimal::new(shares, 0))).build()?;
+            intent.sub_strategy = sub_strategy.clone();
+            send_position(&producer, &intent).await?;
+            let (claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+            assert_eq!(claim.sub_strategy, sub_strategy);
+            assert_eq!(trade_intent.qty, shares as isize);
+            claim_ids.push(claim.id);
+        }
+        assert_ne!(claim_ids[0], claim_ids[1]);
+    }
+    let claims: Vec<Claim> = reqwest::get("http://localhost:8127/claims").await?.json().await?;
+    let claims: Vec<_> = claims.into_iter().filter(|claim| claim.strategy == "S3").collect();
+    assert_eq!(claims.len(), 2);
+    assert!(claims.iter().any(|claim| claim.sub_strategy.is_none()));
+    assert!(claims
+        .iter()
+        .all(|claim| claim.amount == Amount::Shares(Decimal::new(50, 0))));
+    Ok(())
+}
+
+/// A strategy named House keeps its allocations apart from the house account.
+async fn test_20(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    send_position(
+        &producer,
+        &PositionIntent::builder("House", "GE", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (_claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    let client_order_id = trade_intent.id;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    let _trade_intent = receive_event(&consumer).await?;
+    let fill_message = OrderMessage {
+        client_order_id,
+        event_type: EventType::Fill,
+        ticker: "GE",
+        qty: 10.0,
+        position_qty: 10.0,
+        price: 100.0,
+        filled_qty: 10.0,
+        filled_avg_price: 100.0,
+        side: Side::Buy,
+        limit_price: None,
+    };
+    send_order_message(&producer, &fill_message).await?;
+    let (_lot, allocation) = receive_lot_and_allocation(&consumer).await?;
+    assert_eq!(allocation.owner, Owner::Strategy("House".into(), None));
+    let allocations: Vec<Allocation> = reqwest::get("http://localhost:8127/allocations").await?.json().await?;
+    let allocations: Vec<_> = allocations.into_iter().filter(|a| a.ticker == "GE").collect();
+    assert_eq!(allocations.len(), 1);
+    assert_eq!(allocations[0].owner, Owner::Strategy("House".into(), None));
+    Ok(())
+}
+
+/// Trades of a strategy that is routed to another account are sent to the topic of that account,
+/// and their fills are allocated in it.
+async fn test_21(producer: &FutureProducer, consumer: &StreamConsumer) -> Result<()> {
+    let route = AccountRoute {
+        strategy: "S4".into(),
+        sub_strategy: None,
+        account: "paper".into(),
+    };
+    reqwest::Client::new()
+        .put("http://localhost:8127/account_routes")
+        .json(&route)
+        .send()
+        .await?
+        .error_for_status()?;
+    send_position(
+        &producer,
+        &PositionIntent::builder("S4", "IBM", Amount::Shares(Decimal::new(10, 0))).build()?,
+    )
+    .await?;
+    let (claim, trade_intent) = receive_claim_and_risk_check_request(&consumer).await?;
+    assert_eq!(claim.account, "paper");
+    let client_order_id = trade_intent.id;
+    let response = RiskCheckResponse::Granted { intent: trade_intent };
+    send_risk_check_response(producer, &response).await?;
+    let msg = consumer.recv().await?;
+    assert_eq!(msg.topic(), "trade-intents-paper");
+    let fill_message = OrderMessage {
+        client_order_id,
+        event_type: EventType::Fill,
+        ticker: "IBM",
+        qty: 10.0,
+        position_qty: 10.0,
+        price: 100.0,
+        filled_qty: 10.0,
+        filled_avg_price: 100.0,
+        side: Side::Buy,
+        limit_price: None,
+    };
+    send_order_message(&producer, &fill_message).await?;
+    let (lot, allocation) = receive_lot_and_allocation(&consumer).await?;
+    assert_eq!(lot.account, "paper");
+    assert_eq!(allocation.account, "paper");
+    assert_eq!(allocation.owner, Owner::Strategy("S4".into(), None));
+    Ok(())
+}
+
+/// A split adjusts the shares of the existing allocations and the prices of the lots.
+async fn test_22() -> Result<()> {
+    let action = CorporateAction {
+        id: Uuid::new_v4(),
+        ticker: "IBM".into(),
+        kind: CorporateActionKind::Split {
+            from: Decimal::ONE,
+            to: Decimal::new(4, 0),
+        },
+        applied_at: Utc::now(),
+    };
+    let adjusted: u64 = reqwest::Client::new()
+        .post("http://localhost:8127/corporate_actions")
+        .json(&action)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(adjusted > 0);
+    let allocations: Vec<Allocation> = reqwest::get("http://localhost:8127/allocations").await?.json().await?;
+    let allocation = allocations
+        .into_iter()
+        .find(|a| a.ticker == "IBM" && a.owner == Owner::Strategy("S4".into(), None))
+        .ok_or_else(|| anyhow!("Missing allocation"))?;
+    assert_eq!(allocation.shares, Decimal::new(40, 0));
+    let lots: Vec<Lot> = reqwest::get("http://localhost:8127/lots").await?.json().await?;
+    let lot = lots
+        .into_iter()
+        .find(|l| l.id == allocation.lot_id)
+        .ok_or_else(|| anyhow!("Missing lot"))?;
+    assert_eq!(lot.price, Decimal::new(25, 0));
+    // Applying the same action again is rejected
+    let response = reqwest::Client::new()
+        .post("http://localhost:8127/corporate_actions")
+        .json(&action)
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+    Ok(())
+}
+
 #[tokio::test]
 async fn main() {
     let (admin, admin_options, consumer, producer) = setup().await;
@@ -626,6 +1151,28 @@ async fn main() {
     test_10(&producer, &consumer).await.unwrap();
     info!("TEST 11");
     test_11(&producer, &consumer).await.unwrap();
+    info!("TEST 12");
+    test_12(&producer, &consumer).await.unwrap();
+    info!("TEST 13");
+    test_13(&producer, &consumer).await.unwrap();
+    info!("TEST 14");
+    test_14(&producer).await.unwrap();
+    info!("TEST 15");
+    test_15(&producer, &consumer).await.unwrap();
+    info!("TEST 16");
+    test_16(&producer, &consumer).await.unwrap();
+    info!("TEST 17");
+    test_17(&producer, &consumer).await.unwrap();
+    info!("TEST 18");
+    test_18(&producer, &consumer).await.unwrap();
+    info!("TEST 19");
+    test_19(&producer, &consumer).await.unwrap();
+    info!("TEST 20");
+    test_20(&producer, &consumer).await.unwrap();
+    info!("TEST 21");
+    test_21(&producer, &consumer).await.unwrap();
+    info!("TEST 22");
+    test_22().await.unwrap();
 
     teardown(&admin, &admin_options).await;
 }
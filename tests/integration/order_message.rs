@@ -7,12 +7,15 @@ use uuid::Uuid;
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     Fill,
+    #[serde(rename = "canceled")]
+    Cancel,
 }
 
 impl EventType {
     fn format_for_order(&self) -> &'static str {
         match self {
             Self::Fill => "filled",
+            Self::Cancel => "canceled",
         }
     }
 }
@@ -28,10 +31,10 @@ pub struct OrderMessage {
     pub client_order_id: Uuid,
     pub event_type: EventType,
     pub ticker: &'static str,
-    pub qty: usize,
-    pub position_qty: isize,
+    pub qty: f64,
+    pub position_qty: f64,
     pub price: f64,
-    pub filled_qty: usize,
+    pub filled_qty: f64,
     pub filled_avg_price: f64,
     pub side: Side,
     pub limit_price: Option<f64>,
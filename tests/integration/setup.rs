@@ -39,6 +39,7 @@ pub async fn setup() -> (
                 NewTopic::new("risk-check-request", 1, TopicReplication::Fixed(1)),
                 NewTopic::new("risk-check-response", 1, TopicReplication::Fixed(1)),
                 NewTopic::new("trade-intents", 1, TopicReplication::Fixed(1)),
+                NewTopic::new("trade-intents-paper", 1, TopicReplication::Fixed(1)),
                 NewTopic::new("time", 1, TopicReplication::Fixed(1)),
             ],
             &admin_options,
@@ -66,6 +67,7 @@ pub async fn setup() -> (
             &"lots",
             &"risk-check-request",
             &"trade-intents",
+            &"trade-intents-paper",
         ])
         .unwrap();
     consumer
@@ -78,6 +80,7 @@ pub async fn setup() -> (
     let database_name = "order-manager";
     tokio::spawn(async move {
         std::env::set_var("APP__UNREPORTED_TRADE_EXPIRY_SECONDS", "1");
+        std::env::set_var("APP__FRACTIONAL_TICKERS", "TSLA");
         std::env::set_var("DATABASE__NAME", database_name);
         std::env::set_var("DATABASE__URL", database_address);
         std::env::set_var("DATASTORE__BASE_URL", "http://localhost:9010");